@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE settings DROP COLUMN anthropic_api_key;
+ALTER TABLE settings DROP COLUMN anthropic_url;
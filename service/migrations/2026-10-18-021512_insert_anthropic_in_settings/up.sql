@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE settings ADD COLUMN anthropic_api_key TEXT;
+ALTER TABLE settings ADD COLUMN anthropic_url TEXT;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE settings DROP COLUMN local_servers;
+ALTER TABLE chat_models DROP COLUMN server_id;
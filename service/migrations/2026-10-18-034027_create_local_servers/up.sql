@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE settings ADD COLUMN local_servers TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE chat_models ADD COLUMN server_id BINARY;
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE settings ADD COLUMN azure TEXT;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE settings ADD COLUMN gemini_api_key TEXT;
+ALTER TABLE settings ADD COLUMN gemini_url TEXT;
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE chat_models ADD COLUMN unlisted BOOLEAN NOT NULL DEFAULT FALSE;
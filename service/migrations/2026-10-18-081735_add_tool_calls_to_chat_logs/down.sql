@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE chat_logs DROP COLUMN tool_calls;
+ALTER TABLE chat_logs DROP COLUMN tool_call_id;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE chat_logs ADD COLUMN tool_calls TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE chat_logs ADD COLUMN tool_call_id TEXT;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS auto_delete_chat_log_attachments;
+DROP TABLE IF EXISTS attachments;
@@ -0,0 +1,20 @@
+-- Your SQL goes here
+CREATE TABLE attachments (
+  id BINARY PRIMARY KEY NOT NULL,
+  chat_log_id BINARY NOT NULL,
+  name TEXT NOT NULL,
+  mime_type TEXT NOT NULL,
+  data BLOB NOT NULL,
+  width INT NOT NULL,
+  height INT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX attachments_chat_log_id ON attachments (chat_log_id);
+
+CREATE TRIGGER auto_delete_chat_log_attachments
+  AFTER DELETE ON chat_logs
+  FOR EACH ROW
+  BEGIN
+    DELETE FROM attachments WHERE chat_log_id = OLD.id;
+  END;
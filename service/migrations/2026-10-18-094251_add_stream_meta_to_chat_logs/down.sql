@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE chat_logs DROP COLUMN finish_reason;
+ALTER TABLE chat_logs DROP COLUMN prompt_tokens;
+ALTER TABLE chat_logs DROP COLUMN time_to_first_token;
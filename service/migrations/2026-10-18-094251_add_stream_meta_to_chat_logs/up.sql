@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE chat_logs ADD COLUMN finish_reason TEXT;
+ALTER TABLE chat_logs ADD COLUMN prompt_tokens INT;
+ALTER TABLE chat_logs ADD COLUMN time_to_first_token INT;
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE settings ADD COLUMN retry_policy TEXT NOT NULL DEFAULT '{"maxRetries":3,"initialDelay":1000,"maxDelay":30000}';
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS auto_delete_chat_summaries_of_updated_log;
+DROP TRIGGER IF EXISTS auto_delete_chat_summaries_of_deleted_log;
+DROP TABLE IF EXISTS chat_summaries;
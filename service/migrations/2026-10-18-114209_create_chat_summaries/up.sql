@@ -0,0 +1,41 @@
+-- Your SQL goes here
+CREATE TABLE chat_summaries (
+  id BINARY PRIMARY KEY NOT NULL,
+  chat_id BINARY NOT NULL,
+  content TEXT NOT NULL,
+  first_log_id BINARY NOT NULL,
+  last_log_id BINARY NOT NULL,
+  model TEXT NOT NULL,
+  tokens INT NOT NULL,
+  cost FLOAT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX chat_summaries_chat_id ON chat_summaries (chat_id);
+
+-- A summary is stale once a log of its range is deleted or edited,
+-- the next message regenerates it
+CREATE TRIGGER auto_delete_chat_summaries_of_deleted_log
+  AFTER DELETE ON chat_logs
+  FOR EACH ROW
+  BEGIN
+    DELETE FROM chat_summaries
+    WHERE chat_id = OLD.chat_id
+      AND (
+        OLD.id IN (first_log_id, last_log_id)
+        OR OLD.created_at BETWEEN
+          (SELECT created_at FROM chat_logs WHERE id = first_log_id)
+          AND (SELECT created_at FROM chat_logs WHERE id = last_log_id)
+      );
+  END;
+
+CREATE TRIGGER auto_delete_chat_summaries_of_updated_log
+  AFTER UPDATE OF message ON chat_logs
+  FOR EACH ROW
+  BEGIN
+    DELETE FROM chat_summaries
+    WHERE chat_id = NEW.chat_id
+      AND NEW.created_at BETWEEN
+        (SELECT created_at FROM chat_logs WHERE id = first_log_id)
+        AND (SELECT created_at FROM chat_logs WHERE id = last_log_id);
+  END;
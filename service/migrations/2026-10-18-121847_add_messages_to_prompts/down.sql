@@ -0,0 +1,13 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE prompts ADD COLUMN content TEXT NOT NULL DEFAULT '';
+
+UPDATE prompts
+SET content = COALESCE(
+  (
+    SELECT group_concat(json_extract(value, '$.content'), char(10) || char(10))
+    FROM json_each(prompts.messages)
+  ),
+  ''
+);
+
+ALTER TABLE prompts DROP COLUMN messages;
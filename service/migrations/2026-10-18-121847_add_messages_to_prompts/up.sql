@@ -0,0 +1,8 @@
+-- Your SQL goes here
+ALTER TABLE prompts ADD COLUMN messages TEXT NOT NULL DEFAULT '[]';
+
+UPDATE prompts
+SET messages = json_array(json_object('role', 'system', 'content', content))
+WHERE content != '';
+
+ALTER TABLE prompts DROP COLUMN content;
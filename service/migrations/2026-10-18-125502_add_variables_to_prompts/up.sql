@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE prompts ADD COLUMN variables TEXT NOT NULL DEFAULT '[]';
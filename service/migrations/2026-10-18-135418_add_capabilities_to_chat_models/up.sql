@@ -0,0 +1,10 @@
+-- Your SQL goes here
+ALTER TABLE chat_models ADD COLUMN capabilities TEXT NOT NULL DEFAULT '{}';
+
+-- Reasoning models of OpenAI
+UPDATE chat_models
+SET capabilities = '{"fixedSampling":true,"maxCompletionTokens":true}'
+WHERE name LIKE 'o1%'
+   OR name LIKE 'o3%'
+   OR name LIKE 'o4%'
+   OR (name LIKE 'gpt-5%' AND name NOT LIKE 'gpt-5-chat%');
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX chat_logs_parent_id;
+ALTER TABLE chats DROP COLUMN active_log_id;
+ALTER TABLE chat_logs DROP COLUMN parent_id;
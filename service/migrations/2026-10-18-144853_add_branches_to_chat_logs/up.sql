@@ -0,0 +1,24 @@
+-- Your SQL goes here
+ALTER TABLE chat_logs ADD COLUMN parent_id BLOB;
+ALTER TABLE chats ADD COLUMN active_log_id BLOB;
+
+CREATE INDEX chat_logs_parent_id ON chat_logs (parent_id);
+
+-- Existing history is a single branch, in order of creation
+UPDATE chat_logs
+SET parent_id = (
+  SELECT parent.id FROM chat_logs AS parent
+  WHERE parent.chat_id = chat_logs.chat_id
+    AND (parent.created_at < chat_logs.created_at
+      OR (parent.created_at = chat_logs.created_at AND parent.rowid < chat_logs.rowid))
+  ORDER BY parent.created_at DESC, parent.rowid DESC
+  LIMIT 1
+);
+
+UPDATE chats
+SET active_log_id = (
+  SELECT id FROM chat_logs
+  WHERE chat_logs.chat_id = chats.id
+  ORDER BY created_at DESC, rowid DESC
+  LIMIT 1
+);
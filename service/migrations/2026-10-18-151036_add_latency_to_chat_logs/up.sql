@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE chat_logs ADD COLUMN latency INTEGER;
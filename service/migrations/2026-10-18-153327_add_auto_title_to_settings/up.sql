@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE settings ADD COLUMN auto_title BOOLEAN NOT NULL DEFAULT 1;
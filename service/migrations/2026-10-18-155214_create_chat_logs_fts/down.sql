@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER chat_logs_fts_delete;
+DROP TRIGGER chat_logs_fts_update;
+DROP TRIGGER chat_logs_fts_insert;
+DROP TRIGGER chats_fts_delete;
+DROP TRIGGER chats_fts_update;
+DROP TRIGGER chats_fts_insert;
+DROP TABLE chat_logs_fts;
+DROP TABLE chat_logs_fts_ids;
@@ -0,0 +1,67 @@
+-- Your SQL goes here
+-- Rows of the index, one per chat holding its title and one per log holding its message.
+-- The index is only looked up by its rowid
+CREATE TABLE chat_logs_fts_ids (
+  fts_rowid INTEGER PRIMARY KEY NOT NULL,
+  log_id BINARY UNIQUE,
+  chat_id BINARY NOT NULL
+);
+
+CREATE INDEX chat_logs_fts_ids_chat_id ON chat_logs_fts_ids (chat_id);
+
+CREATE VIRTUAL TABLE chat_logs_fts USING fts5(
+  title,
+  message,
+  tokenize = 'porter unicode61'
+);
+
+INSERT INTO chat_logs_fts_ids (log_id, chat_id)
+SELECT NULL, id FROM chats;
+
+INSERT INTO chat_logs_fts_ids (log_id, chat_id)
+SELECT id, chat_id FROM chat_logs;
+
+INSERT INTO chat_logs_fts (rowid, title)
+SELECT chat_logs_fts_ids.fts_rowid, chats.title
+FROM chat_logs_fts_ids
+JOIN chats ON chats.id = chat_logs_fts_ids.chat_id
+WHERE chat_logs_fts_ids.log_id IS NULL;
+
+INSERT INTO chat_logs_fts (rowid, message)
+SELECT chat_logs_fts_ids.fts_rowid, chat_logs.message
+FROM chat_logs_fts_ids
+JOIN chat_logs ON chat_logs.id = chat_logs_fts_ids.log_id;
+
+CREATE TRIGGER chats_fts_insert AFTER INSERT ON chats BEGIN
+  INSERT INTO chat_logs_fts_ids (log_id, chat_id) VALUES (NULL, new.id);
+  INSERT INTO chat_logs_fts (rowid, title) VALUES (last_insert_rowid(), new.title);
+END;
+
+CREATE TRIGGER chats_fts_update AFTER UPDATE OF title ON chats BEGIN
+  UPDATE chat_logs_fts SET title = new.title
+  WHERE rowid = (
+    SELECT fts_rowid FROM chat_logs_fts_ids WHERE chat_id = old.id AND log_id IS NULL
+  );
+END;
+
+CREATE TRIGGER chats_fts_delete AFTER DELETE ON chats BEGIN
+  DELETE FROM chat_logs_fts
+  WHERE rowid IN (SELECT fts_rowid FROM chat_logs_fts_ids WHERE chat_id = old.id);
+  DELETE FROM chat_logs_fts_ids WHERE chat_id = old.id;
+END;
+
+CREATE TRIGGER chat_logs_fts_insert AFTER INSERT ON chat_logs BEGIN
+  INSERT INTO chat_logs_fts_ids (log_id, chat_id) VALUES (new.id, new.chat_id);
+  INSERT INTO chat_logs_fts (rowid, message) VALUES (last_insert_rowid(), new.message);
+END;
+
+CREATE TRIGGER chat_logs_fts_update AFTER UPDATE OF message ON chat_logs BEGIN
+  UPDATE chat_logs_fts SET message = new.message
+  WHERE rowid = (SELECT fts_rowid FROM chat_logs_fts_ids WHERE log_id = old.id);
+END;
+
+CREATE TRIGGER chat_logs_fts_delete AFTER DELETE ON chat_logs BEGIN
+  DELETE FROM chat_logs_fts
+  WHERE rowid = (SELECT fts_rowid FROM chat_logs_fts_ids WHERE log_id = old.id);
+  DELETE FROM chat_logs_fts_ids WHERE log_id = old.id;
+END;
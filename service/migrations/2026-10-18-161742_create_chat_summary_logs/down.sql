@@ -0,0 +1,31 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER auto_delete_chat_summary_logs;
+DROP TRIGGER auto_delete_chat_summaries_of_updated_log;
+DROP TRIGGER auto_delete_chat_summaries_of_deleted_log;
+
+CREATE TRIGGER auto_delete_chat_summaries_of_deleted_log
+  AFTER DELETE ON chat_logs
+  FOR EACH ROW
+  BEGIN
+    DELETE FROM chat_summaries
+    WHERE chat_id = OLD.chat_id
+      AND (
+        OLD.id IN (first_log_id, last_log_id)
+        OR OLD.created_at BETWEEN
+          (SELECT created_at FROM chat_logs WHERE id = first_log_id)
+          AND (SELECT created_at FROM chat_logs WHERE id = last_log_id)
+      );
+  END;
+
+CREATE TRIGGER auto_delete_chat_summaries_of_updated_log
+  AFTER UPDATE OF message ON chat_logs
+  FOR EACH ROW
+  BEGIN
+    DELETE FROM chat_summaries
+    WHERE chat_id = NEW.chat_id
+      AND NEW.created_at BETWEEN
+        (SELECT created_at FROM chat_logs WHERE id = first_log_id)
+        AND (SELECT created_at FROM chat_logs WHERE id = last_log_id);
+  END;
+
+DROP TABLE chat_summary_logs;
@@ -0,0 +1,49 @@
+-- Your SQL goes here
+-- The logs a summary stands for, along the branch it was made on
+CREATE TABLE chat_summary_logs (
+  summary_id BINARY NOT NULL,
+  log_id BINARY NOT NULL,
+  PRIMARY KEY (summary_id, log_id)
+);
+
+CREATE INDEX chat_summary_logs_log_id ON chat_summary_logs (log_id);
+
+INSERT INTO chat_summary_logs (summary_id, log_id)
+WITH RECURSIVE covered(summary_id, log_id, first_log_id) AS (
+  SELECT id, last_log_id, first_log_id FROM chat_summaries
+  UNION ALL
+  SELECT covered.summary_id, chat_logs.parent_id, covered.first_log_id
+  FROM covered
+  JOIN chat_logs ON chat_logs.id = covered.log_id
+  WHERE covered.log_id != covered.first_log_id AND chat_logs.parent_id IS NOT NULL
+)
+SELECT summary_id, log_id FROM covered;
+
+-- Ranges by time also took in the logs of other branches
+DROP TRIGGER auto_delete_chat_summaries_of_deleted_log;
+DROP TRIGGER auto_delete_chat_summaries_of_updated_log;
+
+-- A summary is stale once a log it covers is deleted or edited,
+-- the next message regenerates it
+CREATE TRIGGER auto_delete_chat_summaries_of_deleted_log
+  AFTER DELETE ON chat_logs
+  FOR EACH ROW
+  BEGIN
+    DELETE FROM chat_summaries
+    WHERE id IN (SELECT summary_id FROM chat_summary_logs WHERE log_id = OLD.id);
+  END;
+
+CREATE TRIGGER auto_delete_chat_summaries_of_updated_log
+  AFTER UPDATE OF message ON chat_logs
+  FOR EACH ROW
+  BEGIN
+    DELETE FROM chat_summaries
+    WHERE id IN (SELECT summary_id FROM chat_summary_logs WHERE log_id = NEW.id);
+  END;
+
+CREATE TRIGGER auto_delete_chat_summary_logs
+  AFTER DELETE ON chat_summaries
+  FOR EACH ROW
+  BEGIN
+    DELETE FROM chat_summary_logs WHERE summary_id = OLD.id;
+  END;
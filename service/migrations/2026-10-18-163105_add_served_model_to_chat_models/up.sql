@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE chat_models ADD COLUMN served_model TEXT;
+
+-- Azure deployments were described by the model they serve
+UPDATE chat_models SET served_model = description WHERE vendor = 'azure' AND description != '';
@@ -0,0 +1,506 @@
+use std::collections::BTreeMap;
+
+use futures::{channel::mpsc, stream, StreamExt};
+
+use crate::{
+    api::backend::{ChatBackend, ChatRequest, ChatStream},
+    api::client::{self, Client},
+    api::sse::{self, SseEvent},
+    error::{ApiError, StreamError},
+    models::chat_log::{FinishReason, ToolCall},
+    result::Result,
+    types::{StreamContent, StreamMeta},
+};
+
+use self::params::AnthropicChatParams;
+
+use super::response::{AnthropicErrorResponse, AnthropicResponseError};
+
+pub mod params;
+
+pub const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+pub struct AnthropicChatApi {
+    client: Client,
+    host: String,
+}
+
+impl AnthropicChatApi {
+    pub fn new(client: Client, host: &str) -> Self {
+        Self {
+            client,
+            host: host.to_string(),
+        }
+    }
+
+    pub async fn send_message(&self, params: AnthropicChatParams) -> Result<ChatStream> {
+        let url = self.host.clone() + "/v1/messages";
+
+        log::debug!("url: {}", url);
+        log::debug!("params: {:?}", params);
+
+        let client = self.client.clone();
+
+        // Retries are merged into the stream as they occur, like OpenAI
+        let (retry_sender, retry_receiver) = mpsc::unbounded::<StreamContent>();
+        let response = stream::once(async move {
+            let on_retry = move |attempt| {
+                retry_sender
+                    .unbounded_send(StreamContent::Retry(attempt))
+                    .ok();
+            };
+            match client.post_with_retry(&url, params, on_retry).await {
+                Ok(res) if res.status().is_success() => parse_response(res),
+                Ok(res) => {
+                    let err = client::parse_error::<AnthropicErrorResponse>(res).await;
+                    stream::iter(vec![StreamContent::Error(err.into())]).boxed()
+                }
+                Err(err) => stream::iter(vec![StreamContent::Error(err.into())]).boxed(),
+            }
+        })
+        .flatten();
+
+        let stream = stream::select(retry_receiver, response).boxed();
+
+        Ok(stream)
+    }
+}
+
+fn parse_response(res: reqwest::Response) -> ChatStream {
+    let request_id = client::request_id(&res);
+    let mut meta = StreamMeta::default();
+    let mut tool_calls = BTreeMap::new();
+    sse::decode_response(res)
+        .flat_map(move |event| {
+            let contents = match event {
+                Ok(event) => {
+                    log::debug!("event: {:?}", event);
+                    handle_event(&event, &request_id, &mut tool_calls, &mut meta)
+                }
+                Err(err) => vec![StreamContent::Error(err.into())],
+            };
+            stream::iter(contents)
+        })
+        .boxed()
+}
+
+#[async_trait::async_trait]
+impl ChatBackend for AnthropicChatApi {
+    async fn send_message(&self, request: ChatRequest) -> Result<ChatStream> {
+        AnthropicChatApi::send_message(self, request.into()).await
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicStreamEvent {
+    MessageStart {
+        message: AnthropicStreamMessage,
+    },
+    ContentBlockStart {
+        index: usize,
+        content_block: AnthropicContentBlockStart,
+    },
+    ContentBlockDelta {
+        index: usize,
+        delta: AnthropicContentDelta,
+    },
+    ContentBlockStop {
+        index: usize,
+    },
+    MessageDelta {
+        delta: AnthropicMessageDelta,
+        usage: Option<AnthropicUsage>,
+    },
+    MessageStop,
+    Error {
+        error: AnthropicResponseError,
+    },
+    /// `ping`, ...
+    #[serde(other)]
+    Other,
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicContentBlockStart {
+    /// The input follows as JSON fragments
+    ToolUse { id: String, name: String },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicContentDelta {
+    TextDelta {
+        text: String,
+    },
+    /// Extended thinking, streamed before the text
+    ThinkingDelta {
+        thinking: String,
+    },
+    /// A fragment of the input of a tool call
+    InputJsonDelta {
+        partial_json: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct AnthropicStreamMessage {
+    pub model: String,
+    pub usage: AnthropicUsage,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct AnthropicMessageDelta {
+    pub stop_reason: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct AnthropicUsage {
+    pub input_tokens: Option<usize>,
+    pub output_tokens: Option<usize>,
+}
+
+/// `tool_calls` collects the tool calls being streamed, by block index.
+fn handle_event(
+    event: &SseEvent,
+    request_id: &Option<String>,
+    tool_calls: &mut BTreeMap<usize, ToolCall>,
+    meta: &mut StreamMeta,
+) -> Vec<StreamContent> {
+    // The event name only repeats the `type` carried by the data
+    match event.json::<AnthropicStreamEvent>() {
+        Ok(AnthropicStreamEvent::MessageStart { message }) => {
+            meta.model = Some(message.model);
+            meta.prompt_tokens = message.usage.input_tokens;
+            vec![]
+        }
+        Ok(AnthropicStreamEvent::ContentBlockDelta {
+            delta: AnthropicContentDelta::TextDelta { text },
+            ..
+        }) => vec![StreamContent::Data(text)],
+        Ok(AnthropicStreamEvent::ContentBlockDelta {
+            delta: AnthropicContentDelta::ThinkingDelta { thinking },
+            ..
+        }) => vec![StreamContent::Reasoning(thinking)],
+        Ok(AnthropicStreamEvent::ContentBlockStart {
+            index,
+            content_block: AnthropicContentBlockStart::ToolUse { id, name },
+        }) => {
+            tool_calls.insert(
+                index,
+                ToolCall {
+                    id,
+                    name,
+                    arguments: String::new(),
+                },
+            );
+            vec![]
+        }
+        Ok(AnthropicStreamEvent::ContentBlockDelta {
+            index,
+            delta: AnthropicContentDelta::InputJsonDelta { partial_json },
+        }) => {
+            if let Some(tool_call) = tool_calls.get_mut(&index) {
+                tool_call.arguments.push_str(&partial_json);
+            }
+            vec![]
+        }
+        Ok(AnthropicStreamEvent::ContentBlockStop { index }) => match tool_calls.remove(&index) {
+            Some(mut tool_call) => {
+                // A tool without parameters streams no input
+                if tool_call.arguments.is_empty() {
+                    tool_call.arguments = "{}".to_string();
+                }
+                vec![StreamContent::ToolCall(tool_call)]
+            }
+            None => vec![],
+        },
+        Ok(AnthropicStreamEvent::MessageDelta { delta, usage }) => {
+            meta.finish_reason = delta.stop_reason.map(|reason| match reason.as_str() {
+                "end_turn" | "stop_sequence" => FinishReason::Stop,
+                "max_tokens" => FinishReason::Length,
+                "tool_use" => FinishReason::ToolCalls,
+                _ => FinishReason::Other,
+            });
+            // The output tokens are cumulative
+            if let Some(output_tokens) = usage.and_then(|usage| usage.output_tokens) {
+                meta.completion_tokens = Some(output_tokens);
+            }
+            vec![]
+        }
+        Ok(AnthropicStreamEvent::MessageStop) => vec![
+            StreamContent::Meta(std::mem::take(meta)),
+            StreamContent::Done,
+        ],
+        Ok(AnthropicStreamEvent::Error { error }) => {
+            let err = ApiError::new(AnthropicErrorResponse { error }.into())
+                .with_request_id(request_id.clone());
+            vec![StreamContent::Error(StreamError::Api(err))]
+        }
+        Ok(_) => vec![],
+        Err(err) => vec![StreamContent::Error(err.into())],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use serde_json::json;
+
+    use crate::{
+        api::{
+            backend::{ChatImage, ChatMessage, ChatRequest},
+            client::{Client, RetryPolicy},
+        },
+        error::{ApiError, ApiErrorKind, StreamError},
+        models::chat::{ChatTool, ChatToolChoice},
+        models::chat_log::{FinishReason, Role, ToolCall},
+        test::{mock_server, MockResponse},
+        ChatParams, StreamContent,
+    };
+
+    use super::{params::AnthropicChatParams, AnthropicChatApi};
+
+    fn create_request() -> ChatRequest {
+        ChatRequest {
+            messages: vec![
+                ChatMessage::new(Role::System, "be brief"),
+                ChatMessage::new(Role::User, "Hi"),
+            ],
+            params: ChatParams {
+                model: "claude-3-5-haiku-latest".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let (url, handle) = mock_server(vec![MockResponse::new(
+            200,
+            vec![
+                "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"model\":\"claude-3-5-haiku-20241022\",\"usage\":{\"input_tokens\":12,\"output_tokens\":1}}}\n\n",
+                "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,",
+                "\"delta\":{\"type\":\"text_delta\",\"text\":\"Hello\"}}\n\nevent: ping\ndata: {\"type\": \"ping\"}\n\n",
+                "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\" world\"}}\n\n",
+                "event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"max_tokens\"},\"usage\":{\"output_tokens\":2}}\n\n",
+                "event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n",
+            ],
+        )
+        .header("content-type", "text/event-stream")])
+        .await;
+
+        let api = AnthropicChatApi::new(Client::new(None), &url);
+        let stream = api.send_message(create_request().into()).await.unwrap();
+        let contents = stream.collect::<Vec<StreamContent>>().await;
+
+        let mut reply = String::new();
+        for content in &contents {
+            if let StreamContent::Data(data) = content {
+                reply.push_str(data);
+            }
+        }
+        assert_eq!(reply, "Hello world");
+        assert!(matches!(
+            &contents[contents.len() - 2..],
+            [StreamContent::Meta(meta), StreamContent::Done]
+                if meta.finish_reason == Some(FinishReason::Length)
+                && meta.model.as_deref() == Some("claude-3-5-haiku-20241022")
+                && meta.prompt_tokens == Some(12)
+                && meta.completion_tokens == Some(2)
+        ));
+
+        let requests = handle.await.unwrap();
+        assert!(requests[0].starts_with("POST /v1/messages"));
+        let (_, body) = requests[0].split_once("\r\n\r\n").unwrap();
+        let body = serde_json::from_str::<serde_json::Value>(body).unwrap();
+        assert_eq!(body["system"], "be brief");
+        assert_eq!(
+            body["messages"],
+            json!([{ "role": "user", "content": [{ "type": "text", "text": "Hi" }] }])
+        );
+    }
+
+    #[test]
+    fn test_image_message() {
+        let mut request = create_request();
+        request.messages[1].images = vec![ChatImage {
+            name: "a.png".to_string(),
+            mime_type: "image/png".to_string(),
+            data: vec![1, 2, 3],
+            width: 1,
+            height: 1,
+        }];
+
+        let params = serde_json::to_value(AnthropicChatParams::from(request)).unwrap();
+        assert_eq!(
+            params["messages"][0]["content"],
+            json!([
+                {
+                    "type": "image",
+                    "source": { "type": "base64", "media_type": "image/png", "data": "AQID" },
+                },
+                { "type": "text", "text": "Hi" },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tool_use() {
+        let (url, handle) = mock_server(vec![MockResponse::new(
+            200,
+            vec![
+                "event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+                "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Checking.\"}}\n\n",
+                "event: content_block_stop\ndata: {\"type\":\"content_block_stop\",\"index\":0}\n\n",
+                "event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":1,\"content_block\":{\"type\":\"tool_use\",\"id\":\"toolu_2\",\"name\":\"get_weather\",\"input\":{}}}\n\n",
+                "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"{\\\"city\\\":\"}}\n\n",
+                "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\" \\\"Rome\\\"}\"}}\n\n",
+                "event: content_block_stop\ndata: {\"type\":\"content_block_stop\",\"index\":1}\n\n",
+                "event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"tool_use\"}}\n\n",
+                "event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n",
+            ],
+        )])
+        .await;
+
+        let mut request = create_request();
+        let mut assistant = ChatMessage::new(Role::Assistant, "");
+        assistant.tool_calls = vec![ToolCall {
+            id: "toolu_1".to_string(),
+            name: "get_weather".to_string(),
+            arguments: "{\"city\":\"Paris\"}".to_string(),
+        }];
+        let mut tool = ChatMessage::new(Role::Tool, "sunny");
+        tool.tool_call_id = Some("toolu_1".to_string());
+        request.messages.extend([assistant, tool]);
+        request.params.tools = Some(vec![ChatTool {
+            name: "get_weather".to_string(),
+            description: None,
+            parameters: json!({ "type": "object" }),
+        }]);
+        request.params.tool_choice = Some(ChatToolChoice::Required);
+
+        let api = AnthropicChatApi::new(Client::new(None), &url);
+        let stream = api.send_message(request.into()).await.unwrap();
+        let contents = stream.collect::<Vec<StreamContent>>().await;
+
+        assert!(matches!(
+            &contents[..],
+            [StreamContent::Data(data), StreamContent::ToolCall(tool_call), StreamContent::Meta(meta), StreamContent::Done]
+                if data == "Checking."
+                && *tool_call == ToolCall {
+                    id: "toolu_2".to_string(),
+                    name: "get_weather".to_string(),
+                    arguments: "{\"city\": \"Rome\"}".to_string(),
+                }
+                && meta.finish_reason == Some(FinishReason::ToolCalls)
+        ));
+
+        let requests = handle.await.unwrap();
+        let (_, body) = requests[0].split_once("\r\n\r\n").unwrap();
+        let body = serde_json::from_str::<serde_json::Value>(body).unwrap();
+        assert_eq!(
+            body["tools"],
+            json!([{ "name": "get_weather", "input_schema": { "type": "object" } }])
+        );
+        assert_eq!(body["tool_choice"], json!({ "type": "any" }));
+        // The tool result is sent back in a user turn
+        assert_eq!(
+            body["messages"],
+            json!([
+                { "role": "user", "content": [{ "type": "text", "text": "Hi" }] },
+                {
+                    "role": "assistant",
+                    "content": [{
+                        "type": "tool_use",
+                        "id": "toolu_1",
+                        "name": "get_weather",
+                        "input": { "city": "Paris" },
+                    }],
+                },
+                {
+                    "role": "user",
+                    "content": [{ "type": "tool_result", "tool_use_id": "toolu_1", "content": "sunny" }],
+                },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_error_event() {
+        let (url, _) = mock_server(vec![MockResponse::new(
+            200,
+            vec!["event: error\ndata: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n"],
+        )
+        .header("request-id", "req_018")])
+        .await;
+
+        let api = AnthropicChatApi::new(Client::new(None), &url);
+        let stream = api.send_message(create_request().into()).await.unwrap();
+        let contents = stream.collect::<Vec<StreamContent>>().await;
+
+        assert!(matches!(
+            &contents[..],
+            [StreamContent::Error(StreamError::Api(ApiError {
+                kind: ApiErrorKind::Overloaded(message),
+                status: None,
+                request_id: Some(request_id),
+            }))] if message == "Overloaded" && request_id == "req_018"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_error_response() {
+        let (url, _) = mock_server(vec![MockResponse::new(
+            401,
+            vec!["{\"type\":\"error\",\"error\":{\"type\":\"authentication_error\",\"message\":\"invalid x-api-key\"}}"],
+        )])
+        .await;
+
+        let api = AnthropicChatApi::new(Client::new(None), &url);
+        let stream = api.send_message(create_request().into()).await.unwrap();
+        let contents = stream.collect::<Vec<StreamContent>>().await;
+
+        assert!(matches!(
+            &contents[..],
+            [StreamContent::Error(StreamError::Api(ApiError {
+                kind: ApiErrorKind::InvalidKey,
+                status: Some(401),
+                ..
+            }))]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_overloaded() {
+        let (url, handle) = mock_server(vec![
+            MockResponse::new(529, vec!["{}"]).header("retry-after", "0"),
+            MockResponse::new(
+                200,
+                vec!["event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\nevent: message_stop\ndata: {\"type\":\"message_stop\"}\n\n"],
+            ),
+        ])
+        .await;
+
+        let mut client = Client::new(None);
+        client.retry_policy(RetryPolicy {
+            max_retries: 2,
+            ..Default::default()
+        });
+        let api = AnthropicChatApi::new(client, &url);
+        let stream = api.send_message(create_request().into()).await.unwrap();
+        let contents = stream.collect::<Vec<StreamContent>>().await;
+
+        assert!(matches!(
+            &contents[..],
+            [StreamContent::Retry(attempt), StreamContent::Data(data), StreamContent::Meta(_), StreamContent::Done]
+                if attempt.attempt == 1 && attempt.max_retries == 2 && data == "Hi"
+        ));
+        assert_eq!(handle.await.unwrap().len(), 2);
+    }
+}
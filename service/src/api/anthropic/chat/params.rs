@@ -0,0 +1,231 @@
+use crate::api::backend::{ChatImage, ChatMessage, ChatRequest};
+use crate::models::chat::{ChatTool, ChatToolChoice};
+use crate::models::chat_log::Role;
+
+/// Anthropic requires `max_tokens`, use this when the chat does not limit it.
+pub const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+#[derive(serde::Serialize, serde::Deserialize, Default, Clone, Debug)]
+pub struct AnthropicChatParams {
+    /// The model that will complete your prompt.
+    pub model: String,
+
+    /// Input messages, alternating between `user` and `assistant` turns.
+    pub messages: Vec<AnthropicChatMessage>,
+
+    /// System prompt, Anthropic does not accept a `system` role in `messages`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+
+    /// The maximum number of tokens to generate before stopping.
+    pub max_tokens: u32,
+
+    /// Amount of randomness injected into the response, between 0 and 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+
+    /// Use nucleus sampling.
+    ///
+    /// You should either alter temperature or top_p, but not both.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+
+    /// Custom text sequences that will cause the model to stop generating.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+
+    /// Whether to incrementally stream the response using server-sent events.
+    pub stream: bool,
+
+    /// Definitions of tools that the model may use.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<AnthropicTool>>,
+
+    /// How the model should use the provided tools.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<AnthropicToolChoice>,
+}
+
+impl From<ChatRequest> for AnthropicChatParams {
+    fn from(request: ChatRequest) -> Self {
+        let ChatRequest { messages, params } = request;
+
+        let mut system: Vec<String> = vec![];
+        let mut anthropic_messages: Vec<AnthropicChatMessage> = vec![];
+        for message in messages {
+            let ChatMessage {
+                role,
+                content,
+                tool_calls,
+                tool_call_id,
+                images,
+            } = message;
+
+            let mut blocks = vec![];
+            let role = match role {
+                Role::System => {
+                    system.push(content);
+                    continue;
+                }
+                // Tool results are sent back in a user turn
+                Role::Tool => {
+                    blocks.push(AnthropicContentBlock::ToolResult {
+                        tool_use_id: tool_call_id.unwrap_or_default(),
+                        content,
+                    });
+                    AnthropicChatRole::User
+                }
+                Role::User => {
+                    // Images go before the text that refers to them
+                    blocks.extend(images.iter().map(AnthropicContentBlock::image));
+                    blocks.extend(AnthropicContentBlock::text(content));
+                    AnthropicChatRole::User
+                }
+                Role::Assistant => {
+                    blocks.extend(AnthropicContentBlock::text(content));
+                    blocks.extend(tool_calls.into_iter().map(|tool_call| {
+                        AnthropicContentBlock::ToolUse {
+                            id: tool_call.id,
+                            name: tool_call.name,
+                            input: serde_json::from_str(&tool_call.arguments)
+                                .unwrap_or_else(|_| serde_json::json!({})),
+                        }
+                    }));
+                    AnthropicChatRole::Assistant
+                }
+            };
+
+            // Merge consecutive messages of the same role into one turn
+            match anthropic_messages.last_mut() {
+                Some(last) if last.role == role => last.content.extend(blocks),
+                _ => anthropic_messages.push(AnthropicChatMessage {
+                    role,
+                    content: blocks,
+                }),
+            }
+        }
+
+        Self {
+            model: params.model,
+            messages: anthropic_messages,
+            system: if system.is_empty() {
+                None
+            } else {
+                Some(system.join("\n\n"))
+            },
+            max_tokens: params
+                .max_tokens
+                .or(params.max_completion_tokens)
+                .unwrap_or(DEFAULT_MAX_TOKENS),
+            temperature: params.temperature,
+            top_p: params.top_p,
+            stop_sequences: params.stop,
+            stream: true,
+            tools: params
+                .tools
+                .map(|tools| tools.into_iter().map(Into::into).collect()),
+            tool_choice: params.tool_choice.map(Into::into),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct AnthropicChatMessage {
+    pub role: AnthropicChatRole,
+    pub content: Vec<AnthropicContentBlock>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicContentBlock {
+    Text {
+        text: String,
+    },
+    Image {
+        source: AnthropicImageSource,
+    },
+    /// A tool call of an assistant turn
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    /// The result of a tool call, in the following user turn
+    ToolResult {
+        tool_use_id: String,
+        content: String,
+    },
+}
+
+impl AnthropicContentBlock {
+    /// A text block, empty text blocks are rejected.
+    fn text(text: String) -> Option<Self> {
+        (!text.is_empty()).then_some(Self::Text { text })
+    }
+
+    fn image(image: &ChatImage) -> Self {
+        Self::Image {
+            source: AnthropicImageSource::Base64 {
+                media_type: image.mime_type.clone(),
+                data: image.base64(),
+            },
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicImageSource {
+    Base64 { media_type: String, data: String },
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct AnthropicTool {
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// JSON Schema of the tool input.
+    pub input_schema: serde_json::Value,
+}
+
+impl From<ChatTool> for AnthropicTool {
+    fn from(tool: ChatTool) -> Self {
+        Self {
+            name: tool.name,
+            description: tool.description,
+            input_schema: tool.parameters,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicToolChoice {
+    None,
+    Auto,
+    /// Use any of the tools
+    Any,
+    Tool {
+        name: String,
+    },
+}
+
+impl From<ChatToolChoice> for AnthropicToolChoice {
+    fn from(choice: ChatToolChoice) -> Self {
+        match choice {
+            ChatToolChoice::None => Self::None,
+            ChatToolChoice::Auto => Self::Auto,
+            ChatToolChoice::Required => Self::Any,
+            ChatToolChoice::Function { name } => Self::Tool { name },
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Eq, Clone, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum AnthropicChatRole {
+    User,
+    Assistant,
+}
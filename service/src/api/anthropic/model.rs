@@ -0,0 +1,44 @@
+use crate::{
+    api::client::{self, Client},
+    result::Result,
+};
+
+use super::response::AnthropicErrorResponse;
+
+pub struct AnthropicModelApi {
+    client: Client,
+    host: String,
+}
+
+impl AnthropicModelApi {
+    pub fn new(client: Client, host: &str) -> Self {
+        Self {
+            client,
+            host: host.to_string(),
+        }
+    }
+
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        let url = self.host.clone() + "/v1/models?limit=1000";
+
+        let res = self.client.get(&url).await?;
+
+        if !res.status().is_success() {
+            return Err(client::parse_error::<AnthropicErrorResponse>(res).await);
+        }
+
+        let list = res.json::<AnthropicModelList>().await?;
+
+        Ok(list.data.into_iter().map(|model| model.id).collect())
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct AnthropicModelList {
+    pub data: Vec<AnthropicModel>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct AnthropicModel {
+    pub id: String,
+}
@@ -0,0 +1,10 @@
+#[derive(serde::Deserialize, Debug)]
+pub struct AnthropicErrorResponse {
+    pub error: AnthropicResponseError,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct AnthropicResponseError {
+    pub message: String,
+    pub r#type: String,
+}
@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::pin::Pin;
+
+use base64::Engine;
+use futures::Stream;
+
+use crate::api::openai::chat::params::OpenAIChatMessage;
+use crate::error::ApiErrorKind;
+use crate::error::Error;
+use crate::models::attachment::Attachment;
+use crate::models::chat::ChatParams;
+use crate::models::chat_log::{Role, ToolCall};
+use crate::models::chat_model::ChatModel;
+use crate::models::setting::Setting;
+use crate::result::Result;
+use crate::types::StreamContent;
+
+pub type ChatStream = Pin<Box<dyn Stream<Item = StreamContent> + Send>>;
+
+/// A chat completion provider.
+///
+/// Backends receive a vendor independent [`ChatRequest`] and translate it into
+/// their own wire format, streaming the reply back as [`StreamContent`].
+#[async_trait::async_trait]
+pub trait ChatBackend: Send + Sync {
+    async fn send_message(&self, request: ChatRequest) -> Result<ChatStream>;
+}
+
+#[derive(Clone, Debug)]
+pub struct ChatRequest {
+    pub messages: Vec<ChatMessage>,
+    pub params: ChatParams,
+}
+
+impl ChatRequest {
+    /// Prompt tokens of the request.
+    pub fn calc_tokens(&self) -> usize {
+        let mut tokens = 0;
+        for message in &self.messages {
+            tokens += message.tokens(&self.params.model);
+        }
+
+        tokens
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+    /// Tools called by an assistant message
+    pub tool_calls: Vec<ToolCall>,
+    /// The call a tool message answers
+    pub tool_call_id: Option<String>,
+    pub images: Vec<ChatImage>,
+}
+
+impl ChatMessage {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: content.into(),
+            tool_calls: vec![],
+            tool_call_id: None,
+            images: vec![],
+        }
+    }
+
+    pub fn tokens(&self, model: &str) -> usize {
+        let mut tokens = Self::calc_tokens(model, &self.role, &self.content);
+        for tool_call in &self.tool_calls {
+            tokens += Self::calc_tokens(model, &self.role, &tool_call.name);
+            tokens += Self::calc_tokens(model, &self.role, &tool_call.arguments);
+        }
+        for image in &self.images {
+            tokens += image.tokens();
+        }
+
+        tokens
+    }
+
+    pub fn calc_tokens(model: &str, role: &Role, content: &str) -> usize {
+        OpenAIChatMessage::calc_tokens(model, &role.clone().into(), content)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ChatImage {
+    pub name: String,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ChatImage {
+    /// Read a png, jpeg, gif or webp image.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let data = std::fs::read(path)?;
+
+        Self::new(name, data)
+    }
+
+    pub fn new(name: String, data: Vec<u8>) -> Result<Self> {
+        let mime_type = match imagesize::image_type(&data) {
+            Ok(imagesize::ImageType::Png) => "image/png",
+            Ok(imagesize::ImageType::Jpeg) => "image/jpeg",
+            Ok(imagesize::ImageType::Gif) => "image/gif",
+            Ok(imagesize::ImageType::Webp) => "image/webp",
+            _ => return Err(Error::Unknown(format!("unsupported image: {name}"))),
+        };
+        let size = imagesize::blob_size(&data)
+            .map_err(|err| Error::Unknown(format!("invalid image {name}: {err}")))?;
+
+        Ok(Self {
+            name,
+            mime_type: mime_type.to_string(),
+            data,
+            width: size.width as u32,
+            height: size.height as u32,
+        })
+    }
+
+    pub fn base64(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(&self.data)
+    }
+
+    pub fn data_url(&self) -> String {
+        format!("data:{};base64,{}", self.mime_type, self.base64())
+    }
+
+    /// Tokens of the image sent in high detail.
+    ///
+    /// The image is scaled to fit in 2048x2048, then its shortest side to 768,
+    /// every 512x512 tile costs 170 tokens plus 85 base tokens.
+    pub fn tokens(&self) -> usize {
+        let (mut width, mut height) = (self.width as f64, self.height as f64);
+
+        let scale = (2048.0 / width.max(height)).min(1.0);
+        width *= scale;
+        height *= scale;
+
+        let scale = (768.0 / width.min(height)).min(1.0);
+        width *= scale;
+        height *= scale;
+
+        let tiles = (width / 512.0).ceil() * (height / 512.0).ceil();
+
+        85 + 170 * tiles as usize
+    }
+}
+
+impl From<Attachment> for ChatImage {
+    fn from(attachment: Attachment) -> Self {
+        Self {
+            name: attachment.name,
+            mime_type: attachment.mime_type,
+            data: attachment.data,
+            width: attachment.width as u32,
+            height: attachment.height as u32,
+        }
+    }
+}
+
+pub type BackendFactory = fn(&Setting, &ChatModel) -> Result<Box<dyn ChatBackend>>;
+
+/// Maps vendor names (as stored in `chat_models.vendor` and `chats.vendor`)
+/// to the factories creating their backends.
+#[derive(Clone)]
+pub struct BackendRegistry {
+    factories: HashMap<String, BackendFactory>,
+}
+
+impl Default for BackendRegistry {
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry
+            .register("openai", create_openai_backend)
+            // user defined models are served through the OpenAI compatible endpoint
+            .register("custom", create_openai_backend)
+            .register("anthropic", create_anthropic_backend)
+            .register("local", create_local_backend)
+            .register("azure", create_azure_backend)
+            .register("gemini", create_gemini_backend);
+
+        registry
+    }
+}
+
+impl BackendRegistry {
+    pub fn new() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, vendor: &str, factory: BackendFactory) -> &mut Self {
+        self.factories.insert(vendor.to_string(), factory);
+        self
+    }
+
+    /// Create the backend serving `chat_model`.
+    ///
+    /// The model's own vendor wins, `vendor` (usually the chat's vendor) is only
+    /// used when the model's vendor is not registered.
+    pub fn create(
+        &self,
+        chat_model: &ChatModel,
+        vendor: &str,
+        setting: &Setting,
+    ) -> Result<Box<dyn ChatBackend>> {
+        let factory = self
+            .factories
+            .get(&chat_model.vendor)
+            .or_else(|| self.factories.get(vendor))
+            .ok_or_else(|| ApiErrorKind::UnknownVendor(chat_model.vendor.clone()))?;
+
+        factory(setting, chat_model)
+    }
+}
+
+fn create_openai_backend(
+    setting: &Setting,
+    _chat_model: &ChatModel,
+) -> Result<Box<dyn ChatBackend>> {
+    Ok(Box::new(setting.create_openai_chat()))
+}
+
+fn create_anthropic_backend(
+    setting: &Setting,
+    _chat_model: &ChatModel,
+) -> Result<Box<dyn ChatBackend>> {
+    Ok(Box::new(setting.create_anthropic_chat()))
+}
+
+fn create_gemini_backend(
+    setting: &Setting,
+    _chat_model: &ChatModel,
+) -> Result<Box<dyn ChatBackend>> {
+    Ok(Box::new(setting.create_gemini_chat()))
+}
+
+fn create_local_backend(setting: &Setting, chat_model: &ChatModel) -> Result<Box<dyn ChatBackend>> {
+    let server = chat_model
+        .server_id
+        .and_then(|id| setting.local_server(id))
+        .ok_or_else(|| Error::Unknown(format!("local server of {} not found", chat_model.name)))?;
+
+    Ok(Box::new(setting.create_local_chat(server)))
+}
+
+fn create_azure_backend(setting: &Setting, chat_model: &ChatModel) -> Result<Box<dyn ChatBackend>> {
+    let azure = setting
+        .azure()
+        .ok_or_else(|| Error::Unknown("azure is not configured".to_string()))?;
+
+    // Azure chat models are named after their deployments
+    Ok(Box::new(setting.create_azure_chat(azure, &chat_model.name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChatImage;
+
+    fn png_header(width: u32, height: u32) -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&[8, 6, 0, 0, 0]);
+        data
+    }
+
+    #[test]
+    fn test_image_tokens() {
+        let image = ChatImage::new("a.png".to_string(), png_header(1024, 1024)).unwrap();
+        assert_eq!(image.mime_type, "image/png");
+        assert_eq!((image.width, image.height), (1024, 1024));
+        // scaled to 768x768, 4 tiles
+        assert_eq!(image.tokens(), 765);
+        assert!(image
+            .data_url()
+            .starts_with("data:image/png;base64,iVBORw0KGgo"));
+
+        let image = ChatImage::new("b.png".to_string(), png_header(2048, 4096)).unwrap();
+        // scaled to 1024x2048 then 768x1536, 6 tiles
+        assert_eq!(image.tokens(), 1105);
+
+        assert!(ChatImage::new("c.txt".to_string(), b"hello".to_vec()).is_err());
+    }
+}
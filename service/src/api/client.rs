@@ -1,12 +1,19 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
 use std::time::Duration;
 
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ApiError, ApiErrorKind};
 use crate::result::Result;
+use crate::Error;
 
 #[derive(Default, Clone)]
 pub struct Client {
     headers: Option<reqwest::header::HeaderMap>,
     proxy: Option<reqwest::Proxy>,
     timeout: Option<Duration>,
+    retry_policy: RetryPolicy,
 }
 
 impl Client {
@@ -27,6 +34,11 @@ impl Client {
         self
     }
 
+    pub fn retry_policy(&mut self, retry_policy: RetryPolicy) -> &mut Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
     fn build(&self) -> reqwest::Client {
         let mut client_builder = reqwest::Client::builder();
 
@@ -57,12 +69,163 @@ impl Client {
     }
 
     pub async fn post(&self, url: &str, data: impl serde::Serialize) -> Result<reqwest::Response> {
+        self.post_with_retry(url, data, |_| {}).await
+    }
+
+    /// Post, retrying rate limited, overloaded and unreachable requests
+    /// according to the retry policy.
+    ///
+    /// `on_retry` is called before waiting for each retry. Only the request is
+    /// retried, the caller owns the response body once it is returned.
+    pub async fn post_with_retry(
+        &self,
+        url: &str,
+        data: impl serde::Serialize,
+        mut on_retry: impl FnMut(RetryAttempt),
+    ) -> Result<reqwest::Response> {
         let client = self.build();
 
         let data = serde_json::to_value(data).unwrap();
 
-        let request = client.post(url).json(&data);
+        let mut attempt = 0;
+        loop {
+            let request = client.post(url).json(&data);
+
+            let result = request.send().await;
+            let retry_after = match &result {
+                Ok(res) if is_retryable_status(res.status()) => retry_after(res),
+                Err(err) if err.is_connect() || err.is_timeout() => None,
+                _ => return result.map_err(Into::into),
+            };
+
+            attempt += 1;
+            if attempt > self.retry_policy.max_retries {
+                // Give the last response or error to the caller
+                return result.map_err(Into::into);
+            }
+
+            let delay = self.retry_policy.delay(attempt, retry_after);
+            on_retry(RetryAttempt {
+                attempt,
+                max_retries: self.retry_policy.max_retries,
+                delay: delay.as_millis() as u64,
+            });
+            log::debug!("retry {} in {:?}: {}", attempt, delay, url);
+
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
 
-        request.send().await.map_err(Into::into)
+/// How requests failing with 408, 429, 5xx or a connection error are retried.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// Milliseconds to wait before the first retry, doubled for every next one
+    pub initial_delay: u64,
+    /// Milliseconds to wait at most between retries
+    pub max_delay: u64,
+}
+
+impl Default for RetryPolicy {
+    /// Never retry
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            initial_delay: 1000,
+            max_delay: 30000,
+        }
     }
 }
+
+impl RetryPolicy {
+    /// Exponential backoff with full jitter, unless the server told us how long to wait.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        let max_delay = Duration::from_millis(self.max_delay);
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(max_delay);
+        }
+
+        let backoff = self
+            .initial_delay
+            .saturating_mul(2u64.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+
+        Duration::from_millis((backoff as f64 * jitter()) as u64)
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryAttempt {
+    /// Starts from 1
+    pub attempt: u32,
+    pub max_retries: u32,
+    /// Milliseconds before the retry
+    pub delay: u64,
+}
+
+/// Id the provider assigned to the request, OpenAI and Azure send
+/// `x-request-id` or `apim-request-id`, Anthropic `request-id`.
+pub fn request_id(res: &reqwest::Response) -> Option<String> {
+    ["x-request-id", "request-id", "apim-request-id"]
+        .iter()
+        .find_map(|name| res.headers().get(*name)?.to_str().ok())
+        .map(ToString::to_string)
+}
+
+/// Read an error response, `E` being the error body the provider documents.
+pub async fn parse_error<E>(res: reqwest::Response) -> Error
+where
+    E: serde::de::DeserializeOwned + Into<ApiErrorKind>,
+{
+    let status = res.status().as_u16();
+    let request_id = request_id(&res);
+    let body = match res.text().await {
+        Ok(body) => body,
+        Err(err) => return err.into(),
+    };
+
+    let kind = match serde_json::from_str::<E>(&body) {
+        Ok(err) => err.into(),
+        Err(_) => ApiErrorKind::Unknown(body),
+    };
+
+    Error::Api(
+        ApiError::new(kind)
+            .with_status(status)
+            .with_request_id(request_id),
+    )
+}
+
+fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::REQUEST_TIMEOUT
+        || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || status.is_server_error()
+}
+
+/// `Retry-After` in seconds or as an HTTP date
+fn retry_after(res: &reqwest::Response) -> Option<Duration> {
+    let value = res
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?;
+
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delay = date.timestamp_millis() - chrono::Utc::now().timestamp_millis();
+
+    Some(Duration::from_millis(delay.max(0) as u64))
+}
+
+/// A random factor in `[0, 1)`
+fn jitter() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,526 @@
+use futures::{channel::mpsc, stream, StreamExt};
+
+use crate::{
+    api::backend::{ChatBackend, ChatRequest, ChatStream},
+    api::client::{self, Client},
+    api::sse::{self, SseEvent},
+    error::{ApiError, ApiErrorKind, StreamError},
+    models::chat_log::{FinishReason, ToolCall},
+    result::Result,
+    types::{StreamContent, StreamMeta},
+};
+
+use self::params::{GeminiChatParams, GeminiFunctionCall};
+
+use super::response::{GeminiErrorBody, GeminiErrorResponse, GeminiResponseError};
+
+pub mod params;
+
+pub struct GeminiChatApi {
+    client: Client,
+    host: String,
+}
+
+impl GeminiChatApi {
+    pub fn new(client: Client, host: &str) -> Self {
+        Self {
+            client,
+            host: host.to_string(),
+        }
+    }
+
+    pub async fn send_message(&self, params: GeminiChatParams) -> Result<ChatStream> {
+        let url = format!(
+            "{}/v1beta/models/{}:streamGenerateContent?alt=sse",
+            self.host, params.model
+        );
+
+        log::debug!("url: {}", url);
+        log::debug!("params: {:?}", params);
+
+        let client = self.client.clone();
+
+        // Retries are merged into the stream as they occur, like OpenAI
+        let (retry_sender, retry_receiver) = mpsc::unbounded::<StreamContent>();
+        let response = stream::once(async move {
+            let on_retry = move |attempt| {
+                retry_sender
+                    .unbounded_send(StreamContent::Retry(attempt))
+                    .ok();
+            };
+            match client.post_with_retry(&url, params, on_retry).await {
+                Ok(res) if res.status().is_success() => parse_response(res),
+                Ok(res) => {
+                    let err = client::parse_error::<GeminiErrorBody>(res).await;
+                    stream::iter(vec![StreamContent::Error(err.into())]).boxed()
+                }
+                Err(err) => stream::iter(vec![StreamContent::Error(err.into())]).boxed(),
+            }
+        })
+        .flatten();
+
+        let stream = stream::select(retry_receiver, response).boxed();
+
+        Ok(stream)
+    }
+}
+
+fn parse_response(res: reqwest::Response) -> ChatStream {
+    let mut meta = StreamMeta::default();
+    let mut tool_called = false;
+    let mut ended = false;
+    // `None` marks the end of the body, which must come after a finish reason
+    sse::decode_response(res)
+        .map(Some)
+        .chain(stream::iter([None]))
+        .flat_map(move |event| {
+            let contents = match event {
+                Some(Ok(event)) => {
+                    log::debug!("event: {:?}", event);
+                    handle_event(&event, &mut tool_called, &mut meta)
+                }
+                Some(Err(err)) => vec![StreamContent::Error(err.into())],
+                None if ended => vec![],
+                None => vec![StreamContent::Error(StreamError::Unknown(
+                    "stream ended without a finish reason".to_string(),
+                ))],
+            };
+            ended |= contents
+                .iter()
+                .any(|content| matches!(content, StreamContent::Done | StreamContent::Error(_)));
+            stream::iter(contents)
+        })
+        .boxed()
+}
+
+#[async_trait::async_trait]
+impl ChatBackend for GeminiChatApi {
+    async fn send_message(&self, request: ChatRequest) -> Result<ChatStream> {
+        GeminiChatApi::send_message(self, request.into()).await
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiStreamChunk {
+    #[serde(default)]
+    pub candidates: Vec<GeminiCandidate>,
+    pub usage_metadata: Option<GeminiUsageMetadata>,
+    pub model_version: Option<String>,
+    pub error: Option<GeminiResponseError>,
+    /// Set instead of the candidates when the prompt is blocked
+    pub prompt_feedback: Option<GeminiPromptFeedback>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiPromptFeedback {
+    pub block_reason: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiUsageMetadata {
+    pub prompt_token_count: Option<usize>,
+    pub candidates_token_count: Option<usize>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiCandidate {
+    pub content: Option<GeminiCandidateContent>,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct GeminiCandidateContent {
+    #[serde(default)]
+    pub parts: Vec<GeminiCandidatePart>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiCandidatePart {
+    pub text: Option<String>,
+    /// The text is a thought summary rather than the reply
+    #[serde(default)]
+    pub thought: bool,
+    /// Function calls come whole, never split across chunks
+    pub function_call: Option<GeminiFunctionCall>,
+}
+
+/// `tool_called` remembers a function call for the finish reason,
+/// Gemini finishes with `STOP` either way.
+fn handle_event(
+    event: &SseEvent,
+    tool_called: &mut bool,
+    meta: &mut StreamMeta,
+) -> Vec<StreamContent> {
+    let chunk = match event.json::<GeminiStreamChunk>() {
+        Ok(chunk) => chunk,
+        Err(err) => return vec![StreamContent::Error(err.into())],
+    };
+
+    if let Some(error) = chunk.error {
+        let err = ApiError::new(GeminiErrorResponse { error }.into());
+        return vec![StreamContent::Error(StreamError::Api(err))];
+    }
+
+    if let Some(block_reason) = chunk
+        .prompt_feedback
+        .and_then(|feedback| feedback.block_reason)
+    {
+        let err = ApiError::new(ApiErrorKind::ContentPolicy(format!(
+            "prompt blocked: {}",
+            block_reason
+        )));
+        return vec![StreamContent::Error(StreamError::Api(err))];
+    }
+
+    if chunk.model_version.is_some() {
+        meta.model = chunk.model_version;
+    }
+    // Every chunk carries the usage so far
+    if let Some(usage) = chunk.usage_metadata {
+        meta.prompt_tokens = usage.prompt_token_count;
+        meta.completion_tokens = usage.candidates_token_count;
+    }
+
+    let Some(candidate) = chunk.candidates.into_iter().next() else {
+        return vec![];
+    };
+
+    let mut contents = vec![];
+    let (thoughts, parts): (Vec<_>, Vec<_>) = candidate
+        .content
+        .map(|content| content.parts)
+        .unwrap_or_default()
+        .into_iter()
+        .partition(|part| part.thought);
+    let reasoning = thoughts
+        .into_iter()
+        .filter_map(|part| part.text)
+        .collect::<String>();
+    if !reasoning.is_empty() {
+        contents.push(StreamContent::Reasoning(reasoning));
+    }
+    let mut text = String::new();
+    let mut tool_calls = vec![];
+    for part in parts {
+        text.extend(part.text);
+        if let Some(function_call) = part.function_call {
+            // Calls have no id, one is made up for the tool results to refer to
+            tool_calls.push(StreamContent::ToolCall(ToolCall {
+                id: format!("call_{}", uuid::Uuid::new_v4().simple()),
+                name: function_call.name,
+                arguments: function_call.args.to_string(),
+            }));
+        }
+    }
+    if !text.is_empty() {
+        contents.push(StreamContent::Data(text));
+    }
+    *tool_called |= !tool_calls.is_empty();
+    contents.extend(tool_calls);
+
+    // The stream has no end marker, the last chunk comes with the finish reason
+    if let Some(finish_reason) = candidate.finish_reason {
+        meta.finish_reason = Some(match finish_reason.as_str() {
+            "STOP" if *tool_called => FinishReason::ToolCalls,
+            "STOP" => FinishReason::Stop,
+            "MAX_TOKENS" => FinishReason::Length,
+            "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" => {
+                FinishReason::ContentFilter
+            }
+            _ => FinishReason::Other,
+        });
+        contents.push(StreamContent::Meta(std::mem::take(meta)));
+        contents.push(StreamContent::Done);
+    }
+
+    contents
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use serde_json::json;
+
+    use crate::{
+        api::{
+            backend::{ChatImage, ChatMessage, ChatRequest},
+            client::{Client, RetryPolicy},
+        },
+        error::{ApiError, ApiErrorKind, StreamError},
+        models::{
+            chat::{ChatTool, ChatToolChoice},
+            chat_log::{FinishReason, Role, ToolCall},
+        },
+        test::{mock_server, MockResponse},
+        ChatParams, StreamContent,
+    };
+
+    use super::{params::GeminiChatParams, GeminiChatApi};
+
+    fn create_request() -> ChatRequest {
+        ChatRequest {
+            messages: vec![
+                ChatMessage::new(Role::System, "be brief"),
+                ChatMessage::new(Role::User, "Hi"),
+                ChatMessage::new(Role::Assistant, "Hello"),
+                ChatMessage::new(Role::User, "How are you?"),
+            ],
+            params: ChatParams {
+                model: "gemini-1.5-flash".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let (url, handle) = mock_server(vec![MockResponse::new(
+            200,
+            vec![
+                "data: {\"candidates\": [{\"content\": {\"parts\": [{\"text\": \"Fine\"}],\"role\": \"model\"},",
+                "\"index\": 0}]}\r\n\r\ndata: {\"candidates\": [{\"content\": {\"parts\": [{\"text\": \", thanks\"}],\"role\": \"model\"},\"finishReason\": \"STOP\",\"index\": 0}],",
+                "\"usageMetadata\": {\"promptTokenCount\": 9,\"candidatesTokenCount\": 3},\"modelVersion\": \"gemini-1.5-flash-002\"}\r\n\r\n",
+            ],
+        )
+        .header("content-type", "text/event-stream")])
+        .await;
+
+        let api = GeminiChatApi::new(Client::new(None), &url);
+        let stream = api.send_message(create_request().into()).await.unwrap();
+        let contents = stream.collect::<Vec<StreamContent>>().await;
+
+        let mut reply = String::new();
+        for content in &contents {
+            if let StreamContent::Data(data) = content {
+                reply.push_str(data);
+            }
+        }
+        assert_eq!(reply, "Fine, thanks");
+        assert!(matches!(
+            &contents[contents.len() - 2..],
+            [StreamContent::Meta(meta), StreamContent::Done]
+                if meta.finish_reason == Some(FinishReason::Stop)
+                && meta.model.as_deref() == Some("gemini-1.5-flash-002")
+                && meta.prompt_tokens == Some(9)
+                && meta.completion_tokens == Some(3)
+        ));
+
+        let requests = handle.await.unwrap();
+        assert!(requests[0]
+            .starts_with("POST /v1beta/models/gemini-1.5-flash:streamGenerateContent?alt=sse"));
+        let (_, body) = requests[0].split_once("\r\n\r\n").unwrap();
+        let body = serde_json::from_str::<serde_json::Value>(body).unwrap();
+        assert_eq!(
+            body["systemInstruction"],
+            json!({ "parts": [{ "text": "be brief" }] })
+        );
+        assert_eq!(
+            body["contents"],
+            json!([
+                { "role": "user", "parts": [{ "text": "Hi" }] },
+                { "role": "model", "parts": [{ "text": "Hello" }] },
+                { "role": "user", "parts": [{ "text": "How are you?" }] },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_image_message() {
+        let mut request = create_request();
+        request.messages[1].images = vec![ChatImage {
+            name: "a.png".to_string(),
+            mime_type: "image/png".to_string(),
+            data: vec![1, 2, 3],
+            width: 1,
+            height: 1,
+        }];
+
+        let params = serde_json::to_value(GeminiChatParams::from(request)).unwrap();
+        assert_eq!(
+            params["contents"][0]["parts"],
+            json!([
+                { "inlineData": { "mimeType": "image/png", "data": "AQID" } },
+                { "text": "Hi" },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_function_call() {
+        let (url, handle) = mock_server(vec![MockResponse::new(
+            200,
+            vec![
+                "data: {\"candidates\": [{\"content\": {\"parts\": [{\"functionCall\": {\"name\": \"get_weather\",\"args\": {\"city\": \"Rome\"}}}],\"role\": \"model\"},",
+                "\"finishReason\": \"STOP\",\"index\": 0}]}\r\n\r\n",
+            ],
+        )
+        .header("content-type", "text/event-stream")])
+        .await;
+
+        let mut request = create_request();
+        let mut assistant = ChatMessage::new(Role::Assistant, "");
+        assistant.tool_calls = vec![ToolCall {
+            id: "call_1".to_string(),
+            name: "get_weather".to_string(),
+            arguments: "{\"city\":\"Paris\"}".to_string(),
+        }];
+        let mut tool = ChatMessage::new(Role::Tool, "sunny");
+        tool.tool_call_id = Some("call_1".to_string());
+        request.messages.extend([assistant, tool]);
+        request.params.tools = Some(vec![ChatTool {
+            name: "get_weather".to_string(),
+            description: Some("Current weather".to_string()),
+            parameters: json!({ "type": "object" }),
+        }]);
+        request.params.tool_choice = Some(ChatToolChoice::Function {
+            name: "get_weather".to_string(),
+        });
+
+        let api = GeminiChatApi::new(Client::new(None), &url);
+        let stream = api.send_message(request.into()).await.unwrap();
+        let contents = stream.collect::<Vec<StreamContent>>().await;
+
+        assert!(matches!(
+            &contents[..],
+            [StreamContent::ToolCall(tool_call), StreamContent::Meta(meta), StreamContent::Done]
+                if tool_call.name == "get_weather"
+                && tool_call.arguments == "{\"city\":\"Rome\"}"
+                && !tool_call.id.is_empty()
+                && meta.finish_reason == Some(FinishReason::ToolCalls)
+        ));
+
+        let requests = handle.await.unwrap();
+        let (_, body) = requests[0].split_once("\r\n\r\n").unwrap();
+        let body = serde_json::from_str::<serde_json::Value>(body).unwrap();
+        assert_eq!(
+            body["tools"],
+            json!([{
+                "functionDeclarations": [{
+                    "name": "get_weather",
+                    "description": "Current weather",
+                    "parametersJsonSchema": { "type": "object" },
+                }],
+            }])
+        );
+        assert_eq!(
+            body["toolConfig"],
+            json!({
+                "functionCallingConfig": { "mode": "ANY", "allowedFunctionNames": ["get_weather"] },
+            })
+        );
+        // The function result is sent back by name in a user turn
+        assert_eq!(
+            body["contents"][3],
+            json!({
+                "role": "model",
+                "parts": [{ "functionCall": { "name": "get_weather", "args": { "city": "Paris" } } }],
+            })
+        );
+        assert_eq!(
+            body["contents"][4],
+            json!({
+                "role": "user",
+                "parts": [{
+                    "functionResponse": { "name": "get_weather", "response": { "content": "sunny" } },
+                }],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_error_response() {
+        let (url, _) = mock_server(vec![MockResponse::new(
+            400,
+            vec!["[{\"error\": {\"code\": 400, \"message\": \"API key not valid. Please pass a valid API key.\", \"status\": \"INVALID_ARGUMENT\"}}]"],
+        )])
+        .await;
+
+        let api = GeminiChatApi::new(Client::new(None), &url);
+        let stream = api.send_message(create_request().into()).await.unwrap();
+        let contents = stream.collect::<Vec<StreamContent>>().await;
+
+        assert!(matches!(
+            &contents[..],
+            [StreamContent::Error(StreamError::Api(ApiError {
+                kind: ApiErrorKind::InvalidKey,
+                status: Some(400),
+                ..
+            }))]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_blocked_prompt() {
+        let (url, _) = mock_server(vec![MockResponse::new(
+            200,
+            vec!["data: {\"promptFeedback\": {\"blockReason\": \"SAFETY\"},\"usageMetadata\": {\"promptTokenCount\": 9}}\r\n\r\n"],
+        )])
+        .await;
+
+        let api = GeminiChatApi::new(Client::new(None), &url);
+        let stream = api.send_message(create_request().into()).await.unwrap();
+        let contents = stream.collect::<Vec<StreamContent>>().await;
+
+        assert!(matches!(
+            &contents[..],
+            [StreamContent::Error(StreamError::Api(ApiError {
+                kind: ApiErrorKind::ContentPolicy(message),
+                ..
+            }))] if message.contains("SAFETY")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stream_ends_without_finish_reason() {
+        let (url, _) = mock_server(vec![MockResponse::new(
+            200,
+            vec!["data: {\"candidates\": [{\"content\": {\"parts\": [{\"text\": \"Fine\"}],\"role\": \"model\"},\"index\": 0}]}\r\n\r\n"],
+        )])
+        .await;
+
+        let api = GeminiChatApi::new(Client::new(None), &url);
+        let stream = api.send_message(create_request().into()).await.unwrap();
+        let contents = stream.collect::<Vec<StreamContent>>().await;
+
+        assert!(matches!(
+            &contents[..],
+            [
+                StreamContent::Data(data),
+                StreamContent::Error(StreamError::Unknown(_)),
+            ] if data == "Fine"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_unavailable() {
+        let (url, handle) = mock_server(vec![
+            MockResponse::new(503, vec!["{}"]).header("retry-after", "0"),
+            MockResponse::new(
+                200,
+                vec!["data: {\"candidates\": [{\"content\": {\"parts\": [{\"text\": \"Hi\"}],\"role\": \"model\"},\"finishReason\": \"STOP\",\"index\": 0}]}\r\n\r\n"],
+            ),
+        ])
+        .await;
+
+        let mut client = Client::new(None);
+        client.retry_policy(RetryPolicy {
+            max_retries: 2,
+            ..Default::default()
+        });
+        let api = GeminiChatApi::new(client, &url);
+        let stream = api.send_message(create_request().into()).await.unwrap();
+        let contents = stream.collect::<Vec<StreamContent>>().await;
+
+        assert!(matches!(
+            &contents[..],
+            [StreamContent::Retry(attempt), StreamContent::Data(data), StreamContent::Meta(_), StreamContent::Done]
+                if attempt.attempt == 1 && attempt.max_retries == 2 && data == "Hi"
+        ));
+        assert_eq!(handle.await.unwrap().len(), 2);
+    }
+}
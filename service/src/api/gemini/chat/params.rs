@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+
+use crate::api::backend::{ChatMessage, ChatRequest};
+use crate::models::chat::{ChatResponseFormat, ChatTool, ChatToolChoice};
+use crate::models::chat_log::Role;
+
+#[derive(serde::Serialize, serde::Deserialize, Default, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiChatParams {
+    /// The model is part of the url, it is kept here to build it.
+    #[serde(skip)]
+    pub model: String,
+
+    /// The conversation history, alternating between `user` and `model` turns.
+    pub contents: Vec<GeminiContent>,
+
+    /// System instructions, Gemini does not accept a system role in `contents`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<GeminiContent>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generation_config: Option<GeminiGenerationConfig>,
+
+    /// Functions the model may call.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<GeminiTool>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_config: Option<GeminiToolConfig>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Default, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiGenerationConfig {
+    /// Controls the randomness of the output, between 0 and 2.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+
+    /// The maximum cumulative probability of tokens to consider when sampling.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+
+    /// Character sequences that will stop output generation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+
+    /// The maximum number of tokens to include in a candidate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f64>,
+
+    /// Seed used in decoding.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+
+    /// MIME type of the generated candidate text, `application/json` for JSON replies.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_mime_type: Option<String>,
+
+    /// JSON Schema the generated candidate text must match.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_json_schema: Option<serde_json::Value>,
+}
+
+impl From<ChatRequest> for GeminiChatParams {
+    fn from(request: ChatRequest) -> Self {
+        let ChatRequest { messages, params } = request;
+
+        let mut system: Vec<GeminiPart> = vec![];
+        let mut contents: Vec<GeminiContent> = vec![];
+        // Gemini answers function calls by name rather than by call id
+        let mut function_names = HashMap::new();
+        for message in messages {
+            let ChatMessage {
+                role,
+                content,
+                tool_calls,
+                tool_call_id,
+                images,
+            } = message;
+
+            let mut parts = vec![];
+            let role = match role {
+                Role::System => {
+                    system.push(GeminiPart::text(content));
+                    continue;
+                }
+                Role::User => {
+                    // Images go before the text that refers to them
+                    parts.extend(images.iter().map(|image| GeminiPart {
+                        inline_data: Some(GeminiBlob {
+                            mime_type: image.mime_type.clone(),
+                            data: image.base64(),
+                        }),
+                        ..Default::default()
+                    }));
+                    parts.push(GeminiPart::text(content));
+                    GeminiRole::User
+                }
+                Role::Tool => {
+                    let name = tool_call_id
+                        .and_then(|id| function_names.get(&id).cloned())
+                        .unwrap_or_default();
+                    parts.push(GeminiPart {
+                        function_response: Some(GeminiFunctionResponse {
+                            name,
+                            response: serde_json::json!({ "content": content }),
+                        }),
+                        ..Default::default()
+                    });
+                    GeminiRole::User
+                }
+                Role::Assistant => {
+                    if !content.is_empty() || tool_calls.is_empty() {
+                        parts.push(GeminiPart::text(content));
+                    }
+                    for tool_call in tool_calls {
+                        function_names.insert(tool_call.id, tool_call.name.clone());
+                        parts.push(GeminiPart {
+                            function_call: Some(GeminiFunctionCall {
+                                name: tool_call.name,
+                                args: serde_json::from_str(&tool_call.arguments)
+                                    .unwrap_or_else(|_| serde_json::json!({})),
+                            }),
+                            ..Default::default()
+                        });
+                    }
+                    GeminiRole::Model
+                }
+            };
+
+            // Consecutive messages of the same role become parts of one turn
+            match contents.last_mut() {
+                Some(last) if last.role == Some(role.clone()) => last.parts.extend(parts),
+                _ => contents.push(GeminiContent {
+                    role: Some(role),
+                    parts,
+                }),
+            }
+        }
+
+        let (response_mime_type, response_json_schema) = match params.response_format {
+            None => (None, None),
+            Some(ChatResponseFormat::Text) => (Some("text/plain".to_string()), None),
+            Some(ChatResponseFormat::JsonObject) => (Some("application/json".to_string()), None),
+            Some(ChatResponseFormat::JsonSchema { schema, .. }) => {
+                (Some("application/json".to_string()), Some(schema))
+            }
+        };
+        let generation_config = GeminiGenerationConfig {
+            temperature: params.temperature,
+            top_p: params.top_p,
+            stop_sequences: params.stop,
+            max_output_tokens: params.max_tokens.or(params.max_completion_tokens),
+            presence_penalty: params.presence_penalty,
+            frequency_penalty: params.frequency_penalty,
+            seed: params.seed,
+            response_mime_type,
+            response_json_schema,
+        };
+
+        Self {
+            model: params.model,
+            contents,
+            system_instruction: if system.is_empty() {
+                None
+            } else {
+                Some(GeminiContent {
+                    role: None,
+                    parts: system,
+                })
+            },
+            generation_config: Some(generation_config),
+            tools: params.tools.map(|tools| {
+                vec![GeminiTool {
+                    function_declarations: tools.into_iter().map(Into::into).collect(),
+                }]
+            }),
+            tool_config: params.tool_choice.map(Into::into),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct GeminiContent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<GeminiRole>,
+    pub parts: Vec<GeminiPart>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Default, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiPart {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inline_data: Option<GeminiBlob>,
+
+    /// A function call of a model turn
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function_call: Option<GeminiFunctionCall>,
+
+    /// The result of a function call, in the following user turn
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function_response: Option<GeminiFunctionResponse>,
+}
+
+impl GeminiPart {
+    pub fn text(text: String) -> Self {
+        Self {
+            text: Some(text),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiBlob {
+    pub mime_type: String,
+    /// Base64 encoded bytes
+    pub data: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct GeminiFunctionCall {
+    pub name: String,
+    #[serde(default)]
+    pub args: serde_json::Value,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct GeminiFunctionResponse {
+    pub name: String,
+    /// Gemini only accepts an object here
+    pub response: serde_json::Value,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiTool {
+    pub function_declarations: Vec<GeminiFunctionDeclaration>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiFunctionDeclaration {
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// JSON Schema of the function arguments.
+    pub parameters_json_schema: serde_json::Value,
+}
+
+impl From<ChatTool> for GeminiFunctionDeclaration {
+    fn from(tool: ChatTool) -> Self {
+        Self {
+            name: tool.name,
+            description: tool.description,
+            parameters_json_schema: tool.parameters,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiToolConfig {
+    pub function_calling_config: GeminiFunctionCallingConfig,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiFunctionCallingConfig {
+    /// `AUTO`, `ANY` or `NONE`
+    pub mode: String,
+
+    /// Functions the model may call in the `ANY` mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_function_names: Option<Vec<String>>,
+}
+
+impl From<ChatToolChoice> for GeminiToolConfig {
+    fn from(choice: ChatToolChoice) -> Self {
+        let (mode, allowed_function_names) = match choice {
+            ChatToolChoice::None => ("NONE", None),
+            ChatToolChoice::Auto => ("AUTO", None),
+            ChatToolChoice::Required => ("ANY", None),
+            ChatToolChoice::Function { name } => ("ANY", Some(vec![name])),
+        };
+
+        Self {
+            function_calling_config: GeminiFunctionCallingConfig {
+                mode: mode.to_string(),
+                allowed_function_names,
+            },
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Eq, Clone, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum GeminiRole {
+    User,
+    Model,
+}
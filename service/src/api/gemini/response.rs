@@ -0,0 +1,33 @@
+use crate::error::ApiErrorKind;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct GeminiErrorResponse {
+    pub error: GeminiResponseError,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct GeminiResponseError {
+    pub code: u16,
+    pub message: String,
+    pub status: String,
+}
+
+/// Error responses may also be wrapped in an array like the non-sse stream.
+#[derive(serde::Deserialize, Debug)]
+#[serde(untagged)]
+pub enum GeminiErrorBody {
+    Object(GeminiErrorResponse),
+    Array(Vec<GeminiErrorResponse>),
+}
+
+impl From<GeminiErrorBody> for ApiErrorKind {
+    fn from(body: GeminiErrorBody) -> Self {
+        match body {
+            GeminiErrorBody::Object(err) => err.into(),
+            GeminiErrorBody::Array(mut errors) => match errors.pop() {
+                Some(err) => err.into(),
+                None => ApiErrorKind::Unknown("empty error response".to_string()),
+            },
+        }
+    }
+}
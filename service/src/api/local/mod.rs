@@ -0,0 +1,99 @@
+use crate::{api::client::Client, result::Result};
+
+/// Local inference servers (Ollama, llama.cpp server, vLLM, ...).
+///
+/// They all speak the OpenAI chat completions protocol, so chatting goes
+/// through [`OpenAIChatApi`](crate::api::openai::chat::OpenAIChatApi), this
+/// api only discovers the models a server provides.
+pub struct LocalApi {
+    client: Client,
+    host: String,
+}
+
+impl LocalApi {
+    pub fn new(client: Client, host: &str) -> Self {
+        Self {
+            client,
+            host: host.to_string(),
+        }
+    }
+
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        // Ollama
+        let res = self.client.get(&(self.host.clone() + "/api/tags")).await?;
+        if res.status().is_success() {
+            if let Ok(tags) = res.json::<OllamaTags>().await {
+                return Ok(tags.models.into_iter().map(|model| model.name).collect());
+            }
+        }
+
+        // OpenAI compatible servers
+        let res = self.client.get(&(self.host.clone() + "/v1/models")).await?;
+        let models = res.json::<LocalModelList>().await?;
+
+        Ok(models.data.into_iter().map(|model| model.id).collect())
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct OllamaTags {
+    pub models: Vec<OllamaTag>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct OllamaTag {
+    pub name: String,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct LocalModelList {
+    pub data: Vec<LocalModel>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct LocalModel {
+    pub id: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        api::client::Client,
+        test::{mock_server, MockResponse},
+    };
+
+    use super::LocalApi;
+
+    #[tokio::test]
+    async fn test_list_ollama_models() {
+        let (url, handle) = mock_server(vec![MockResponse::new(
+            200,
+            vec![r#"{"models":[{"name":"llama3:latest","size":1},{"name":"qwen2:7b"}]}"#],
+        )])
+        .await;
+
+        let api = LocalApi::new(Client::new(None), &url);
+        let models = api.list_models().await.unwrap();
+
+        assert_eq!(models, vec!["llama3:latest", "qwen2:7b"]);
+        assert!(handle.await.unwrap()[0].starts_with("GET /api/tags"));
+    }
+
+    #[tokio::test]
+    async fn test_list_openai_compatible_models() {
+        let (url, handle) = mock_server(vec![
+            MockResponse::new(404, vec!["Not Found"]),
+            MockResponse::new(
+                200,
+                vec![r#"{"object":"list","data":[{"id":"mistral-7b-instruct","object":"model"}]}"#],
+            ),
+        ])
+        .await;
+
+        let api = LocalApi::new(Client::new(None), &url);
+        let models = api.list_models().await.unwrap();
+
+        assert_eq!(models, vec!["mistral-7b-instruct"]);
+        assert!(handle.await.unwrap()[1].starts_with("GET /v1/models"));
+    }
+}
@@ -1,2 +1,8 @@
+pub mod anthropic;
+pub mod backend;
 pub mod client;
+pub mod gemini;
+pub mod local;
 pub mod openai;
+pub mod sse;
+pub mod tokenizer;
@@ -1,94 +1,110 @@
-use std::pin::Pin;
+use futures::{channel::mpsc, stream, StreamExt};
 
-use futures::{stream, Stream, StreamExt};
+use crate::{
+    api::backend::{ChatBackend, ChatRequest, ChatStream},
+    api::client::{self, Client},
+    api::sse::{self, SseEvent},
+    error::{ApiError, StreamError},
+    models::chat_log::{FinishReason, ToolCall},
+    result::Result,
+    types::{StreamChoice, StreamContent, StreamMeta},
+};
 
-use crate::{api::client::Client, result::Result, types::StreamContent, Error};
+use self::params::{OpenAIChatParams, OpenAIChatRole, OpenAIStreamOptions};
 
-use self::params::{OpenAIChatParams, OpenAIChatRole};
-
-use super::response::OpenAIErrorResponse;
+use super::response::{OpenAIErrorResponse, OpenAIResponseError};
 
 pub mod params;
 
 pub struct OpenAIChatApi {
     client: Client,
-    host: String,
+    url: String,
+    include_usage: bool,
 }
 
 impl OpenAIChatApi {
     pub fn new(client: Client, host: &str) -> Self {
+        Self::with_url(client, &(host.to_string() + "/v1/chat/completions"))
+    }
+
+    /// Create the api with the full chat completions url,
+    /// for endpoints not following the `/v1/chat/completions` layout (e.g. Azure).
+    pub fn with_url(client: Client, url: &str) -> Self {
         Self {
             client,
-            host: host.to_string(),
+            url: url.to_string(),
+            include_usage: false,
         }
     }
 
-    pub async fn send_message(
-        &self,
-        params: OpenAIChatParams,
-    ) -> Result<Pin<Box<dyn Stream<Item = StreamContent> + Send + '_>>> {
-        let url = self.host.clone() + "/v1/chat/completions";
+    /// Ask for the usage in the last chunk with `stream_options`,
+    /// proxies, local servers and older Azure api versions reject the request with it.
+    pub fn include_usage(&mut self, include_usage: bool) -> &mut Self {
+        self.include_usage = include_usage;
+        self
+    }
+
+    pub async fn send_message(&self, mut params: OpenAIChatParams) -> Result<ChatStream> {
+        let url = self.url.clone();
+        if self.include_usage {
+            params.stream_options = Some(OpenAIStreamOptions {
+                include_usage: true,
+            });
+        }
 
         log::debug!("url: {}", url);
         log::debug!("params: {:?}", params);
 
-        let res = self.client.post(&url, params).await?;
+        let client = self.client.clone();
 
-        let stream = res.bytes_stream();
-
-        let mut left_chunk: Option<Vec<u8>> = None;
-        let mut left_line: Option<String> = None;
-        let stream = stream
-            .flat_map(move |chunk| {
-                if let Err(err) = chunk {
-                    let err: Error = err.into();
-                    return stream::iter(vec![StreamContent::Error(err.into())]);
+        // Retries happen before the response is streamed, their events are
+        // merged into the stream as they occur
+        let (retry_sender, retry_receiver) = mpsc::unbounded::<StreamContent>();
+        let response = stream::once(async move {
+            let on_retry = move |attempt| {
+                retry_sender
+                    .unbounded_send(StreamContent::Retry(attempt))
+                    .ok();
+            };
+            match client.post_with_retry(&url, params, on_retry).await {
+                Ok(res) if res.status().is_success() => parse_response(res),
+                Ok(res) => {
+                    let err = client::parse_error::<OpenAIErrorResponse>(res).await;
+                    stream::iter(vec![StreamContent::Error(err.into())]).boxed()
                 }
+                Err(err) => stream::iter(vec![StreamContent::Error(err.into())]).boxed(),
+            }
+        })
+        .flatten();
 
-                let mut vec = chunk.unwrap().to_vec();
-                if let Some(left_chunk) = left_chunk.take() {
-                    vec = [left_chunk, vec].concat();
-                }
+        let stream = stream::select(retry_receiver, response).boxed();
 
-                let Ok(data) = String::from_utf8(vec.clone()) else {
-                    left_chunk = Some(vec);
-                    return stream::iter(vec![]);
-                };
+        Ok(stream)
+    }
+}
 
-                if data.starts_with("{\n    \"error\"") || data.starts_with("{\n  \"error\"") {
-                    let res = serde_json::from_str::<OpenAIErrorResponse>(&data).unwrap();
-                    let err: Error = res.into();
-                    return stream::iter(vec![StreamContent::Error(err.into())]);
+fn parse_response(res: reqwest::Response) -> ChatStream {
+    let request_id = client::request_id(&res);
+    let mut tool_calls = ToolCallAccumulator::default();
+    let mut meta = StreamMeta::default();
+    sse::decode_response(res)
+        .flat_map(move |event| {
+            let contents = match event {
+                Ok(event) => {
+                    log::debug!("event: {:?}", event);
+                    handle_event(&event, &request_id, &mut tool_calls, &mut meta)
                 }
+                Err(err) => vec![StreamContent::Error(err.into())],
+            };
+            stream::iter(contents)
+        })
+        .boxed()
+}
 
-                let chunks = data
-                    .lines()
-                    .filter_map(|line| {
-                        let line = line.trim();
-                        log::debug!("line: {}", line);
-                        if line.is_empty() {
-                            None
-                        } else if line.starts_with("data: [DONE]") {
-                            Some(StreamContent::Done)
-                        } else {
-                            let line = left_line.take().unwrap_or_default() + line;
-                            match handle_line(&line) {
-                                Some(Some(content)) => Some(content),
-                                Some(None) => None,
-                                None => {
-                                    left_line = Some(line);
-                                    None
-                                }
-                            }
-                        }
-                    })
-                    .collect::<Vec<StreamContent>>();
-
-                stream::iter(chunks)
-            })
-            .boxed();
-
-        Ok(stream)
+#[async_trait::async_trait]
+impl ChatBackend for OpenAIChatApi {
+    async fn send_message(&self, request: ChatRequest) -> Result<ChatStream> {
+        OpenAIChatApi::send_message(self, request.into()).await
     }
 }
 
@@ -96,7 +112,18 @@ impl OpenAIChatApi {
 pub struct OpenAIStreamChunk {
     pub object: Option<String>,
     pub model: Option<String>,
+    /// Empty in the last chunk carrying the usage
+    #[serde(default)]
     pub choices: Vec<OpenAIStreamChunkChoice>,
+    /// Only sent when `stream_options.include_usage` is set
+    pub usage: Option<OpenAIUsage>,
+    pub error: Option<OpenAIResponseError>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct OpenAIUsage {
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
 }
 
 #[derive(serde::Deserialize, Debug)]
@@ -112,38 +139,402 @@ pub enum OpenAIFinishReason {
     Stop,
     Length,
     ContentFilter,
+    ToolCalls,
+    FunctionCall,
+    #[serde(other)]
+    Other,
+}
+
+impl From<&OpenAIFinishReason> for FinishReason {
+    fn from(reason: &OpenAIFinishReason) -> Self {
+        match reason {
+            OpenAIFinishReason::Stop => FinishReason::Stop,
+            OpenAIFinishReason::Length => FinishReason::Length,
+            OpenAIFinishReason::ContentFilter => FinishReason::ContentFilter,
+            OpenAIFinishReason::ToolCalls | OpenAIFinishReason::FunctionCall => {
+                FinishReason::ToolCalls
+            }
+            OpenAIFinishReason::Other => FinishReason::Other,
+        }
+    }
 }
 
 #[derive(serde::Deserialize, Debug)]
 pub struct OpenAIStreamChunkChoiceDelta {
     pub role: Option<OpenAIChatRole>,
     pub content: Option<String>,
+    /// Sent by compatible servers serving reasoning models, e.g. DeepSeek
+    pub reasoning_content: Option<String>,
+    pub tool_calls: Option<Vec<OpenAIToolCallDelta>>,
 }
 
-fn handle_line(line: &str) -> Option<Option<StreamContent>> {
-    log::debug!("handle_line: {}", line);
-    if !line.starts_with("data:") {
-        return None;
+/// A fragment of a tool call, `id` and `name` only come with the first one.
+#[derive(serde::Deserialize, Debug)]
+pub struct OpenAIToolCallDelta {
+    pub index: usize,
+    pub id: Option<String>,
+    pub function: Option<OpenAIFunctionCallDelta>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct OpenAIFunctionCallDelta {
+    pub name: Option<String>,
+    pub arguments: Option<String>,
+}
+
+/// Joins the streamed fragments of tool calls.
+#[derive(Default)]
+struct ToolCallAccumulator {
+    tool_calls: Vec<ToolCall>,
+}
+
+impl ToolCallAccumulator {
+    fn push(&mut self, delta: &OpenAIToolCallDelta) {
+        if self.tool_calls.len() <= delta.index {
+            self.tool_calls.resize_with(delta.index + 1, || ToolCall {
+                id: String::new(),
+                name: String::new(),
+                arguments: String::new(),
+            });
+        }
+
+        let tool_call = &mut self.tool_calls[delta.index];
+        if let Some(id) = &delta.id {
+            tool_call.id.push_str(id);
+        }
+        if let Some(function) = &delta.function {
+            if let Some(name) = &function.name {
+                tool_call.name.push_str(name);
+            }
+            if let Some(arguments) = &function.arguments {
+                tool_call.arguments.push_str(arguments);
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Vec<StreamContent> {
+        self.tool_calls
+            .drain(..)
+            .map(StreamContent::ToolCall)
+            .collect()
+    }
+}
+
+fn handle_event(
+    event: &SseEvent,
+    request_id: &Option<String>,
+    tool_calls: &mut ToolCallAccumulator,
+    meta: &mut StreamMeta,
+) -> Vec<StreamContent> {
+    if event.data == "[DONE]" {
+        // Servers not sending the `tool_calls` finish reason
+        let mut contents = tool_calls.finish();
+        contents.push(StreamContent::Meta(std::mem::take(meta)));
+        contents.push(StreamContent::Done);
+        return contents;
     }
-    let json_data = if line.starts_with("data: {") {
-        &line[6..]
-    } else if line.starts_with("data:{") {
-        &line[5..]
-    } else {
-        return None;
+
+    let json = match event.json::<OpenAIStreamChunk>() {
+        Ok(json) => json,
+        Err(err) => return vec![StreamContent::Error(err.into())],
     };
-    match serde_json::from_str::<OpenAIStreamChunk>(json_data) {
-        Ok(json) => {
-            let stream_content = json.choices.get(0).and_then(|choice| {
-                choice
-                    .delta
-                    .content
-                    .as_ref()
-                    .map(|content| StreamContent::Data(content.to_string()))
-            });
 
-            Some(stream_content)
+    // Compatible servers may report errors in the stream
+    if let Some(error) = json.error {
+        let err = ApiError::new(OpenAIErrorResponse { error }.into())
+            .with_request_id(request_id.clone());
+        return vec![StreamContent::Error(StreamError::Api(err))];
+    }
+
+    if json.model.is_some() {
+        meta.model = json.model.clone();
+    }
+    if let Some(usage) = &json.usage {
+        meta.prompt_tokens = Some(usage.prompt_tokens);
+        meta.completion_tokens = Some(usage.completion_tokens);
+    }
+
+    let mut contents = vec![];
+    for choice in &json.choices {
+        // Additional replies only stream their content
+        let index = choice.index.unwrap_or_default();
+        if index > 0 {
+            if let Some(finish_reason) = &choice.finish_reason {
+                if meta.choice_finish_reasons.len() < index {
+                    meta.choice_finish_reasons.resize(index, None);
+                }
+                meta.choice_finish_reasons[index - 1] = Some(finish_reason.into());
+            }
+            if let Some(content) = &choice.delta.content {
+                contents.push(StreamContent::Choice(StreamChoice {
+                    index,
+                    data: content.to_string(),
+                }));
+            }
+            continue;
+        }
+
+        if let Some(finish_reason) = &choice.finish_reason {
+            meta.finish_reason = Some(finish_reason.into());
+        }
+        if let Some(reasoning) = &choice.delta.reasoning_content {
+            contents.push(StreamContent::Reasoning(reasoning.to_string()));
+        }
+        if let Some(content) = &choice.delta.content {
+            contents.push(StreamContent::Data(content.to_string()));
         }
-        Err(_err) => None,
+        for delta in choice.delta.tool_calls.iter().flatten() {
+            tool_calls.push(delta);
+        }
+        if let Some(OpenAIFinishReason::ToolCalls) = choice.finish_reason {
+            contents.extend(tool_calls.finish());
+        }
+    }
+
+    contents
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use crate::{
+        api::client::{Client, RetryPolicy},
+        error::{ApiError, ApiErrorKind, StreamError},
+        models::chat_log::{FinishReason, ToolCall},
+        test::{mock_server, MockResponse},
+        StreamContent,
+    };
+
+    use super::{params::OpenAIChatParams, OpenAIChatApi};
+
+    #[tokio::test]
+    async fn test_tool_calls() {
+        let (url, _) = mock_server(vec![MockResponse::new(
+            200,
+            vec![
+                "data: {\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":null,\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"type\":\"function\",\"function\":{\"name\":\"get_weather\",\"arguments\":\"\"}}]}}]}\n\n",
+                "data: {\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"{\\\"city\\\":\"}}]}}]}\n\n",
+                "data: {\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"\\\"Paris\\\"}\"}}]}}]}\n\n",
+                "data: {\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"tool_calls\"}]}\n\n",
+                "data: {\"model\":\"gpt-4o-2024-08-06\",\"choices\":[],\"usage\":{\"prompt_tokens\":52,\"completion_tokens\":17,\"total_tokens\":69}}\n\ndata: [DONE]\n\n",
+            ],
+        )])
+        .await;
+
+        let api = OpenAIChatApi::new(Client::new(None), &url);
+        let stream = api.send_message(OpenAIChatParams::default()).await.unwrap();
+        let contents = stream.collect::<Vec<StreamContent>>().await;
+
+        assert!(matches!(
+            &contents[..],
+            [StreamContent::ToolCall(tool_call), StreamContent::Meta(meta), StreamContent::Done]
+                if *tool_call == ToolCall {
+                    id: "call_1".to_string(),
+                    name: "get_weather".to_string(),
+                    arguments: "{\"city\":\"Paris\"}".to_string(),
+                }
+                && meta.finish_reason == Some(FinishReason::ToolCalls)
+                && meta.model.as_deref() == Some("gpt-4o-2024-08-06")
+                && meta.prompt_tokens == Some(52)
+                && meta.completion_tokens == Some(17)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_include_usage() {
+        let response = vec!["data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hi\"},\"finish_reason\":\"stop\"}]}\n\ndata: [DONE]\n\n"];
+        let (url, handle) = mock_server(vec![
+            MockResponse::new(200, response.clone()),
+            MockResponse::new(200, response),
+        ])
+        .await;
+
+        let mut api = OpenAIChatApi::new(Client::new(None), &url);
+        for include_usage in [false, true] {
+            api.include_usage(include_usage);
+            let stream = api.send_message(OpenAIChatParams::default()).await.unwrap();
+            stream.collect::<Vec<StreamContent>>().await;
+        }
+
+        let requests = handle.await.unwrap();
+        let bodies = requests
+            .iter()
+            .map(|request| {
+                let (_, body) = request.split_once("\r\n\r\n").unwrap();
+                serde_json::from_str::<serde_json::Value>(body).unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert!(bodies[0].get("stream_options").is_none());
+        assert_eq!(
+            bodies[1]["stream_options"],
+            serde_json::json!({ "include_usage": true })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reasoning_content() {
+        let (url, _) = mock_server(vec![MockResponse::new(
+            200,
+            vec![
+                "data: {\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":null,\"reasoning_content\":\"The user greets.\"}}]}\n\n",
+                "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hi\",\"reasoning_content\":null},\"finish_reason\":\"stop\"}]}\n\ndata: [DONE]\n\n",
+            ],
+        )])
+        .await;
+
+        let api = OpenAIChatApi::new(Client::new(None), &url);
+        let stream = api.send_message(OpenAIChatParams::default()).await.unwrap();
+        let contents = stream.collect::<Vec<StreamContent>>().await;
+
+        assert!(matches!(
+            &contents[..],
+            [StreamContent::Reasoning(reasoning), StreamContent::Data(data), StreamContent::Meta(_), StreamContent::Done]
+                if reasoning == "The user greets." && data == "Hi"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_multiple_choices() {
+        let (url, _) = mock_server(vec![MockResponse::new(
+            200,
+            vec![
+                "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hi\"}}]}\n\n",
+                "data: {\"choices\":[{\"index\":1,\"delta\":{\"content\":\"Hello\"}}]}\n\n",
+                "data: {\"choices\":[{\"index\":1,\"delta\":{},\"finish_reason\":\"length\"}]}\n\n",
+                "data: {\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}]}\n\ndata: [DONE]\n\n",
+            ],
+        )])
+        .await;
+
+        let api = OpenAIChatApi::new(Client::new(None), &url);
+        let params = OpenAIChatParams {
+            n: Some(2),
+            ..Default::default()
+        };
+        let stream = api.send_message(params).await.unwrap();
+        let contents = stream.collect::<Vec<StreamContent>>().await;
+
+        assert!(matches!(
+            &contents[..],
+            [StreamContent::Data(data), StreamContent::Choice(choice), StreamContent::Meta(meta), StreamContent::Done]
+                if data == "Hi"
+                && choice.index == 1
+                && choice.data == "Hello"
+                && meta.finish_reason == Some(FinishReason::Stop)
+                && meta.choice_finish_reasons == [Some(FinishReason::Length)]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_rate_limit() {
+        let (url, handle) = mock_server(vec![
+            MockResponse::new(429, vec!["{}"]).header("retry-after", "0"),
+            MockResponse::new(
+                200,
+                vec!["data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hi\"}}]}\n\ndata: [DONE]\n\n"],
+            ),
+        ])
+        .await;
+
+        let mut client = Client::new(None);
+        client.retry_policy(RetryPolicy {
+            max_retries: 2,
+            ..Default::default()
+        });
+        let api = OpenAIChatApi::new(client, &url);
+        let stream = api.send_message(OpenAIChatParams::default()).await.unwrap();
+        let contents = stream.collect::<Vec<StreamContent>>().await;
+
+        assert!(matches!(
+            &contents[..],
+            [StreamContent::Retry(attempt), StreamContent::Data(data), StreamContent::Meta(_), StreamContent::Done]
+                if attempt.attempt == 1 && attempt.max_retries == 2 && data == "Hi"
+        ));
+        assert_eq!(handle.await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_error_response() {
+        let (url, _) = mock_server(vec![MockResponse::new(
+            401,
+            vec!["{\n    \"error\": {\n        \"message\": \"Incorrect API key provided\",\n        \"type\": \"invalid_request_error\",\n        \"param\": null,\n        \"code\": \"invalid_api_key\"\n    }\n}\n"],
+        )])
+        .await;
+
+        let api = OpenAIChatApi::new(Client::new(None), &url);
+        let stream = api.send_message(OpenAIChatParams::default()).await.unwrap();
+        let contents = stream.collect::<Vec<StreamContent>>().await;
+
+        assert!(matches!(
+            &contents[..],
+            [StreamContent::Error(StreamError::Api(ApiError {
+                kind: ApiErrorKind::InvalidKey,
+                status: Some(401),
+                ..
+            }))]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_event() {
+        let (url, _) = mock_server(vec![MockResponse::new(
+            200,
+            vec!["data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hi\"}}]}\r\n\r\ndata: {\"choices\": 1}\r\n\r\n"],
+        )])
+        .await;
+
+        let api = OpenAIChatApi::new(Client::new(None), &url);
+        let stream = api.send_message(OpenAIChatParams::default()).await.unwrap();
+        let contents = stream.collect::<Vec<StreamContent>>().await;
+
+        assert!(matches!(
+            &contents[..],
+            [StreamContent::Data(data), StreamContent::Error(StreamError::Api(ApiError {
+                kind: ApiErrorKind::InvalidResponse(_),
+                ..
+            }))]
+                if data == "Hi"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_context_length_exceeded() {
+        let (url, _) = mock_server(vec![MockResponse::new(
+            400,
+            vec![r#"{"error":{"message":"This model's maximum context length is 8192 tokens.","type":"invalid_request_error","param":"messages","code":"context_length_exceeded"}}"#],
+        )
+        .header("x-request-id", "req_456")])
+        .await;
+
+        let api = OpenAIChatApi::new(Client::new(None), &url);
+        let stream = api.send_message(OpenAIChatParams::default()).await.unwrap();
+        let contents = stream.collect::<Vec<StreamContent>>().await;
+
+        assert!(matches!(
+            &contents[..],
+            [StreamContent::Error(StreamError::Api(ApiError {
+                kind: ApiErrorKind::ContextLengthExceeded(_),
+                status: Some(400),
+                request_id: Some(request_id),
+            }))] if request_id == "req_456"
+        ));
+
+        let json = match &contents[0] {
+            StreamContent::Error(err) => serde_json::to_value(err).unwrap(),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "type": "api",
+                "error": {
+                    "type": "ContextLengthExceeded",
+                    "message": "This model's maximum context length is 8192 tokens.",
+                    "status": 400,
+                    "requestId": "req_456",
+                },
+            })
+        );
     }
 }
@@ -1,6 +1,10 @@
+use std::collections::HashMap;
 use std::fmt::Display;
 
-use tiktoken_rs::{self, cl100k_base, model::get_context_size};
+use crate::api::backend::{ChatMessage, ChatRequest};
+use crate::api::tokenizer::Tokenizer;
+use crate::models::chat::{ChatResponseFormat, ChatTool, ChatToolChoice};
+use crate::models::chat_log::ToolCall;
 
 #[derive(serde::Serialize, serde::Deserialize, Default, Clone, Debug)]
 pub struct OpenAIChatParams {
@@ -39,6 +43,10 @@ pub struct OpenAIChatParams {
     /// with the stream terminated by a data: [DONE] message.
     pub stream: bool,
 
+    /// Options for streaming response.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<OpenAIStreamOptions>,
+
     /// Up to 4 sequences where the API will stop generating further tokens.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stop: Option<Vec<String>>,
@@ -49,6 +57,13 @@ pub struct OpenAIChatParams {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_tokens: Option<u32>,
 
+    /// An upper bound for the number of tokens that can be generated for a completion,
+    /// including visible output tokens and reasoning tokens.
+    ///
+    /// Reasoning models reject `max_tokens` and require this instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_completion_tokens: Option<u32>,
+
     /// Number between -2.0 and 2.0.
     /// Positive values penalize new tokens based on whether they appear in the text so far,
     /// increasing the model's likelihood to talk about new topics.
@@ -64,49 +79,310 @@ pub struct OpenAIChatParams {
     /// A unique identifier representing your end-user, which can help OpenAI to monitor and detect abuse.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user: Option<String>,
+
+    /// If specified, the system will make a best effort to sample deterministically,
+    /// such that repeated requests with the same seed and parameters should return the same result.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+
+    /// Modify the likelihood of specified tokens appearing in the completion,
+    /// maps token ids to a bias value from -100 to 100.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logit_bias: Option<HashMap<String, i32>>,
+
+    /// An object specifying the format that the model must output.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<OpenAIResponseFormat>,
+
+    /// A list of tools the model may call. Currently, only functions are supported as a tool.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<OpenAITool>>,
+
+    /// Controls which (if any) tool is called by the model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<OpenAIToolChoice>,
 }
 
 impl OpenAIChatParams {
+    /// Prompt tokens of the request.
     pub fn calc_tokens(&self) -> usize {
-        let context_size = get_context_size(&self.model);
         let mut tokens = 0;
         for message in &self.messages {
-            tokens += message.tokens();
+            tokens += message.tokens(&self.model);
         }
 
-        context_size.saturating_sub(tokens)
+        tokens
     }
 }
 
+impl From<ChatRequest> for OpenAIChatParams {
+    fn from(request: ChatRequest) -> Self {
+        let ChatRequest { messages, params } = request;
+
+        Self {
+            stream: true,
+            // Set by the api, not every endpoint accepts it
+            stream_options: None,
+            model: params.model,
+            messages: messages.into_iter().map(Into::into).collect(),
+            frequency_penalty: params.frequency_penalty,
+            presence_penalty: params.presence_penalty,
+            temperature: params.temperature,
+            top_p: params.top_p,
+            stop: params.stop,
+            max_tokens: params.max_tokens,
+            max_completion_tokens: params.max_completion_tokens,
+            seed: params.seed,
+            n: params.n,
+            logit_bias: params.logit_bias,
+            response_format: params.response_format.map(Into::into),
+            tools: params
+                .tools
+                .map(|tools| tools.into_iter().map(Into::into).collect()),
+            tool_choice: params.tool_choice.map(Into::into),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OpenAIResponseFormat {
+    Text,
+    JsonObject,
+    JsonSchema { json_schema: OpenAIJsonSchema },
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct OpenAIJsonSchema {
+    pub name: String,
+    pub schema: serde_json::Value,
+    pub strict: bool,
+}
+
+impl From<ChatResponseFormat> for OpenAIResponseFormat {
+    fn from(format: ChatResponseFormat) -> Self {
+        match format {
+            ChatResponseFormat::Text => OpenAIResponseFormat::Text,
+            ChatResponseFormat::JsonObject => OpenAIResponseFormat::JsonObject,
+            ChatResponseFormat::JsonSchema {
+                name,
+                schema,
+                strict,
+            } => OpenAIResponseFormat::JsonSchema {
+                json_schema: OpenAIJsonSchema {
+                    name,
+                    schema,
+                    strict,
+                },
+            },
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct OpenAIStreamOptions {
+    /// Stream an additional chunk with the token usage of the entire request before `data: [DONE]`.
+    pub include_usage: bool,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct OpenAIChatMessage {
     pub role: OpenAIChatRole,
-    pub content: String,
+    pub content: OpenAIChatContent,
+
+    /// The tool calls generated by the model.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<OpenAIToolCall>,
+
+    /// Tool call that this message is responding to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
 }
 
 impl OpenAIChatMessage {
-    pub fn tokens(&self) -> usize {
-        Self::calc_tokens(&self.role, &self.content)
+    /// Tokens of the text, images are estimated by [`ChatMessage::tokens`].
+    pub fn tokens(&self, model: &str) -> usize {
+        Self::calc_tokens(model, &self.role, &self.content.text())
     }
 
-    pub fn calc_tokens(role: &OpenAIChatRole, content: &str) -> usize {
-        let bpe = cl100k_base().unwrap();
+    pub fn calc_tokens(model: &str, role: &OpenAIChatRole, content: &str) -> usize {
+        let tokenizer = Tokenizer::for_model(model);
 
         let mut num_tokens = 0;
         num_tokens += 4; // every message follows <im_start>{role/name}\n{content}<im_end>\n
-        num_tokens += bpe.encode_with_special_tokens(&role.to_string()).len();
-        num_tokens += bpe.encode_with_special_tokens(content).len();
+        num_tokens += tokenizer.count(&role.to_string());
+        num_tokens += tokenizer.count(content);
 
         num_tokens
     }
 }
 
+impl From<ChatMessage> for OpenAIChatMessage {
+    fn from(message: ChatMessage) -> Self {
+        let content = if message.images.is_empty() {
+            OpenAIChatContent::Text(message.content)
+        } else {
+            let mut parts = vec![OpenAIChatContentPart::Text {
+                text: message.content,
+            }];
+            for image in message.images {
+                parts.push(OpenAIChatContentPart::ImageUrl {
+                    image_url: OpenAIImageUrl {
+                        url: image.data_url(),
+                        detail: None,
+                    },
+                });
+            }
+            OpenAIChatContent::Parts(parts)
+        };
+
+        Self {
+            role: message.role.into(),
+            content,
+            tool_calls: message.tool_calls.into_iter().map(Into::into).collect(),
+            tool_call_id: message.tool_call_id,
+        }
+    }
+}
+
+/// Either plain text or an array of text and image parts.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum OpenAIChatContent {
+    Text(String),
+    Parts(Vec<OpenAIChatContentPart>),
+}
+
+impl OpenAIChatContent {
+    pub fn text(&self) -> String {
+        match self {
+            OpenAIChatContent::Text(text) => text.clone(),
+            OpenAIChatContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    OpenAIChatContentPart::Text { text } => Some(text.as_str()),
+                    OpenAIChatContentPart::ImageUrl { .. } => None,
+                })
+                .collect::<Vec<&str>>()
+                .join("\n"),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OpenAIChatContentPart {
+    Text { text: String },
+    ImageUrl { image_url: OpenAIImageUrl },
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct OpenAIImageUrl {
+    /// Either a URL of the image or the base64 encoded image data.
+    pub url: String,
+
+    /// `low`, `high` or `auto`, defaults to `auto`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct OpenAITool {
+    /// The type of the tool. Currently, only `function` is supported.
+    pub r#type: String,
+    pub function: OpenAIFunction,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct OpenAIFunction {
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// The parameters the functions accepts, described as a JSON Schema object.
+    pub parameters: serde_json::Value,
+}
+
+impl From<ChatTool> for OpenAITool {
+    fn from(tool: ChatTool) -> Self {
+        Self {
+            r#type: "function".to_string(),
+            function: OpenAIFunction {
+                name: tool.name,
+                description: tool.description,
+                parameters: tool.parameters,
+            },
+        }
+    }
+}
+
+/// `none`, `auto`, `required` or `{"type": "function", "function": {"name": "my_function"}}`
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum OpenAIToolChoice {
+    Mode(String),
+    Function {
+        r#type: String,
+        function: OpenAIToolChoiceFunction,
+    },
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct OpenAIToolChoiceFunction {
+    pub name: String,
+}
+
+impl From<ChatToolChoice> for OpenAIToolChoice {
+    fn from(choice: ChatToolChoice) -> Self {
+        match choice {
+            ChatToolChoice::None => Self::Mode("none".to_string()),
+            ChatToolChoice::Auto => Self::Mode("auto".to_string()),
+            ChatToolChoice::Required => Self::Mode("required".to_string()),
+            ChatToolChoice::Function { name } => Self::Function {
+                r#type: "function".to_string(),
+                function: OpenAIToolChoiceFunction { name },
+            },
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct OpenAIToolCall {
+    pub id: String,
+    /// The type of the tool. Currently, only `function` is supported.
+    pub r#type: String,
+    pub function: OpenAIFunctionCall,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct OpenAIFunctionCall {
+    pub name: String,
+    /// The arguments to call the function with, as generated by the model in JSON format.
+    pub arguments: String,
+}
+
+impl From<ToolCall> for OpenAIToolCall {
+    fn from(tool_call: ToolCall) -> Self {
+        Self {
+            id: tool_call.id,
+            r#type: "function".to_string(),
+            function: OpenAIFunctionCall {
+                name: tool_call.name,
+                arguments: tool_call.arguments,
+            },
+        }
+    }
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 #[serde(rename_all = "lowercase")]
 pub enum OpenAIChatRole {
     System,
     User,
     Assistant,
+    Tool,
 }
 
 impl Display for OpenAIChatRole {
@@ -115,6 +391,7 @@ impl Display for OpenAIChatRole {
             OpenAIChatRole::System => write!(f, "system"),
             OpenAIChatRole::User => write!(f, "user"),
             OpenAIChatRole::Assistant => write!(f, "assistant"),
+            OpenAIChatRole::Tool => write!(f, "tool"),
         }
     }
 }
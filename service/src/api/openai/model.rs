@@ -0,0 +1,119 @@
+use crate::{
+    api::client::{self, Client},
+    result::Result,
+};
+
+use super::response::OpenAIErrorResponse;
+
+pub struct OpenAIModelApi {
+    client: Client,
+    host: String,
+}
+
+impl OpenAIModelApi {
+    pub fn new(client: Client, host: &str) -> Self {
+        Self {
+            client,
+            host: host.to_string(),
+        }
+    }
+
+    /// Ids of the chat models the provider serves.
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        let url = self.host.clone() + "/v1/models";
+
+        let res = self.client.get(&url).await?;
+
+        if !res.status().is_success() {
+            return Err(client::parse_error::<OpenAIErrorResponse>(res).await);
+        }
+
+        let list = res.json::<OpenAIModelList>().await?;
+
+        Ok(list
+            .data
+            .into_iter()
+            .map(|model| model.id)
+            .filter(|id| is_chat_model(id))
+            .collect())
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct OpenAIModelList {
+    pub data: Vec<OpenAIModel>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct OpenAIModel {
+    pub id: String,
+}
+
+/// `/v1/models` also lists embedding, audio, image and moderation models.
+fn is_chat_model(id: &str) -> bool {
+    const EXCLUDED: [&str; 8] = [
+        "embedding",
+        "whisper",
+        "tts",
+        "dall-e",
+        "moderation",
+        "davinci",
+        "babbage",
+        "transcribe",
+    ];
+
+    !EXCLUDED.iter().any(|excluded| id.contains(excluded))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        api::client::Client,
+        error::{ApiError, ApiErrorKind},
+        test::{mock_server, MockResponse},
+        Error,
+    };
+
+    use super::OpenAIModelApi;
+
+    #[tokio::test]
+    async fn test_list_models() {
+        let (url, handle) = mock_server(vec![MockResponse::new(
+            200,
+            vec![
+                r#"{"object":"list","data":[{"id":"gpt-4o","object":"model"},{"id":"text-embedding-3-small","object":"model"},{"id":"gpt-4o-mini","object":"model"}]}"#,
+            ],
+        )])
+        .await;
+
+        let api = OpenAIModelApi::new(Client::new(None), &url);
+        let models = api.list_models().await.unwrap();
+
+        assert_eq!(models, vec!["gpt-4o", "gpt-4o-mini"]);
+        assert!(handle.await.unwrap()[0].starts_with("GET /v1/models"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_key() {
+        let (url, _) = mock_server(vec![MockResponse::new(
+            401,
+            vec![
+                r#"{"error":{"message":"Incorrect API key provided","type":"invalid_request_error","code":"invalid_api_key"}}"#,
+            ],
+        )
+        .header("x-request-id", "req_123")])
+        .await;
+
+        let api = OpenAIModelApi::new(Client::new(None), &url);
+        let result = api.list_models().await;
+
+        assert!(matches!(
+            result,
+            Err(Error::Api(ApiError {
+                kind: ApiErrorKind::InvalidKey,
+                status: Some(401),
+                request_id: Some(request_id),
+            })) if request_id == "req_123"
+        ));
+    }
+}
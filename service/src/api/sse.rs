@@ -0,0 +1,325 @@
+use futures::{stream, stream::BoxStream, StreamExt};
+
+use crate::{error::ApiErrorKind, result::Result, Error};
+
+/// An event of a `text/event-stream` response.
+#[derive(Default, PartialEq, Eq, Clone, Debug)]
+pub struct SseEvent {
+    /// The `event` field, `None` for the default `message` type
+    pub event: Option<String>,
+    /// The `data` lines joined with `\n`
+    pub data: String,
+    /// The last `id` seen in the stream
+    pub id: Option<String>,
+}
+
+impl SseEvent {
+    /// Parse the data as JSON, a payload not matching `T` is an invalid response.
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_str(&self.data).map_err(|err| {
+            Error::from(ApiErrorKind::InvalidResponse(format!(
+                "{}: {}",
+                err, self.data
+            )))
+        })
+    }
+}
+
+/// Incremental decoder of server-sent events, following
+/// <https://html.spec.whatwg.org/multipage/server-sent-events.html#event-stream-interpretation>.
+///
+/// Chunks may split or merge lines and events anywhere, including inside a
+/// multi-byte character or between the `\r` and `\n` of a line ending.
+#[derive(Default)]
+pub struct SseDecoder {
+    line: Vec<u8>,
+    /// The last line ended with `\r`, a following `\n` belongs to the same line ending
+    after_cr: bool,
+    started: bool,
+    event: Option<String>,
+    data: Option<String>,
+    last_id: Option<String>,
+    /// A line of the current event is malformed, the rest of it is skipped
+    discarding: bool,
+}
+
+impl SseDecoder {
+    pub fn decode(&mut self, chunk: &[u8]) -> Vec<Result<SseEvent>> {
+        let mut events = vec![];
+        for &byte in chunk {
+            if std::mem::take(&mut self.after_cr) && byte == b'\n' {
+                continue;
+            }
+
+            match byte {
+                b'\r' | b'\n' => {
+                    self.after_cr = byte == b'\r';
+                    let line = std::mem::take(&mut self.line);
+                    events.extend(self.handle_line(line));
+                }
+                _ => self.line.push(byte),
+            }
+        }
+
+        events
+    }
+
+    /// Flush the decoder at the end of the stream,
+    /// an unterminated event means the stream was cut.
+    pub fn finish(&mut self) -> Vec<Result<SseEvent>> {
+        let mut events = vec![];
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            events.extend(self.handle_line(line));
+        }
+
+        if self.data.is_some() || self.event.is_some() {
+            let data = self.data.take().unwrap_or_default();
+            self.event = None;
+            events.push(Err(invalid_response(format!(
+                "stream ended inside an event: {}",
+                data
+            ))));
+        }
+
+        events
+    }
+
+    fn handle_line(&mut self, line: Vec<u8>) -> Option<Result<SseEvent>> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if self.discarding {
+            return None;
+        }
+
+        let mut line = match String::from_utf8(line) {
+            Ok(line) => line,
+            Err(err) => {
+                return Some(Err(self.discard(format!(
+                    "invalid utf-8: {}",
+                    String::from_utf8_lossy(err.as_bytes())
+                ))))
+            }
+        };
+        if !std::mem::replace(&mut self.started, true) && line.starts_with('\u{feff}') {
+            line.remove(0);
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, Some(value.strip_prefix(' ').unwrap_or(value))),
+            None => (line.as_str(), None),
+        };
+        let has_colon = value.is_some();
+        let value = value.unwrap_or_default();
+
+        match field {
+            // Comment, e.g. keep-alive
+            "" => {}
+            "event" => self.event = Some(value.to_string()),
+            "data" => match &mut self.data {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => self.data = Some(value.to_string()),
+            },
+            "id" => {
+                if !value.contains('\0') {
+                    self.last_id = Some(value.to_string());
+                }
+            }
+            // Reconnecting is up to the retry policy of the client
+            "retry" => {}
+            // Unknown fields are ignored, a line without a colon is not a field at all
+            _ if has_colon => {}
+            _ => return Some(Err(self.discard(format!("unexpected line: {}", line)))),
+        }
+
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<Result<SseEvent>> {
+        let event = self.event.take();
+        let data = self.data.take();
+        if std::mem::take(&mut self.discarding) {
+            return None;
+        }
+
+        // Events with an empty data buffer are not dispatched
+        let data = data.filter(|data| !data.is_empty())?;
+        Some(Ok(SseEvent {
+            event,
+            data,
+            id: self.last_id.clone(),
+        }))
+    }
+
+    fn discard(&mut self, message: String) -> Error {
+        self.discarding = true;
+        invalid_response(message)
+    }
+}
+
+fn invalid_response(message: String) -> Error {
+    Error::from(ApiErrorKind::InvalidResponse(message))
+}
+
+/// Decode the body of a streamed response into events.
+pub fn decode_response(res: reqwest::Response) -> BoxStream<'static, Result<SseEvent>> {
+    let mut decoder = SseDecoder::default();
+    res.bytes_stream()
+        .map(Some)
+        .chain(stream::once(async { None }))
+        .flat_map(move |chunk| {
+            let events = match chunk {
+                Some(Ok(chunk)) => decoder.decode(&chunk),
+                Some(Err(err)) => vec![Err(err.into())],
+                None => decoder.finish(),
+            };
+            stream::iter(events)
+        })
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SseDecoder, SseEvent};
+
+    fn event(event: Option<&str>, data: &str, id: Option<&str>) -> SseEvent {
+        SseEvent {
+            event: event.map(ToString::to_string),
+            data: data.to_string(),
+            id: id.map(ToString::to_string),
+        }
+    }
+
+    fn decode(chunks: &[&[u8]]) -> Vec<std::result::Result<SseEvent, String>> {
+        let mut decoder = SseDecoder::default();
+        let mut events = vec![];
+        for chunk in chunks {
+            events.extend(decoder.decode(chunk));
+        }
+        events.extend(decoder.finish());
+
+        events
+            .into_iter()
+            .map(|event| event.map_err(|err| err.to_string()))
+            .collect()
+    }
+
+    fn samples() -> Vec<(&'static [u8], Vec<SseEvent>)> {
+        vec![
+            (
+                "\u{feff}event: message_start\ndata: {\"a\":1}\n\n: keep-alive\n\ndata: héllo 世界 🦀\n\n"
+                    .as_bytes(),
+                vec![
+                    event(Some("message_start"), "{\"a\":1}", None),
+                    event(None, "héllo 世界 🦀", None),
+                ],
+            ),
+            (
+                b"id: 1\r\ndata: first\r\ndata:  second\r\n\r\nevent: ping\r\ndata:\r\ndata:\r\n\r\nevent: ignored\r\n\r\n",
+                vec![
+                    event(None, "first\n second", Some("1")),
+                    event(Some("ping"), "\n", Some("1")),
+                ],
+            ),
+            (
+                b"data: a\rdata:b\r\rid\rdata: c\r\r",
+                vec![event(None, "a\nb", None), event(None, "c", Some(""))],
+            ),
+            (
+                b"retry: 1000\ndata: [DONE]\n\n",
+                vec![event(None, "[DONE]", None)],
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_decode_whole() {
+        for (data, events) in samples() {
+            let expected = events.into_iter().map(Ok).collect::<Vec<_>>();
+            assert_eq!(decode(&[data]), expected);
+        }
+    }
+
+    #[test]
+    fn test_decode_split_chunks() {
+        for (data, events) in samples() {
+            let expected = events.into_iter().map(Ok).collect::<Vec<_>>();
+
+            // Byte by byte
+            let bytes = data.chunks(1).collect::<Vec<_>>();
+            assert_eq!(decode(&bytes), expected);
+
+            // Every pair of split points, empty chunks included
+            for i in 0..=data.len() {
+                for j in i..=data.len() {
+                    let chunks = [&data[..i], &data[i..j], &data[j..]];
+                    assert_eq!(decode(&chunks), expected, "split at {} and {}", i, j);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_merged_chunks() {
+        let samples = samples();
+        let data = samples
+            .iter()
+            .flat_map(|(data, _)| data.to_vec())
+            .collect::<Vec<u8>>();
+        // The last event id carries over to the following samples
+        let mut id = None;
+        let expected = samples
+            .into_iter()
+            .flat_map(|(_, events)| events)
+            .map(|mut event| {
+                if event.id.is_some() {
+                    id = event.id.clone();
+                }
+                event.id = id.clone();
+                Ok(event)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(decode(&[&data]), expected);
+        for size in [2, 3, 7, 16, 64] {
+            let chunks = data.chunks(size).collect::<Vec<_>>();
+            assert_eq!(decode(&chunks), expected, "chunks of {}", size);
+        }
+    }
+
+    #[test]
+    fn test_decode_empty_data() {
+        let events = decode(&[b"data:\n\nevent: ping\ndata\n\nid: 2\ndata:\n\ndata: a\n\n"]);
+        assert_eq!(events, vec![Ok(event(None, "a", Some("2")))]);
+    }
+
+    #[test]
+    fn test_decode_malformed() {
+        let events =
+            decode(&[b"data: a\nInternal Server Error\ndata: b\n\ndata: \xff\n\ndata: c\n\n"]);
+        assert_eq!(
+            events,
+            vec![
+                Err("invalid response: unexpected line: Internal Server Error".to_string()),
+                Err("invalid response: invalid utf-8: data: \u{fffd}".to_string()),
+                Ok(event(None, "c", None)),
+            ]
+        );
+
+        // Unknown fields don't fail the event
+        let events = decode(&[b"data: a\nfoo: 1\n\"error\": 2\ndata: b\n\n"]);
+        assert_eq!(events, vec![Ok(event(None, "a\nb", None))]);
+
+        let events = decode(&[b"data: {\"a\":", b"1}\n"]);
+        assert_eq!(
+            events,
+            vec![Err(
+                "invalid response: stream ended inside an event: {\"a\":1}".to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use tiktoken_rs::CoreBPE;
+
+/// Models counted with `o200k_base`, checked before the `cl100k_base` ones
+/// so that `gpt-4o` is not taken for `gpt-4`.
+const O200K_MODELS: [&str; 8] = [
+    "gpt-4o",
+    "chatgpt-4o",
+    "gpt-4.1",
+    "gpt-4.5",
+    "gpt-5",
+    "o1",
+    "o3",
+    "o4",
+];
+
+const CL100K_MODELS: [&str; 4] = ["gpt-4", "gpt-3.5", "gpt-35", "text-embedding"];
+
+/// Context windows tiktoken does not know, matched by prefix in order.
+const CONTEXT_SIZES: [(&str, usize); 12] = [
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4.5", 128_000),
+    ("gpt-5", 400_000),
+    ("gpt-4-turbo", 128_000),
+    ("chatgpt-4o", 128_000),
+    ("o1-mini", 128_000),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("o4", 200_000),
+    ("claude", 200_000),
+    ("gemini-1.5-pro", 2_097_152),
+    ("gemini", 1_048_576),
+];
+
+/// Tokens the model accepts for the prompt and the completion together,
+/// 4096 for unknown models.
+pub fn context_size(model: &str) -> usize {
+    let name = model.rsplit('/').next().unwrap_or(model);
+
+    CONTEXT_SIZES
+        .iter()
+        .find(|(prefix, _)| name.starts_with(prefix))
+        .map(|(_, size)| *size)
+        .unwrap_or_else(|| tiktoken_rs::model::get_context_size(name))
+}
+
+/// Counts the tokens of a model's text.
+#[derive(Clone)]
+pub enum Tokenizer {
+    Bpe(Arc<CoreBPE>),
+    /// For models whose tokenizer is not public, a token is about
+    /// 4 characters of latin text or a single character of other scripts.
+    Approximate,
+}
+
+impl Tokenizer {
+    /// The tokenizer of the model, built once and cached.
+    pub fn for_model(model: &str) -> Self {
+        static TOKENIZERS: OnceLock<Mutex<HashMap<String, Tokenizer>>> = OnceLock::new();
+
+        TOKENIZERS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap()
+            .entry(model.to_string())
+            .or_insert_with(|| Self::create(model))
+            .clone()
+    }
+
+    fn create(model: &str) -> Self {
+        static O200K: OnceLock<Arc<CoreBPE>> = OnceLock::new();
+        static CL100K: OnceLock<Arc<CoreBPE>> = OnceLock::new();
+
+        // Routers name models like `openai/gpt-4o`
+        let name = model.rsplit('/').next().unwrap_or(model);
+        if O200K_MODELS.iter().any(|prefix| name.starts_with(prefix)) {
+            let bpe = O200K.get_or_init(|| Arc::new(tiktoken_rs::o200k_base().unwrap()));
+            Tokenizer::Bpe(bpe.clone())
+        } else if CL100K_MODELS.iter().any(|prefix| name.starts_with(prefix)) {
+            let bpe = CL100K.get_or_init(|| Arc::new(tiktoken_rs::cl100k_base().unwrap()));
+            Tokenizer::Bpe(bpe.clone())
+        } else {
+            Tokenizer::Approximate
+        }
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        match self {
+            Tokenizer::Bpe(bpe) => bpe.encode_with_special_tokens(text).len(),
+            Tokenizer::Approximate => {
+                let ascii = text.chars().filter(char::is_ascii).count();
+                let other = text.chars().count() - ascii;
+
+                ascii.div_ceil(4) + other
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::Tokenizer;
+
+    fn bpe(model: &str) -> Option<Arc<tiktoken_rs::CoreBPE>> {
+        match Tokenizer::for_model(model) {
+            Tokenizer::Bpe(bpe) => Some(bpe),
+            Tokenizer::Approximate => None,
+        }
+    }
+
+    #[test]
+    fn test_for_model() {
+        let o200k = bpe("gpt-4o-mini").unwrap();
+        for model in ["gpt-4o", "o3-mini", "gpt-4.1-nano", "openai/o1"] {
+            assert!(Arc::ptr_eq(&bpe(model).unwrap(), &o200k), "{}", model);
+        }
+
+        let cl100k = bpe("gpt-3.5-turbo").unwrap();
+        for model in ["gpt-4", "gpt-4-turbo", "gpt-35-turbo"] {
+            assert!(Arc::ptr_eq(&bpe(model).unwrap(), &cl100k), "{}", model);
+        }
+        assert!(!Arc::ptr_eq(&o200k, &cl100k));
+
+        for model in [
+            "claude-3-5-haiku-latest",
+            "gemini-1.5-flash",
+            "llama3:latest",
+        ] {
+            assert!(bpe(model).is_none(), "{}", model);
+        }
+    }
+
+    #[test]
+    fn test_count() {
+        assert_eq!(Tokenizer::for_model("gpt-4o").count("hello world"), 2);
+        assert_eq!(Tokenizer::Approximate.count("hello world"), 3);
+        assert_eq!(Tokenizer::Approximate.count("你好, world"), 4);
+    }
+}
@@ -1,6 +1,14 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
 use crate::{
+    api::backend::ChatImage,
+    database::pagination::PaginatedRecords,
     models::{
-        chat_log::ChatLog, chat_model::ChatModel, plugin::InstalledPlugin,
+        chat_log::{ChatLog, ChatLogHit},
+        chat_model::{ChatModel, ChatModelCapabilities},
+        chat_summary::ChatSummary,
+        plugin::InstalledPlugin,
         prompt_source::PromptSource,
     },
     result::Result,
@@ -8,8 +16,8 @@ use crate::{
     services::{plugin_market::InstallMarketPluginPayload, setting::*},
     services::{plugin_market::MarketPlugin, prompt_market::*},
     services::{plugin_market::PluginMarketService, prompt::*},
-    Chat, ChatConfig, CursorQueryResult, DbConn, Id, Prompt, PromptIndex, Setting, StreamContent,
-    Theme,
+    Chat, ChatConfig, CursorQueryResult, DbConn, Id, Prompt, PromptIndex, PromptMessage,
+    PromptVariable, Setting, StreamContent, Theme,
 };
 use serde::Deserialize;
 use tokio::sync::mpsc::{self, Receiver};
@@ -37,19 +45,25 @@ impl LoginCommand {
 pub struct NewChatCommand {
     pub title: Option<String>,
     pub prompt_id: Option<Id>,
+    /// Values of the variables of the prompt
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
 }
 
 impl NewChatCommand {
     pub fn exec(self, conn: &DbConn) -> Result<Id> {
         let chat_service = ChatService::new(conn.clone());
 
-        let title = self.title.as_deref().unwrap_or("New Chat");
+        let title = self.title.as_deref().unwrap_or(DEFAULT_CHAT_TITLE);
         let chat_id = chat_service.create_chat(CreateChatPayload {
             title: title.to_string(),
             user_id: Id::local(),
             prompt_id: self.prompt_id,
             vendor: "openai".to_string(),
-            config: ChatConfig::default(),
+            config: ChatConfig {
+                variables: self.variables,
+                ..Default::default()
+            },
         })?;
 
         Ok(chat_id)
@@ -237,6 +251,31 @@ impl LoadChatLogByCursorCommand {
     }
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchChatLogsCommand {
+    pub keyword: String,
+    pub chat_id: Option<Id>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+impl SearchChatLogsCommand {
+    pub fn exec(self, conn: &DbConn) -> Result<PaginatedRecords<ChatLogHit>> {
+        let chat_service = ChatService::new(conn.clone());
+
+        let result = chat_service.search_chat_logs(SearchChatLogPayload {
+            keyword: self.keyword,
+            page: self.page,
+            per_page: self.per_page,
+            chat_id: self.chat_id,
+            user_id: Id::local(),
+        })?;
+
+        Ok(result)
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DeleteChatCommand {
@@ -261,15 +300,15 @@ pub struct UpdateChatLogCommand {
 }
 
 impl UpdateChatLogCommand {
-    pub fn exec(self, conn: &DbConn) -> Result<()> {
+    pub fn exec(self, conn: &DbConn) -> Result<Id> {
         let chat_service = ChatService::new(conn.clone());
 
-        chat_service.update_chat_log(UpdateChatLogPayload {
+        let id = chat_service.update_chat_log(UpdateChatLogPayload {
             id: self.id,
             content: self.content,
         })?;
 
-        Ok(())
+        Ok(id)
     }
 }
 
@@ -289,11 +328,62 @@ impl DeleteChatLogCommand {
     }
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetChatLogBranchesCommand {
+    pub id: Id,
+}
+
+impl GetChatLogBranchesCommand {
+    pub fn exec(self, conn: &DbConn) -> Result<Vec<ChatLog>> {
+        let chat_service = ChatService::new(conn.clone());
+
+        let branches = chat_service.get_chat_log_branches(self.id)?;
+
+        Ok(branches)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SwitchChatBranchCommand {
+    pub id: Id,
+}
+
+impl SwitchChatBranchCommand {
+    pub fn exec(self, conn: &DbConn) -> Result<Id> {
+        let chat_service = ChatService::new(conn.clone());
+
+        let active_log_id = chat_service.switch_chat_branch(self.id)?;
+
+        Ok(active_log_id)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PickChatReplyCommand {
+    pub id: Id,
+}
+
+impl PickChatReplyCommand {
+    pub fn exec(self, conn: &DbConn) -> Result<Id> {
+        let chat_service = ChatService::new(conn.clone());
+
+        let active_log_id = chat_service.pick_chat_reply(self.id)?;
+
+        Ok(active_log_id)
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SendMessageCommand {
     pub chat_id: Id,
     pub message: String,
+    /// Paths of local image files to attach
+    #[serde(default)]
+    pub images: Vec<PathBuf>,
 }
 
 impl SendMessageCommand {
@@ -303,6 +393,12 @@ impl SendMessageCommand {
     ) -> Result<(Receiver<StreamContent>, Sender<()>, Id, Id)> {
         let chat_service = ChatService::new(conn.clone());
 
+        let images = self
+            .images
+            .iter()
+            .map(ChatImage::open)
+            .collect::<Result<Vec<ChatImage>>>()?;
+
         let (sender, receiver) = mpsc::channel::<StreamContent>(20);
         let (stop_sender, stop_receiver) = oneshot::channel::<()>();
         let (message_id, reply_id, _) = chat_service
@@ -310,6 +406,7 @@ impl SendMessageCommand {
                 SendMessagePayload {
                     chat_id: self.chat_id,
                     message: self.message,
+                    images,
                 },
                 sender,
                 stop_receiver,
@@ -347,6 +444,87 @@ impl ResendMessageCommand {
     }
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerateChatTitleCommand {
+    pub chat_id: Id,
+}
+
+impl GenerateChatTitleCommand {
+    pub async fn exec(self, conn: &DbConn) -> Result<Option<String>> {
+        let chat_service = ChatService::new(conn.clone());
+
+        let title = chat_service.generate_title(self.chat_id).await?;
+
+        Ok(title)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArenaMessageCommand {
+    pub chat_id: Id,
+    pub message: String,
+    /// Paths of local image files to attach
+    #[serde(default)]
+    pub images: Vec<PathBuf>,
+    /// Ids of the chat models
+    pub models: Vec<Id>,
+}
+
+/// The reply of one model of the arena, streamed and stopped by its id.
+pub struct ArenaStream {
+    pub model: String,
+    pub reply_id: Id,
+    pub receiver: Receiver<StreamContent>,
+    pub stop_sender: Sender<()>,
+}
+
+impl ArenaMessageCommand {
+    pub async fn exec(self, conn: &DbConn) -> Result<(Id, Vec<ArenaStream>)> {
+        let chat_service = ChatService::new(conn.clone());
+
+        let images = self
+            .images
+            .iter()
+            .map(ChatImage::open)
+            .collect::<Result<Vec<ChatImage>>>()?;
+
+        let mut channels = vec![];
+        let mut ends = vec![];
+        for _ in &self.models {
+            let (sender, receiver) = mpsc::channel::<StreamContent>(20);
+            let (stop_sender, stop_receiver) = oneshot::channel::<()>();
+            channels.push((sender, stop_receiver));
+            ends.push((receiver, stop_sender));
+        }
+
+        let (message_id, replies) = chat_service
+            .arena_message(
+                ArenaMessagePayload {
+                    chat_id: self.chat_id,
+                    message: self.message,
+                    images,
+                    models: self.models,
+                },
+                channels,
+            )
+            .await?;
+        let streams = replies
+            .into_iter()
+            .zip(ends)
+            .map(|(reply, (receiver, stop_sender))| ArenaStream {
+                model: reply.model,
+                reply_id: reply.reply_id,
+                receiver,
+                stop_sender,
+            })
+            .collect();
+
+        Ok((message_id, streams))
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StopReplyCommand {
@@ -372,30 +550,93 @@ impl GetChatModelsCommand {
 pub struct CreateChatModelCommand {
     pub name: String,
     pub price: f32,
+    pub unit: Option<String>,
+    pub vendor: Option<String>,
+    /// Detected from the name when not given
+    pub capabilities: Option<ChatModelCapabilities>,
 }
 
 impl CreateChatModelCommand {
     pub fn exec(self, conn: &DbConn) -> Result<Id> {
         let chat_service = ChatService::new(conn.clone());
 
+        let capabilities = self
+            .capabilities
+            .unwrap_or_else(|| ChatModelCapabilities::detect(&self.name));
         let id = chat_service.create_chat_model(CreateChatModelPayload {
             name: self.name,
             description: "".to_string(),
             price: self.price,
-            unit: "USD".to_string(),
-            vendor: "custom".to_string(),
+            unit: self.unit.unwrap_or_else(|| "USD".to_string()),
+            vendor: self.vendor.unwrap_or_else(|| "custom".to_string()),
+            capabilities,
+        })?;
+
+        Ok(id)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddToolMessageCommand {
+    pub chat_id: Id,
+    pub tool_call_id: String,
+    pub content: String,
+}
+
+impl AddToolMessageCommand {
+    pub fn exec(self, conn: &DbConn) -> Result<Id> {
+        let chat_service = ChatService::new(conn.clone());
+
+        let id = chat_service.add_tool_message(AddToolMessagePayload {
+            chat_id: self.chat_id,
+            tool_call_id: self.tool_call_id,
+            content: self.content,
         })?;
 
         Ok(id)
     }
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetChatSummariesCommand {
+    pub chat_id: Id,
+}
+
+impl GetChatSummariesCommand {
+    pub fn exec(self, conn: &DbConn) -> Result<Vec<ChatSummary>> {
+        let chat_service = ChatService::new(conn.clone());
+
+        chat_service.get_chat_summaries(self.chat_id)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncChatModelsCommand {
+    /// Defaults to `openai`
+    pub vendor: Option<String>,
+}
+
+impl SyncChatModelsCommand {
+    pub async fn exec(self, conn: &DbConn) -> Result<Vec<ChatModel>> {
+        let chat_service = ChatService::new(conn.clone());
+
+        let vendor = self.vendor.unwrap_or_else(|| "openai".to_string());
+        let records = chat_service.sync_chat_models(Id::local(), &vendor).await?;
+
+        Ok(records)
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UpdateChatModelCommand {
     pub id: Id,
     pub name: Option<String>,
     pub price: Option<f32>,
+    pub capabilities: Option<ChatModelCapabilities>,
 }
 
 impl UpdateChatModelCommand {
@@ -409,6 +650,7 @@ impl UpdateChatModelCommand {
             price: self.price,
             unit: None,
             vendor: None,
+            capabilities: self.capabilities,
         })?;
 
         Ok(())
@@ -430,6 +672,34 @@ impl DeleteChatModelCommand {
     }
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoverLocalModelsCommand;
+
+impl DiscoverLocalModelsCommand {
+    pub async fn exec(self, conn: &DbConn) -> Result<Vec<ChatModel>> {
+        let chat_service = ChatService::new(conn.clone());
+
+        let records = chat_service.discover_local_models(Id::local()).await?;
+
+        Ok(records)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncAzureModelsCommand;
+
+impl SyncAzureModelsCommand {
+    pub fn exec(self, conn: &DbConn) -> Result<Vec<ChatModel>> {
+        let chat_service = ChatService::new(conn.clone());
+
+        let records = chat_service.sync_azure_models(Id::local())?;
+
+        Ok(records)
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AllPromptsCommand;
@@ -464,7 +734,10 @@ impl LoadPromptCommand {
 #[serde(rename_all = "camelCase")]
 pub struct CreatePromptCommand {
     pub name: String,
-    pub content: String,
+    #[serde(default)]
+    pub messages: Vec<PromptMessage>,
+    #[serde(default)]
+    pub variables: Vec<PromptVariable>,
 }
 
 impl CreatePromptCommand {
@@ -473,7 +746,8 @@ impl CreatePromptCommand {
 
         let id = prompt_service.create_prompt(CreatePromptPayload {
             name: self.name,
-            content: self.content,
+            messages: self.messages,
+            variables: self.variables,
             user_id: Id::local(),
         })?;
 
@@ -497,6 +771,22 @@ impl UpdatePromptCommand {
     }
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetPromptVariablesCommand {
+    pub id: Id,
+}
+
+impl GetPromptVariablesCommand {
+    pub fn exec(self, conn: &DbConn) -> Result<Vec<PromptVariable>> {
+        let prompt_service = PromptService::new(conn.clone());
+
+        let result = prompt_service.get_prompt_variables(self.id)?;
+
+        Ok(result)
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DeletePromptCommand {
@@ -557,10 +847,8 @@ impl InstallMarketPromptCommand {
         let prompt_market_service = PromptMarketService::new(conn.clone());
 
         let id = prompt_market_service.install_market_prompt(InstallMarketPromptPayload {
-            prompt: MarketPrompt {
-                name: self.name,
-                content: self.content,
-            },
+            prompt: MarketPrompt::new(self.name, self.content),
+            variables: HashMap::new(),
             user_id: Id::local(),
         })?;
 
@@ -573,6 +861,9 @@ impl InstallMarketPromptCommand {
 pub struct InstallMarketPromptAndCreateChatCommand {
     pub name: String,
     pub content: String,
+    /// Values of the variables of the prompt
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
 }
 
 impl InstallMarketPromptAndCreateChatCommand {
@@ -581,10 +872,8 @@ impl InstallMarketPromptAndCreateChatCommand {
 
         let (prompt_id, chat_id) = prompt_market_service.install_market_prompt_and_create_chat(
             InstallMarketPromptPayload {
-                prompt: MarketPrompt {
-                    name: self.name,
-                    content: self.content,
-                },
+                prompt: MarketPrompt::new(self.name, self.content),
+                variables: self.variables,
                 user_id: Id::local(),
             },
         )?;
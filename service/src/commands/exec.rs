@@ -68,6 +68,10 @@ impl CommandExecutor {
                 .exec(conn)
                 .into_result(),
 
+            "search_chat_logs" => from_value::<SearchChatLogsCommand>(payload)?
+                .exec(conn)
+                .into_result(),
+
             "update_chat" => from_value::<UpdateChatCommand>(payload)?
                 .exec(conn)
                 .into_result(),
@@ -104,8 +108,21 @@ impl CommandExecutor {
                 .exec(conn)
                 .into_result(),
 
+            "get_chat_log_branches" => from_value::<GetChatLogBranchesCommand>(payload)?
+                .exec(conn)
+                .into_result(),
+
+            "switch_chat_branch" => from_value::<SwitchChatBranchCommand>(payload)?
+                .exec(conn)
+                .into_result(),
+
+            "pick_chat_reply" => from_value::<PickChatReplyCommand>(payload)?
+                .exec(conn)
+                .into_result(),
+
             "send_message" => {
                 let command = from_value::<SendMessageCommand>(payload)?;
+                let chat_id = command.chat_id;
                 let (mut receiver, stop_sender, message_id, reply_id) = command.exec(conn).await?;
 
                 self.stop_reply_sender_map
@@ -113,6 +130,7 @@ impl CommandExecutor {
                     .await
                     .insert(message_id, stop_sender);
 
+                let conn = conn.clone();
                 let stop_reply_sender_map = self.stop_reply_sender_map.clone();
                 tokio::spawn(async move {
                     let event_id = message_id.to_string();
@@ -125,6 +143,24 @@ impl CommandExecutor {
                     }
 
                     stop_reply_sender_map.lock().await.remove(&message_id);
+
+                    // Title the chat after its first exchange
+                    let title = GenerateChatTitleCommand { chat_id }.exec(&conn).await;
+                    match title {
+                        Ok(Some(title)) => {
+                            let result = send(CommandEvent {
+                                name: "chat-updated".to_string(),
+                                payload: json!({ "id": chat_id, "title": title }),
+                            });
+                            if let Err(err) = result.await {
+                                log::warn!("send title of chat {} failed: {}", chat_id, err)
+                            }
+                        }
+                        Ok(None) => {}
+                        Err(err) => {
+                            log::warn!("generate title of chat {} failed: {}", chat_id, err)
+                        }
+                    }
                 });
 
                 Ok(Box::new((message_id, reply_id)))
@@ -156,6 +192,50 @@ impl CommandExecutor {
                 Ok(Box::new((message_id, reply_id)))
             }
 
+            "arena_message" => {
+                let command = from_value::<ArenaMessageCommand>(payload)?;
+                let (message_id, streams) = command.exec(conn).await?;
+
+                // Each reply streams under its own id, and is stopped by it
+                let send = Arc::new(send);
+                let mut replies = vec![];
+                for stream in streams {
+                    let ArenaStream {
+                        model,
+                        reply_id,
+                        mut receiver,
+                        stop_sender,
+                    } = stream;
+
+                    self.stop_reply_sender_map
+                        .lock()
+                        .await
+                        .insert(reply_id, stop_sender);
+
+                    let send = send.clone();
+                    let stop_reply_sender_map = self.stop_reply_sender_map.clone();
+                    tokio::spawn(async move {
+                        let event_id = reply_id.to_string();
+                        while let Some(content) = receiver.recv().await {
+                            let result = send(CommandEvent {
+                                name: event_id.clone(),
+                                payload: to_value(&content).unwrap(),
+                            });
+                            result.await.unwrap();
+                        }
+
+                        stop_reply_sender_map.lock().await.remove(&reply_id);
+                    });
+
+                    replies.push(json!({ "model": model, "replyId": reply_id }));
+                }
+
+                Ok(Box::new(json!({
+                    "messageId": message_id,
+                    "replies": replies,
+                })))
+            }
+
             "stop_reply" => {
                 let command = from_value::<StopReplyCommand>(payload)?;
                 let message_id = command.message_id;
@@ -183,6 +263,28 @@ impl CommandExecutor {
                 .exec(conn)
                 .into_result(),
 
+            "add_tool_message" => from_value::<AddToolMessageCommand>(payload)?
+                .exec(conn)
+                .into_result(),
+
+            "get_chat_summaries" => from_value::<GetChatSummariesCommand>(payload)?
+                .exec(conn)
+                .into_result(),
+
+            "sync_chat_models" => from_value::<SyncChatModelsCommand>(payload)?
+                .exec(conn)
+                .await
+                .into_result(),
+
+            "discover_local_models" => from_value::<DiscoverLocalModelsCommand>(payload)?
+                .exec(conn)
+                .await
+                .into_result(),
+
+            "sync_azure_models" => from_value::<SyncAzureModelsCommand>(payload)?
+                .exec(conn)
+                .into_result(),
+
             "all_prompts" => from_value::<AllPromptsCommand>(payload)?
                 .exec(conn)
                 .into_result(),
@@ -191,6 +293,10 @@ impl CommandExecutor {
                 .exec(conn)
                 .into_result(),
 
+            "get_prompt_variables" => from_value::<GetPromptVariablesCommand>(payload)?
+                .exec(conn)
+                .into_result(),
+
             "create_prompt" => {
                 let command = from_value::<CreatePromptCommand>(payload)?;
                 let result = command.exec(conn)?;
@@ -353,7 +459,36 @@ impl CommandExecutor {
                     .unwrap();
                 }
 
-                command.exec(conn).into_result()
+                let discover_local_models = command.payload.local_servers.is_some();
+                let sync_azure_models = command.payload.azure.is_some();
+                let result = command.exec(conn);
+
+                if result.is_ok() && sync_azure_models && SyncAzureModelsCommand.exec(conn).is_ok() {
+                    send(CommandEvent {
+                        name: "chat-models-changed".to_string(),
+                        payload: json!(null),
+                    })
+                    .await
+                    .unwrap();
+                }
+
+                let result = result.into_result();
+
+                if discover_local_models {
+                    let conn = conn.clone();
+                    tokio::spawn(async move {
+                        if DiscoverLocalModelsCommand.exec(&conn).await.is_ok() {
+                            send(CommandEvent {
+                                name: "chat-models-changed".to_string(),
+                                payload: json!(null),
+                            })
+                            .await
+                            .unwrap();
+                        }
+                    });
+                }
+
+                result
             }
 
             "get_locale" => from_value::<GetLocaleCommand>(payload)?
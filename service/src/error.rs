@@ -29,6 +29,10 @@ pub enum Error {
     #[error("plugin error: {0}")]
     Plugin(String),
 
+    /// Invalid input of a command, e.g. a missing field
+    #[error("invalid input: {0}")]
+    Validation(String),
+
     #[error("error: {0}")]
     Unknown(String),
 }
@@ -68,20 +72,101 @@ impl serde::ser::Serialize for Error {
             }
             Error::Wasmtime(err) => err.to_string().serialize(serializer),
             Error::Plugin(err) => err.serialize(serializer),
+            Error::Validation(err) => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("type", "validation")?;
+                map.serialize_entry("message", err)?;
+                map.end()
+            }
             Error::Unknown(err) => err.serialize(serializer),
         }
     }
 }
 
+/// An error of the provider api, with the response it came from if any.
+#[derive(thiserror::Error, serde::Serialize, PartialEq, Eq, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+#[error("{kind}")]
+pub struct ApiError {
+    #[serde(flatten)]
+    pub kind: ApiErrorKind,
+    /// Http status of the error response, `None` for errors sent in the stream
+    pub status: Option<u16>,
+    /// Id the provider assigned to the request, for support requests
+    pub request_id: Option<String>,
+}
+
+impl ApiError {
+    pub fn new(kind: ApiErrorKind) -> Self {
+        Self {
+            kind,
+            status: None,
+            request_id: None,
+        }
+    }
+
+    /// Errors the provider did not describe are classified by the status.
+    pub fn with_status(mut self, status: u16) -> Self {
+        if let ApiErrorKind::Unknown(message) = self.kind {
+            self.kind = ApiErrorKind::from_status(status, message);
+        }
+        self.status = Some(status);
+        self
+    }
+
+    pub fn with_request_id(mut self, request_id: Option<String>) -> Self {
+        self.request_id = request_id;
+        self
+    }
+}
+
 #[derive(thiserror::Error, serde::Serialize, PartialEq, Eq, Clone, Debug)]
 #[serde(tag = "type", content = "message")]
-pub enum ApiError {
+pub enum ApiErrorKind {
     #[error("invalid api key")]
     InvalidKey,
+    /// Too many requests or tokens in a time window, worth retrying later
+    #[error("rate limit exceeded: {0}")]
+    RateLimit(String),
+    /// The account ran out of credit, retrying does not help
+    #[error("insufficient quota: {0}")]
+    InsufficientQuota(String),
+    /// The prompt and the completion do not fit the model, the context needs trimming
+    #[error("context length exceeded: {0}")]
+    ContextLengthExceeded(String),
+    #[error("model not found: {0}")]
+    ModelNotFound(String),
+    #[error("content policy violation: {0}")]
+    ContentPolicy(String),
+    /// The provider is temporarily unable to serve, worth retrying or switching model
+    #[error("server overloaded: {0}")]
+    Overloaded(String),
+    #[error("unknown vendor: {0}")]
+    UnknownVendor(String),
+    #[error("invalid response: {0}")]
+    InvalidResponse(String),
     #[error("unknown error: {0}")]
     Unknown(String),
 }
 
+impl ApiErrorKind {
+    pub fn from_status(status: u16, message: String) -> Self {
+        match status {
+            401 | 403 => ApiErrorKind::InvalidKey,
+            404 => ApiErrorKind::ModelNotFound(message),
+            429 => ApiErrorKind::RateLimit(message),
+            502 | 503 | 529 => ApiErrorKind::Overloaded(message),
+            _ => ApiErrorKind::Unknown(message),
+        }
+    }
+}
+
+impl From<ApiErrorKind> for Error {
+    fn from(kind: ApiErrorKind) -> Self {
+        Error::Api(ApiError::new(kind))
+    }
+}
+
 #[derive(thiserror::Error, serde::Serialize, serde::Deserialize, PartialEq, Eq, Clone, Debug)]
 #[serde(tag = "type", content = "message")]
 pub enum NetworkError {
@@ -105,11 +190,72 @@ impl From<reqwest::Error> for Error {
     }
 }
 
-impl From<crate::api::openai::response::OpenAIErrorResponse> for Error {
+impl From<crate::api::openai::response::OpenAIErrorResponse> for ApiErrorKind {
     fn from(err: crate::api::openai::response::OpenAIErrorResponse) -> Self {
-        match err.error.code.as_deref() {
-            Some("invalid_api_key") => Error::Api(ApiError::InvalidKey),
-            _ => Error::Api(ApiError::Unknown(err.error.message)),
+        let err = err.error;
+        // Azure puts the kind in `code`, OpenAI in `code` or `type`
+        let code = err.code.as_deref().unwrap_or(err.r#type.as_str());
+        match code {
+            "invalid_api_key" | "invalid_authentication" => ApiErrorKind::InvalidKey,
+            "insufficient_quota" => ApiErrorKind::InsufficientQuota(err.message),
+            "rate_limit_exceeded" => ApiErrorKind::RateLimit(err.message),
+            "context_length_exceeded" => ApiErrorKind::ContextLengthExceeded(err.message),
+            "model_not_found" | "DeploymentNotFound" => ApiErrorKind::ModelNotFound(err.message),
+            "content_policy_violation" | "content_filter" => {
+                ApiErrorKind::ContentPolicy(err.message)
+            }
+            "server_overloaded" | "engine_overloaded" => ApiErrorKind::Overloaded(err.message),
+            _ => match err.r#type.as_str() {
+                "insufficient_quota" => ApiErrorKind::InsufficientQuota(err.message),
+                "server_error" => ApiErrorKind::Overloaded(err.message),
+                _ => ApiErrorKind::Unknown(err.message),
+            },
+        }
+    }
+}
+
+impl From<crate::api::anthropic::response::AnthropicErrorResponse> for ApiErrorKind {
+    fn from(err: crate::api::anthropic::response::AnthropicErrorResponse) -> Self {
+        let message = err.error.message;
+        match err.error.r#type.as_str() {
+            "authentication_error" | "permission_error" => ApiErrorKind::InvalidKey,
+            "rate_limit_error" => ApiErrorKind::RateLimit(message),
+            "not_found_error" => ApiErrorKind::ModelNotFound(message),
+            "overloaded_error" | "api_error" => ApiErrorKind::Overloaded(message),
+            // Anthropic reports these as bad requests, only the message tells them apart
+            "invalid_request_error" if message.contains("prompt is too long") => {
+                ApiErrorKind::ContextLengthExceeded(message)
+            }
+            "invalid_request_error" if message.contains("credit balance") => {
+                ApiErrorKind::InsufficientQuota(message)
+            }
+            _ => ApiErrorKind::Unknown(message),
+        }
+    }
+}
+
+impl From<crate::api::gemini::response::GeminiErrorResponse> for ApiErrorKind {
+    fn from(err: crate::api::gemini::response::GeminiErrorResponse) -> Self {
+        let message = err.error.message;
+        // Gemini reports an invalid key as a bad argument
+        if err.error.status == "UNAUTHENTICATED"
+            || err.error.status == "PERMISSION_DENIED"
+            || message.starts_with("API key not valid")
+        {
+            return ApiErrorKind::InvalidKey;
+        }
+
+        match err.error.status.as_str() {
+            "RESOURCE_EXHAUSTED" if message.contains("quota") && !message.contains("rate") => {
+                ApiErrorKind::InsufficientQuota(message)
+            }
+            "RESOURCE_EXHAUSTED" => ApiErrorKind::RateLimit(message),
+            "NOT_FOUND" => ApiErrorKind::ModelNotFound(message),
+            "UNAVAILABLE" | "INTERNAL" => ApiErrorKind::Overloaded(message),
+            "INVALID_ARGUMENT" if message.contains("token count") => {
+                ApiErrorKind::ContextLengthExceeded(message)
+            }
+            _ => ApiErrorKind::Unknown(message),
         }
     }
 }
@@ -124,6 +270,10 @@ pub enum StreamError {
     #[error(transparent)]
     Network(NetworkError),
 
+    /// The reply does not match the schema of the response format
+    #[error("reply does not match the schema: {0}")]
+    SchemaViolation(String),
+
     #[error("unknown error: {0}")]
     Unknown(String),
 }
@@ -138,3 +288,14 @@ impl From<Error> for StreamError {
         }
     }
 }
+
+impl From<StreamError> for Error {
+    fn from(err: StreamError) -> Self {
+        match err {
+            StreamError::Api(err) => Error::Api(err),
+            StreamError::Network(err) => Error::Network(err),
+            StreamError::SchemaViolation(_) => Error::Validation(err.to_string()),
+            StreamError::Unknown(err) => Error::Unknown(err),
+        }
+    }
+}
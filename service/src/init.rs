@@ -1,6 +1,5 @@
-use crate::NewChat;
 use crate::error::Error;
-use crate::models::chat_model::NewChatModel;
+use crate::models::chat_model::{ChatModelCapabilities, NewChatModel};
 use crate::models::prompt_source::NewPromptSource;
 use crate::models::setting::{NewSetting, Theme};
 use crate::repositories::chat::ChatRepo;
@@ -8,6 +7,7 @@ use crate::repositories::chat_model::ChatModelRepo;
 use crate::repositories::prompt_source::PromptSourceRepo;
 use crate::repositories::setting::SettingRepo;
 use crate::result::Result;
+use crate::NewChat;
 use crate::{database::DbConn, models::user::NewUser, repositories::user::UserRepo, types::Id};
 use diesel::sqlite::Sqlite;
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
@@ -68,6 +68,9 @@ pub fn init(db_url: &str) -> Result<DbConn> {
             price: 0.002,
             unit: "USD".to_string(),
             vendor: "openai".to_string(),
+            server_id: None,
+            capabilities: ChatModelCapabilities::default().into(),
+            served_model: None,
         },
         NewChatModel {
             id: Id::from("a5224f79-6d95-439e-a312-22cce02fd61f"),
@@ -76,6 +79,53 @@ pub fn init(db_url: &str) -> Result<DbConn> {
             price: 0.06,
             unit: "USD".to_string(),
             vendor: "openai".to_string(),
+            server_id: None,
+            capabilities: ChatModelCapabilities::default().into(),
+            served_model: None,
+        },
+        NewChatModel {
+            id: Id::from("4f0c5ae4-8a3b-4b6e-9a0c-6f1b2d6f7c31"),
+            name: "claude-3-5-sonnet-latest".to_string(),
+            description: "".to_string(),
+            price: 0.015,
+            unit: "USD".to_string(),
+            vendor: "anthropic".to_string(),
+            server_id: None,
+            capabilities: ChatModelCapabilities::default().into(),
+            served_model: None,
+        },
+        NewChatModel {
+            id: Id::from("9b2e6d1a-3c47-4f58-8e2d-0a5c7b9e1f64"),
+            name: "claude-3-5-haiku-latest".to_string(),
+            description: "".to_string(),
+            price: 0.004,
+            unit: "USD".to_string(),
+            vendor: "anthropic".to_string(),
+            server_id: None,
+            capabilities: ChatModelCapabilities::default().into(),
+            served_model: None,
+        },
+        NewChatModel {
+            id: Id::from("2d7f4c19-8e6a-4b3d-9f25-c1a0e7b84d56"),
+            name: "gemini-1.5-pro".to_string(),
+            description: "".to_string(),
+            price: 0.0105,
+            unit: "USD".to_string(),
+            vendor: "gemini".to_string(),
+            server_id: None,
+            capabilities: ChatModelCapabilities::default().into(),
+            served_model: None,
+        },
+        NewChatModel {
+            id: Id::from("7a3e9b52-1f64-4c8d-a0b7-5e2d6c9f1a83"),
+            name: "gemini-1.5-flash".to_string(),
+            description: "".to_string(),
+            price: 0.00105,
+            unit: "USD".to_string(),
+            vendor: "gemini".to_string(),
+            server_id: None,
+            capabilities: ChatModelCapabilities::default().into(),
+            served_model: None,
         },
     ];
     let chat_model_repo = ChatModelRepo::new(conn.clone());
@@ -0,0 +1,33 @@
+use chrono::NaiveDateTime;
+use diesel::*;
+use serde::Serialize;
+
+use crate::schema::attachments;
+use crate::types::Id;
+
+/// An image attached to a chat log.
+#[derive(Queryable, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub id: Id,
+    pub chat_log_id: Id,
+    pub name: String,
+    pub mime_type: String,
+    #[serde(skip)]
+    pub data: Vec<u8>,
+    pub width: i32,
+    pub height: i32,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = attachments)]
+pub struct NewAttachment {
+    pub id: Id,
+    pub chat_log_id: Id,
+    pub name: String,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+    pub width: i32,
+    pub height: i32,
+}
@@ -1,11 +1,16 @@
+use std::collections::HashMap;
+
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::error::StreamError;
+use crate::result::Result;
 use crate::schema::chats;
 use crate::types::Id;
 use crate::types::JsonWrapper;
+use crate::Error;
 
 #[derive(Insertable, Debug)]
 #[diesel(table_name = chats)]
@@ -55,6 +60,8 @@ pub struct Chat {
     pub stick: bool,
     pub archive: bool,
     pub archived_at: Option<NaiveDateTime>,
+    /// The last log of the branch being followed
+    pub active_log_id: Option<Id>,
 }
 
 #[derive(AsChangeset, Deserialize, Default, Debug)]
@@ -75,45 +82,387 @@ pub struct PatchChat {
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct ChatConfig {
+    /// Max number of logs sent as history, 0 means no limit but the context size.
     pub backtrack: usize,
+    /// Condense the logs left out of the context into a summary sent in their place.
+    #[serde(default)]
+    pub summarize: bool,
+    /// Values of the variables of the prompt.
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
     pub params: ChatParams,
 }
 
+// Spelled out to keep the history default visible
+#[allow(clippy::derivable_impls)]
 impl Default for ChatConfig {
     fn default() -> Self {
         Self {
-            backtrack: 2,
+            // The history is bounded by the context size rather than a number of logs
+            backtrack: 0,
+            summarize: false,
+            variables: HashMap::new(),
             params: ChatParams::default(),
         }
     }
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct ChatParams {
     pub model: String,
 
+    /// The `chat_models` row of `model`, models of several vendors or servers may share a name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_id: Option<Id>,
+
+    /// Between 0 and 2.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub temperature: Option<f64>,
 
+    /// Between 0 and 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+
+    /// Up to 4 sequences ending the reply.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stop: Option<Vec<String>>,
 
+    /// Tokens of the reply, the rest of the context is left to the history.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+
+    /// Tokens of the reply including the reasoning, used by reasoning models instead of `max_tokens`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_completion_tokens: Option<u32>,
+
+    /// Between -2 and 2.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub presence_penalty: Option<f64>,
 
+    /// Between -2 and 2.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub frequency_penalty: Option<f64>,
+
+    /// Sample deterministically, as far as the provider can.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+
+    /// Replies to generate at once, only OpenAI compatible backends return more than one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+
+    /// Bias between -100 and 100 added to the logits of token ids.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logit_bias: Option<HashMap<String, i32>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ChatResponseFormat>,
+
+    /// Tools the model may call.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<ChatTool>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ChatToolChoice>,
 }
 
 impl Default for ChatParams {
     fn default() -> Self {
         Self {
             model: "gpt-3.5-turbo".to_string(),
+            model_id: None,
             temperature: None,
+            top_p: None,
             stop: None,
+            max_tokens: None,
+            max_completion_tokens: None,
             presence_penalty: None,
             frequency_penalty: None,
+            seed: None,
+            n: None,
+            logit_bias: None,
+            response_format: None,
+            tools: None,
+            tool_choice: None,
+        }
+    }
+}
+
+impl ChatParams {
+    /// Check the ranges of the parameters before sending them to a backend of `vendor`.
+    pub fn validate(&self, vendor: &str) -> Result<()> {
+        fn check_range(name: &str, value: Option<f64>, min: f64, max: f64) -> Result<()> {
+            match value {
+                Some(value) if !(min..=max).contains(&value) => Err(Error::Validation(format!(
+                    "{} must be between {} and {}, got {}",
+                    name, min, max, value
+                ))),
+                _ => Ok(()),
+            }
+        }
+
+        // Anthropic takes a narrower temperature range than OpenAI and Gemini
+        let max_temperature = match vendor {
+            "anthropic" => 1.0,
+            _ => 2.0,
+        };
+        check_range("temperature", self.temperature, 0.0, max_temperature)?;
+        check_range("topP", self.top_p, 0.0, 1.0)?;
+        check_range("presencePenalty", self.presence_penalty, -2.0, 2.0)?;
+        check_range("frequencyPenalty", self.frequency_penalty, -2.0, 2.0)?;
+
+        if self.max_tokens == Some(0) || self.max_completion_tokens == Some(0) {
+            return Err(Error::Validation(
+                "maxTokens must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.n == Some(0) {
+            return Err(Error::Validation("n must be greater than 0".to_string()));
+        }
+
+        if let Some(stop) = &self.stop {
+            if stop.len() > 4 || stop.iter().any(String::is_empty) {
+                return Err(Error::Validation(
+                    "stop must be up to 4 non-empty sequences".to_string(),
+                ));
+            }
+        }
+
+        for (token, bias) in self.logit_bias.iter().flatten() {
+            if token.parse::<u32>().is_err() {
+                return Err(Error::Validation(format!(
+                    "logitBias keys must be token ids, got {}",
+                    token
+                )));
+            }
+            check_range("logitBias", Some(*bias as f64), -100.0, 100.0)?;
+        }
+
+        // Anthropic has no structured output, a JSON reply would go unchecked
+        if vendor == "anthropic"
+            && !matches!(self.response_format, None | Some(ChatResponseFormat::Text))
+        {
+            return Err(Error::Validation(
+                "responseFormat is not supported by anthropic".to_string(),
+            ));
+        }
+
+        if let Some(ChatResponseFormat::JsonSchema { name, schema, .. }) = &self.response_format {
+            if name.is_empty() {
+                return Err(Error::Validation(
+                    "responseFormat must name its schema".to_string(),
+                ));
+            }
+            jsonschema::JSONSchema::compile(schema).map_err(|err| {
+                Error::Validation(format!("responseFormat has an invalid schema: {}", err))
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The format of the reply.
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Clone, Debug)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ChatResponseFormat {
+    Text,
+    /// A valid JSON object, the messages should ask for JSON as well
+    JsonObject,
+    /// A JSON value matching the schema
+    JsonSchema {
+        name: String,
+        schema: serde_json::Value,
+        /// Ask the provider to follow the schema exactly, if supported
+        #[serde(default)]
+        strict: bool,
+    },
+}
+
+impl ChatResponseFormat {
+    /// Parse the assembled reply as the JSON the format asks for, `None` for plain text.
+    pub fn parse(
+        &self,
+        reply: &str,
+    ) -> std::result::Result<Option<serde_json::Value>, StreamError> {
+        let schema = match self {
+            ChatResponseFormat::Text => return Ok(None),
+            ChatResponseFormat::JsonObject => None,
+            ChatResponseFormat::JsonSchema { schema, .. } => Some(schema),
+        };
+
+        let value = serde_json::from_str::<serde_json::Value>(reply)
+            .map_err(|err| StreamError::SchemaViolation(format!("invalid JSON: {}", err)))?;
+
+        if let Some(schema) = schema {
+            let schema = jsonschema::JSONSchema::compile(schema)
+                .map_err(|err| StreamError::SchemaViolation(err.to_string()))?;
+            let errors = match schema.validate(&value) {
+                Ok(()) => vec![],
+                Err(errors) => errors
+                    .map(|err| match err.instance_path.to_string() {
+                        path if path.is_empty() => err.to_string(),
+                        path => format!("{}: {}", path, err),
+                    })
+                    .collect::<Vec<_>>(),
+            };
+            if !errors.is_empty() {
+                return Err(StreamError::SchemaViolation(errors.join("; ")));
+            }
         }
+
+        Ok(Some(value))
+    }
+}
+
+/// A function the model may call.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatTool {
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// JSON Schema of the function arguments.
+    pub parameters: serde_json::Value,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum ChatToolChoice {
+    /// Never call a tool
+    None,
+    /// Let the model decide
+    Auto,
+    /// Call one or more tools
+    Required,
+    /// Call the named tool
+    Function { name: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::{ChatConfig, ChatParams, ChatResponseFormat};
+
+    #[test]
+    fn test_default_config() {
+        // New chats send as much history as fits in the context
+        let config = ChatConfig::default();
+        assert_eq!(config.backtrack, 0);
+        assert!(!config.summarize);
+        assert!(config.variables.is_empty());
+    }
+
+    #[test]
+    fn test_validate_params() {
+        let params = ChatParams {
+            temperature: Some(0.7),
+            top_p: Some(1.0),
+            max_tokens: Some(256),
+            stop: Some(vec!["\n\n".to_string()]),
+            logit_bias: Some(HashMap::from([("50256".to_string(), -100)])),
+            ..Default::default()
+        };
+        assert!(params.validate("openai").is_ok());
+
+        let params = ChatParams {
+            temperature: Some(1.5),
+            ..Default::default()
+        };
+        assert!(params.validate("openai").is_ok());
+        assert!(params.validate("anthropic").is_err());
+
+        let params = ChatParams {
+            response_format: Some(ChatResponseFormat::JsonObject),
+            ..Default::default()
+        };
+        assert!(params.validate("openai").is_ok());
+        assert!(params.validate("anthropic").is_err());
+
+        let invalid = [
+            ChatParams {
+                temperature: Some(2.5),
+                ..Default::default()
+            },
+            ChatParams {
+                top_p: Some(-0.1),
+                ..Default::default()
+            },
+            ChatParams {
+                max_tokens: Some(0),
+                ..Default::default()
+            },
+            ChatParams {
+                n: Some(0),
+                ..Default::default()
+            },
+            ChatParams {
+                stop: Some(vec![String::new()]),
+                ..Default::default()
+            },
+            ChatParams {
+                logit_bias: Some(HashMap::from([("token".to_string(), 1)])),
+                ..Default::default()
+            },
+            ChatParams {
+                logit_bias: Some(HashMap::from([("50256".to_string(), 101)])),
+                ..Default::default()
+            },
+        ];
+        for params in invalid {
+            assert!(params.validate("openai").is_err());
+        }
+    }
+
+    #[test]
+    fn test_parse_structured_reply() {
+        let format = ChatResponseFormat::JsonSchema {
+            name: "person".to_string(),
+            schema: json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "age": { "type": "integer" }
+                },
+                "required": ["name", "age"]
+            }),
+            strict: false,
+        };
+        assert!(ChatParams {
+            response_format: Some(format.clone()),
+            ..Default::default()
+        }
+        .validate("openai")
+        .is_ok());
+
+        assert_eq!(
+            format.parse(r#"{"name": "Alice", "age": 30}"#).unwrap(),
+            Some(json!({ "name": "Alice", "age": 30 }))
+        );
+        assert!(format.parse(r#"{"name": "Alice", "age": "30"}"#).is_err());
+        assert!(format.parse(r#"{"name": "Alice"}"#).is_err());
+        assert!(format.parse("Alice, 30").is_err());
+        assert_eq!(ChatResponseFormat::Text.parse("Alice, 30").unwrap(), None);
+        assert!(ChatResponseFormat::JsonObject
+            .parse("[1, 2]")
+            .unwrap()
+            .is_some());
+
+        let invalid = ChatParams {
+            response_format: Some(ChatResponseFormat::JsonSchema {
+                name: "person".to_string(),
+                schema: json!({ "type": "person" }),
+                strict: false,
+            }),
+            ..Default::default()
+        };
+        assert!(invalid.validate("openai").is_err());
     }
 }
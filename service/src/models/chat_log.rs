@@ -6,7 +6,7 @@ use serde::Serialize;
 
 use crate::api::openai::chat::params::OpenAIChatRole;
 use crate::schema::chat_logs;
-use crate::types::{Id, TextWrapper};
+use crate::types::{Id, JsonWrapper, TextWrapper};
 
 #[derive(Queryable, Serialize)]
 pub struct ChatLog {
@@ -20,6 +20,44 @@ pub struct ChatLog {
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
     pub finished: bool,
+    /// Tools called by an assistant reply
+    pub tool_calls: JsonWrapper<Vec<ToolCall>>,
+    /// The call answered by a tool message
+    pub tool_call_id: Option<String>,
+    pub finish_reason: Option<TextWrapper<FinishReason>>,
+    /// Prompt tokens of a reply, reported by the provider or estimated
+    pub prompt_tokens: Option<i32>,
+    /// Milliseconds to the first token of a reply
+    pub time_to_first_token: Option<i32>,
+    /// Logs sent as history along with the prompt of a reply
+    pub context_log_ids: JsonWrapper<Vec<Id>>,
+    /// Reply parsed as JSON, when the chat asks for a JSON response format
+    pub structured: Option<JsonWrapper<serde_json::Value>>,
+    /// Reasoning streamed before a reply, never sent back as context
+    pub reasoning: Option<String>,
+    /// The log this one follows, logs sharing a parent are branches of the chat
+    pub parent_id: Option<Id>,
+    /// Milliseconds to the last token of a reply
+    pub latency: Option<i32>,
+}
+
+impl ChatLog {
+    /// The reply was cut by the max tokens limit
+    pub fn truncated(&self) -> bool {
+        matches!(
+            self.finish_reason.as_ref().map(|reason| &reason.0),
+            Some(FinishReason::Length)
+        )
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Eq, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    /// JSON encoded arguments, as generated by the model
+    pub arguments: String,
 }
 
 #[derive(Hash, PartialEq, Eq, Clone, Serialize, Debug)]
@@ -28,6 +66,7 @@ pub enum Role {
     System,
     Assistant,
     User,
+    Tool,
 }
 
 impl AsRef<str> for Role {
@@ -36,6 +75,7 @@ impl AsRef<str> for Role {
             Role::System => "system",
             Role::Assistant => "assistant",
             Role::User => "user",
+            Role::Tool => "tool",
         }
     }
 }
@@ -48,6 +88,7 @@ impl FromStr for Role {
             "system" => Ok(Role::System),
             "assistant" => Ok(Role::Assistant),
             "user" => Ok(Role::User),
+            "tool" => Ok(Role::Tool),
             _ => Err("Invalid role".into()),
         }
     }
@@ -59,6 +100,44 @@ impl From<Role> for OpenAIChatRole {
             Role::System => OpenAIChatRole::System,
             Role::Assistant => OpenAIChatRole::Assistant,
             Role::User => OpenAIChatRole::User,
+            Role::Tool => OpenAIChatRole::Tool,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum FinishReason {
+    Stop,
+    Length,
+    ContentFilter,
+    ToolCalls,
+    Other,
+}
+
+impl AsRef<str> for FinishReason {
+    fn as_ref(&self) -> &str {
+        match self {
+            FinishReason::Stop => "stop",
+            FinishReason::Length => "length",
+            FinishReason::ContentFilter => "content_filter",
+            FinishReason::ToolCalls => "tool_calls",
+            FinishReason::Other => "other",
+        }
+    }
+}
+
+impl FromStr for FinishReason {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stop" => Ok(FinishReason::Stop),
+            "length" => Ok(FinishReason::Length),
+            "content_filter" => Ok(FinishReason::ContentFilter),
+            "tool_calls" => Ok(FinishReason::ToolCalls),
+            "other" => Ok(FinishReason::Other),
+            _ => Err("Invalid finish reason".into()),
         }
     }
 }
@@ -74,6 +153,7 @@ pub struct PatchChatLog {
     pub tokens: Option<i32>,
     pub cost: Option<f32>,
     pub finished: Option<bool>,
+    pub tool_calls: Option<JsonWrapper<Vec<ToolCall>>>,
 }
 
 #[derive(Insertable)]
@@ -87,4 +167,36 @@ pub struct NewChatLog {
     pub tokens: i32,
     pub cost: f32,
     pub finished: bool,
+    pub tool_calls: JsonWrapper<Vec<ToolCall>>,
+    pub tool_call_id: Option<String>,
+    pub finish_reason: Option<TextWrapper<FinishReason>>,
+    pub prompt_tokens: Option<i32>,
+    pub time_to_first_token: Option<i32>,
+    pub context_log_ids: JsonWrapper<Vec<Id>>,
+    pub structured: Option<JsonWrapper<serde_json::Value>>,
+    pub reasoning: Option<String>,
+    pub parent_id: Option<Id>,
+    pub latency: Option<i32>,
+}
+
+/// A log matching a full-text search of the chat history
+#[derive(QueryableByName, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatLogHit {
+    /// Log of the hit, `None` when only the chat title matched.
+    /// Opening it switches the chat to the branch of the log, then loads from it on
+    #[diesel(sql_type = sql_types::Nullable<sql_types::Binary>)]
+    pub id: Option<Id>,
+    #[diesel(sql_type = sql_types::Binary)]
+    pub chat_id: Id,
+    #[diesel(sql_type = sql_types::Nullable<sql_types::Text>)]
+    pub role: Option<TextWrapper<Role>>,
+    /// Title of the chat as HTML, with matched terms in `<mark>`
+    #[diesel(sql_type = sql_types::Text)]
+    pub title: String,
+    /// Excerpt of the message around the matched terms as HTML, highlighted alike
+    #[diesel(sql_type = sql_types::Text)]
+    pub snippet: String,
+    #[diesel(sql_type = sql_types::Timestamp)]
+    pub created_at: NaiveDateTime,
 }
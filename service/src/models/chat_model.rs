@@ -1,8 +1,9 @@
 use chrono::NaiveDateTime;
 use serde::Serialize;
 
+use crate::models::chat::ChatParams;
 use crate::schema::chat_models;
-use crate::types::Id;
+use crate::types::{Id, JsonWrapper};
 use diesel::*;
 
 #[derive(Queryable, Serialize)]
@@ -16,12 +17,65 @@ pub struct ChatModel {
     pub vendor: String,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub server_id: Option<Id>,
+    /// The provider no longer lists the model
+    pub unlisted: bool,
+    pub capabilities: JsonWrapper<ChatModelCapabilities>,
+    /// The model answering under `name` when they differ, e.g. of an Azure deployment
+    pub served_model: Option<String>,
 }
 
 impl ChatModel {
     pub fn calc_cost(&self, tokens: usize) -> f32 {
         self.price * tokens as f32 / 1000.0
     }
+
+    /// The model tokens and the context size are counted for.
+    pub fn served_model(&self) -> &str {
+        self.served_model.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// Parameter rules of a model differing from the common ones, e.g. of reasoning models.
+#[derive(serde::Serialize, serde::Deserialize, Default, PartialEq, Eq, Clone, Debug)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChatModelCapabilities {
+    /// Temperature, top p, penalties and logit bias are rejected
+    pub fixed_sampling: bool,
+    /// The max tokens must be sent as `max_completion_tokens`
+    pub max_completion_tokens: bool,
+}
+
+impl ChatModelCapabilities {
+    /// Capabilities of the known models, by name.
+    pub fn detect(name: &str) -> Self {
+        let reasoning = ["o1", "o3", "o4"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+            || (name.starts_with("gpt-5") && !name.starts_with("gpt-5-chat"));
+
+        Self {
+            fixed_sampling: reasoning,
+            max_completion_tokens: reasoning,
+        }
+    }
+
+    /// Drop or rename the parameters the model does not accept.
+    pub fn adapt(&self, mut params: ChatParams) -> ChatParams {
+        if self.fixed_sampling {
+            params.temperature = None;
+            params.top_p = None;
+            params.presence_penalty = None;
+            params.frequency_penalty = None;
+            params.logit_bias = None;
+        }
+        if self.max_completion_tokens {
+            params.max_completion_tokens =
+                params.max_completion_tokens.or(params.max_tokens.take());
+        }
+
+        params
+    }
 }
 
 #[derive(Insertable, AsChangeset)]
@@ -34,6 +88,9 @@ pub struct NewChatModel {
     pub price: f32,
     pub unit: String,
     pub vendor: String,
+    pub server_id: Option<Id>,
+    pub capabilities: JsonWrapper<ChatModelCapabilities>,
+    pub served_model: Option<String>,
 }
 
 #[derive(AsChangeset)]
@@ -45,4 +102,37 @@ pub struct PatchChatModel {
     pub price: Option<f32>,
     pub unit: Option<String>,
     pub vendor: Option<String>,
-}
\ No newline at end of file
+    pub unlisted: Option<bool>,
+    pub capabilities: Option<JsonWrapper<ChatModelCapabilities>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::chat::ChatParams;
+
+    use super::ChatModelCapabilities;
+
+    #[test]
+    fn test_adapt_params() {
+        let params = ChatParams {
+            model: "o3-mini".to_string(),
+            temperature: Some(0.7),
+            presence_penalty: Some(1.0),
+            max_tokens: Some(1024),
+            ..Default::default()
+        };
+
+        let capabilities = ChatModelCapabilities::detect(&params.model);
+        let adapted = capabilities.adapt(params.clone());
+        assert_eq!(adapted.temperature, None);
+        assert_eq!(adapted.presence_penalty, None);
+        assert_eq!(adapted.max_tokens, None);
+        assert_eq!(adapted.max_completion_tokens, Some(1024));
+
+        let capabilities = ChatModelCapabilities::detect("gpt-4o");
+        assert_eq!(capabilities, ChatModelCapabilities::default());
+        let adapted = capabilities.adapt(params);
+        assert_eq!(adapted.temperature, Some(0.7));
+        assert_eq!(adapted.max_tokens, Some(1024));
+    }
+}
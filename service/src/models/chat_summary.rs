@@ -0,0 +1,43 @@
+use chrono::NaiveDateTime;
+use diesel::*;
+use serde::Serialize;
+
+use crate::schema::{chat_summaries, chat_summary_logs};
+use crate::types::Id;
+
+/// A condensed version of the logs of a chat, from `first_log_id` to `last_log_id`,
+/// sent in place of them.
+#[derive(Queryable, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatSummary {
+    pub id: Id,
+    pub chat_id: Id,
+    pub content: String,
+    pub first_log_id: Id,
+    pub last_log_id: Id,
+    pub model: String,
+    pub tokens: i32,
+    pub cost: f32,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = chat_summaries)]
+pub struct NewChatSummary {
+    pub id: Id,
+    pub chat_id: Id,
+    pub content: String,
+    pub first_log_id: Id,
+    pub last_log_id: Id,
+    pub model: String,
+    pub tokens: i32,
+    pub cost: f32,
+}
+
+/// A log covered by a summary, the summary is deleted along with any of its logs.
+#[derive(Insertable)]
+#[diesel(table_name = chat_summary_logs)]
+pub struct NewChatSummaryLog {
+    pub summary_id: Id,
+    pub log_id: Id,
+}
@@ -1,18 +1,42 @@
+use std::collections::HashMap;
+
 use chrono::NaiveDateTime;
 use diesel::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
+use crate::models::chat_log::Role;
+use crate::result::Result;
 use crate::schema::prompts;
-use crate::types::Id;
+use crate::types::{Id, JsonWrapper};
+use crate::Error;
 
 #[derive(Queryable, Serialize)]
 pub struct Prompt {
     pub id: Id,
     pub name: String,
-    pub content: String,
     pub user_id: Id,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub messages: JsonWrapper<Vec<PromptMessage>>,
+    pub variables: JsonWrapper<Vec<PromptVariable>>,
+}
+
+impl Prompt {
+    /// The messages with their `{name}` placeholders replaced by the values
+    /// of the variables, or their defaults.
+    pub fn render(&self, values: &HashMap<String, String>) -> Result<Vec<PromptMessage>> {
+        let values = PromptVariable::fill(&self.variables.0, values)?;
+
+        Ok(self
+            .messages
+            .0
+            .iter()
+            .map(|message| PromptMessage {
+                role: message.role,
+                content: render(&message.content, &values),
+            })
+            .collect())
+    }
 }
 
 #[derive(Queryable, Serialize)]
@@ -20,11 +44,13 @@ pub struct Prompt {
 pub struct PromptIndex {
     pub id: Id,
     pub name: String,
-    #[serde(skip_serializing)]
-    pub content: String,
     pub user_id: Id,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    #[serde(skip_serializing)]
+    pub messages: JsonWrapper<Vec<PromptMessage>>,
+    #[serde(skip_serializing)]
+    pub variables: JsonWrapper<Vec<PromptVariable>>,
 }
 
 #[derive(Insertable)]
@@ -32,8 +58,9 @@ pub struct PromptIndex {
 pub struct NewPrompt {
     pub id: Id,
     pub name: String,
-    pub content: String,
     pub user_id: Id,
+    pub messages: JsonWrapper<Vec<PromptMessage>>,
+    pub variables: JsonWrapper<Vec<PromptVariable>>,
 }
 
 #[derive(AsChangeset, Default, Debug)]
@@ -41,5 +68,190 @@ pub struct NewPrompt {
 pub struct PatchPrompt {
     pub id: Id,
     pub name: Option<String>,
-    pub content: Option<String>,
+    pub messages: Option<JsonWrapper<Vec<PromptMessage>>>,
+    pub variables: Option<JsonWrapper<Vec<PromptVariable>>>,
+}
+
+/// A message of a prompt, sent in order before the history of the chat:
+/// system instructions, or user and assistant turns as examples.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptMessage {
+    pub role: PromptRole,
+    pub content: String,
+}
+
+impl PromptMessage {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self {
+            role: PromptRole::System,
+            content: content.into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum PromptRole {
+    System,
+    User,
+    Assistant,
+}
+
+impl From<PromptRole> for Role {
+    fn from(role: PromptRole) -> Self {
+        match role {
+            PromptRole::System => Role::System,
+            PromptRole::User => Role::User,
+            PromptRole::Assistant => Role::Assistant,
+        }
+    }
+}
+
+/// A variable of a prompt, written `{name}` in its messages
+/// and filled in when a chat is created with the prompt.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptVariable {
+    pub name: String,
+    /// The variable is required unless it has a default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl PromptVariable {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            default: None,
+            description: None,
+        }
+    }
+
+    /// Required variables of the placeholders in a text, in order of appearance.
+    pub fn parse(text: &str) -> Vec<Self> {
+        let mut variables: Vec<Self> = vec![];
+        for name in placeholders(text) {
+            if variables.iter().all(|variable| variable.name != name) {
+                variables.push(Self::new(name));
+            }
+        }
+
+        variables
+    }
+
+    /// The value of each variable, missing required ones are an error.
+    pub fn fill<'a>(
+        variables: &'a [Self],
+        values: &'a HashMap<String, String>,
+    ) -> Result<HashMap<&'a str, &'a str>> {
+        let mut filled = HashMap::new();
+        let mut missing = vec![];
+        for variable in variables {
+            match values.get(&variable.name).or(variable.default.as_ref()) {
+                Some(value) => {
+                    filled.insert(variable.name.as_str(), value.as_str());
+                }
+                None => missing.push(variable.name.as_str()),
+            }
+        }
+
+        if !missing.is_empty() {
+            return Err(Error::Validation(format!(
+                "missing prompt variables: {}",
+                missing.join(", ")
+            )));
+        }
+
+        Ok(filled)
+    }
+}
+
+/// The `{name}` placeholders of a text, names are made of letters, digits and `_`.
+fn placeholders(text: &str) -> impl Iterator<Item = &str> {
+    text.split('{').skip(1).filter_map(|part| {
+        let (name, _) = part.split_once('}')?;
+        let valid = !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+
+        valid.then_some(name)
+    })
+}
+
+/// Replace the placeholders of the given variables, others are left as is.
+fn render(text: &str, values: &HashMap<&str, &str>) -> String {
+    let mut rendered = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let value = rest[1..]
+            .split_once('}')
+            .and_then(|(name, _)| Some((name, values.get(name)?)));
+        match value {
+            Some((name, value)) => {
+                rendered.push_str(value);
+                rest = &rest[name.len() + 2..];
+            }
+            None => {
+                rendered.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{render, PromptVariable};
+
+    #[test]
+    fn test_parse_variables() {
+        let variables = PromptVariable::parse(
+            "I want you to act as a {language} translator, reply in {language} \
+            with {style_1} {}{1st} {not a name} {\"json\": 1}",
+        );
+
+        assert_eq!(
+            variables,
+            vec![
+                PromptVariable::new("language"),
+                PromptVariable::new("style_1")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fill_and_render() {
+        let variables = vec![
+            PromptVariable::new("language"),
+            PromptVariable {
+                default: Some("formal".to_string()),
+                ..PromptVariable::new("tone")
+            },
+        ];
+        let text = "Translate to {language} in a {tone} tone, as {\"to\": \"{language}\"} {other}";
+
+        let err = PromptVariable::fill(&variables, &HashMap::new()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid input: missing prompt variables: language"
+        );
+
+        let values = HashMap::from([("language".to_string(), "French".to_string())]);
+        let filled = PromptVariable::fill(&variables, &values).unwrap();
+        assert_eq!(
+            render(text, &filled),
+            "Translate to French in a formal tone, as {\"to\": \"French\"} {other}"
+        );
+    }
 }
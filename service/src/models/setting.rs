@@ -3,10 +3,15 @@ use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 use std::time::Duration;
 
-use crate::api::client::Client;
+use crate::api::anthropic::chat::{AnthropicChatApi, ANTHROPIC_VERSION};
+use crate::api::anthropic::model::AnthropicModelApi;
+use crate::api::client::{Client, RetryPolicy};
+use crate::api::gemini::chat::GeminiChatApi;
+use crate::api::local::LocalApi;
 use crate::api::openai::chat::OpenAIChatApi;
+use crate::api::openai::model::OpenAIModelApi;
 use crate::schema::settings;
-use crate::types::{Id, TextWrapper};
+use crate::types::{Id, JsonWrapper, TextWrapper};
 
 #[derive(Queryable, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -24,6 +29,15 @@ pub struct Setting {
     pub hide_taskbar: bool,
     pub home_page: TextWrapper<HomePage>,
     pub scale: i32,
+    pub anthropic_api_key: Option<String>,
+    pub anthropic_url: Option<String>,
+    pub local_servers: JsonWrapper<Vec<LocalServer>>,
+    pub azure: Option<JsonWrapper<AzureSetting>>,
+    pub gemini_api_key: Option<String>,
+    pub gemini_url: Option<String>,
+    pub retry_policy: JsonWrapper<RetryPolicy>,
+    /// Title new chats after their first exchange
+    pub auto_title: bool,
 }
 
 impl Setting {
@@ -45,6 +59,38 @@ impl Setting {
             .and_then(|inner| if inner.is_empty() { None } else { Some(inner) })
     }
 
+    pub fn anthropic_api_key(&self) -> Option<&str> {
+        self.anthropic_api_key.as_deref().and_then(|inner| {
+            if inner.is_empty() {
+                None
+            } else {
+                Some(inner)
+            }
+        })
+    }
+
+    pub fn anthropic_url(&self) -> Option<&str> {
+        self.anthropic_url
+            .as_deref()
+            .and_then(|inner| if inner.is_empty() { None } else { Some(inner) })
+    }
+
+    pub fn gemini_api_key(&self) -> Option<&str> {
+        self.gemini_api_key
+            .as_deref()
+            .and_then(|inner| if inner.is_empty() { None } else { Some(inner) })
+    }
+
+    pub fn gemini_url(&self) -> Option<&str> {
+        self.gemini_url
+            .as_deref()
+            .and_then(|inner| if inner.is_empty() { None } else { Some(inner) })
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy.as_ref().clone()
+    }
+
     pub fn create_client(&self, timeout: Option<Duration>) -> Client {
         let proxy = self.proxy().map(|item| reqwest::Proxy::all(item).unwrap());
 
@@ -55,6 +101,22 @@ impl Setting {
     }
 
     pub fn create_openai_chat(&self) -> OpenAIChatApi {
+        let mut api = OpenAIChatApi::new(self.create_openai_client(), self.openai_host());
+        // A forward url may point to any OpenAI compatible proxy
+        api.include_usage(self.forward_url().is_none());
+
+        api
+    }
+
+    pub fn create_openai_model_api(&self) -> OpenAIModelApi {
+        OpenAIModelApi::new(self.create_openai_client(), self.openai_host())
+    }
+
+    fn openai_host(&self) -> &str {
+        self.forward_url().unwrap_or("https://api.openai.com")
+    }
+
+    fn create_openai_client(&self) -> Client {
         let mut headers = reqwest::header::HeaderMap::new();
 
         if let Some(api_key) = self.api_key() {
@@ -72,10 +134,103 @@ impl Setting {
         let mut client = Client::new(None);
         client.headers(Some(headers));
         client.proxy(proxy);
+        client.retry_policy(self.retry_policy());
+
+        client
+    }
+
+    pub fn local_server(&self, id: Id) -> Option<&LocalServer> {
+        self.local_servers
+            .as_ref()
+            .iter()
+            .find(|server| server.id == id)
+    }
+
+    pub fn create_local_api(&self, server: &LocalServer) -> LocalApi {
+        LocalApi::new(Client::new(None), server.host())
+    }
+
+    pub fn create_local_chat(&self, server: &LocalServer) -> OpenAIChatApi {
+        // Local servers need neither an api key nor the proxy
+        let mut client = Client::new(None);
+        client.retry_policy(self.retry_policy());
+
+        OpenAIChatApi::new(client, server.host())
+    }
+
+    pub fn azure(&self) -> Option<&AzureSetting> {
+        self.azure.as_ref().map(|azure| azure.as_ref())
+    }
+
+    pub fn create_azure_chat(&self, azure: &AzureSetting, deployment: &str) -> OpenAIChatApi {
+        let mut headers = reqwest::header::HeaderMap::new();
+
+        if let Some(api_key) = azure.api_key() {
+            headers.insert("api-key", api_key.parse().unwrap());
+        }
+
+        let proxy = self.proxy().map(|item| reqwest::Proxy::all(item).unwrap());
+
+        let mut client = Client::new(None);
+        client.headers(Some(headers));
+        client.proxy(proxy);
+        client.retry_policy(self.retry_policy());
+
+        let mut api = OpenAIChatApi::with_url(client, &azure.chat_completions_url(deployment));
+        api.include_usage(azure.supports_stream_usage());
+
+        api
+    }
+
+    pub fn create_anthropic_chat(&self) -> AnthropicChatApi {
+        AnthropicChatApi::new(self.create_anthropic_client(), self.anthropic_host())
+    }
+
+    pub fn create_anthropic_model_api(&self) -> AnthropicModelApi {
+        AnthropicModelApi::new(self.create_anthropic_client(), self.anthropic_host())
+    }
+
+    fn anthropic_host(&self) -> &str {
+        self.anthropic_url().unwrap_or("https://api.anthropic.com")
+    }
+
+    fn create_anthropic_client(&self) -> Client {
+        let mut headers = reqwest::header::HeaderMap::new();
+
+        if let Some(api_key) = self.anthropic_api_key() {
+            headers.insert("x-api-key", api_key.parse().unwrap());
+        }
+        headers.insert("anthropic-version", ANTHROPIC_VERSION.parse().unwrap());
+
+        let proxy = self.proxy().map(|item| reqwest::Proxy::all(item).unwrap());
+
+        let mut client = Client::new(None);
+        client.headers(Some(headers));
+        client.proxy(proxy);
+        client.retry_policy(self.retry_policy());
 
-        let host = self.forward_url().unwrap_or("https://api.openai.com");
+        client
+    }
 
-        OpenAIChatApi::new(client, host)
+    pub fn create_gemini_chat(&self) -> GeminiChatApi {
+        let mut headers = reqwest::header::HeaderMap::new();
+
+        if let Some(api_key) = self.gemini_api_key() {
+            headers.insert("x-goog-api-key", api_key.parse().unwrap());
+        }
+
+        let proxy = self.proxy().map(|item| reqwest::Proxy::all(item).unwrap());
+
+        let mut client = Client::new(None);
+        client.headers(Some(headers));
+        client.proxy(proxy);
+        client.retry_policy(self.retry_policy());
+
+        let host = self
+            .gemini_url()
+            .unwrap_or("https://generativelanguage.googleapis.com");
+
+        GeminiChatApi::new(client, host)
     }
 
     pub fn home_page_url(&self) -> String {
@@ -83,6 +238,63 @@ impl Setting {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalServer {
+    pub id: Id,
+    pub name: String,
+    /// Base url of the server, e.g. `http://localhost:11434`
+    pub url: String,
+}
+
+impl LocalServer {
+    pub fn host(&self) -> &str {
+        self.url.trim_end_matches('/')
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AzureSetting {
+    /// Resource endpoint, e.g. `https://my-resource.openai.azure.com`
+    pub endpoint: String,
+    pub api_key: Option<String>,
+    pub api_version: String,
+    pub deployments: Vec<AzureDeployment>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AzureDeployment {
+    /// Deployment name, used as the chat model name
+    pub name: String,
+    /// The model served by the deployment, e.g. `gpt-4`
+    pub model: String,
+}
+
+impl AzureSetting {
+    pub fn api_key(&self) -> Option<&str> {
+        self.api_key
+            .as_deref()
+            .and_then(|inner| if inner.is_empty() { None } else { Some(inner) })
+    }
+
+    pub fn chat_completions_url(&self, deployment: &str) -> String {
+        format!(
+            "{}/openai/deployments/{}/chat/completions?api-version={}",
+            self.endpoint.trim_end_matches('/'),
+            deployment,
+            self.api_version
+        )
+    }
+
+    /// `stream_options` is accepted from the `2024-09-01-preview` api version on.
+    pub fn supports_stream_usage(&self) -> bool {
+        // Api versions are dates, optionally suffixed with `-preview`
+        self.api_version.as_str() >= "2024-09-01"
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub enum HomePage {
@@ -180,4 +392,37 @@ pub struct PatchSetting {
     pub enable_web_server: Option<bool>,
     pub home_page: Option<TextWrapper<HomePage>>,
     pub scale: Option<i32>,
+    pub anthropic_api_key: Option<String>,
+    pub anthropic_url: Option<String>,
+    pub local_servers: Option<JsonWrapper<Vec<LocalServer>>>,
+    pub azure: Option<JsonWrapper<AzureSetting>>,
+    pub gemini_api_key: Option<String>,
+    pub gemini_url: Option<String>,
+    pub retry_policy: Option<JsonWrapper<RetryPolicy>>,
+    pub auto_title: Option<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AzureSetting;
+
+    #[test]
+    fn test_azure_chat_completions_url() {
+        let mut azure = AzureSetting {
+            endpoint: "https://my-resource.openai.azure.com/".to_string(),
+            api_key: None,
+            api_version: "2024-08-01-preview".to_string(),
+            deployments: vec![],
+        };
+        assert_eq!(
+            azure.chat_completions_url("team-gpt"),
+            "https://my-resource.openai.azure.com/openai/deployments/team-gpt/chat/completions?api-version=2024-08-01-preview"
+        );
+        assert!(!azure.supports_stream_usage());
+
+        azure.api_version = "2024-09-01-preview".to_string();
+        assert!(azure.supports_stream_usage());
+        azure.api_version = "2024-10-21".to_string();
+        assert!(azure.supports_stream_usage());
+    }
 }
@@ -1,6 +1,7 @@
 use std::io::Write;
 use std::time::Duration;
 
+use crate::models::chat::ChatParams;
 use crate::services::plugin::PluginService;
 use crate::{Id, StreamContent};
 use host::WasiCtx;
@@ -17,15 +18,17 @@ bindgen!({
 pub struct RunningPluginState {
     wasi_ctx: WasiCtx,
     plugin_service: PluginService,
+    chat_params: ChatParams,
     loading_bar: Option<ProgressBar>,
 }
 
 impl RunningPluginState {
-    pub fn new(plugin_service: PluginService) -> Self {
+    pub fn new(plugin_service: PluginService, chat_params: ChatParams) -> Self {
         let wasi_ctx = WasiCtxBuilder::new().inherit_stdio().build();
         Self {
             wasi_ctx,
             plugin_service,
+            chat_params,
             loading_bar: None,
         }
     }
@@ -84,14 +87,21 @@ impl Host_Imports for RunningPluginState {
     }
 
     async fn host_openai(&mut self, prompt: String) -> wasmtime::Result<(i32, String)> {
-        match self.plugin_service.send_message(&prompt).await {
+        match self
+            .plugin_service
+            .send_message(&prompt, self.chat_params.clone())
+            .await
+        {
             Ok(reply) => Ok((0, reply)),
             Err(err) => Ok((1, err.to_string())),
         }
     }
 
     async fn host_openai_stream(&mut self, prompt: String) -> wasmtime::Result<String> {
-        let id = self.plugin_service.send_message_stream(&prompt).await?;
+        let id = self
+            .plugin_service
+            .send_message_stream(&prompt, self.chat_params.clone())
+            .await?;
 
         Ok(id.to_string())
     }
@@ -106,6 +116,12 @@ impl Host_Imports for RunningPluginState {
         let message = message.and_then(|content| match content {
             StreamContent::Error(err) => Some(Err(err.to_string())),
             StreamContent::Data(data) => Some(Ok(data)),
+            // Plugins do not define tools
+            StreamContent::ToolCall(_) => Some(Ok(String::new())),
+            StreamContent::Reasoning(_)
+            | StreamContent::Meta(_)
+            | StreamContent::Retry(_)
+            | StreamContent::Choice(_) => Some(Ok(String::new())),
             StreamContent::Done => None,
         });
 
@@ -175,13 +191,15 @@ impl RunningPlugin {
 mod tests {
     use std::thread;
 
-    use crate::{services::plugin::PluginService, test::establish_connection};
+    use crate::{
+        models::chat::ChatParams, services::plugin::PluginService, test::establish_connection,
+    };
 
     #[test]
     fn test_loading() {
         let conn = establish_connection();
         let plugin_service = PluginService::new(conn);
-        let mut state = super::RunningPluginState::new(plugin_service);
+        let mut state = super::RunningPluginState::new(plugin_service, ChatParams::default());
 
         state.show_loading();
         thread::sleep(std::time::Duration::from_secs(3));
@@ -192,7 +210,7 @@ mod tests {
     async fn test_commit_summary() {
         let conn = establish_connection();
         let plugin_service = PluginService::new(conn);
-        let state = super::RunningPluginState::new(plugin_service);
+        let state = super::RunningPluginState::new(plugin_service, ChatParams::default());
 
         let binary = std::fs::read("../../chat-wizard-plugins/built/commit_summary.wasm").unwrap();
         let plugin = super::RunningPlugin::init(&binary, state).await.unwrap();
@@ -203,7 +221,7 @@ mod tests {
     async fn test_chat() {
         let conn = establish_connection();
         let plugin_service = PluginService::new(conn);
-        let state = super::RunningPluginState::new(plugin_service);
+        let state = super::RunningPluginState::new(plugin_service, ChatParams::default());
 
         let binary = std::fs::read("../../chat-wizard-plugins/built/chat.wasm").unwrap();
         let plugin = super::RunningPlugin::init(&binary, state).await.unwrap();
@@ -0,0 +1,38 @@
+use crate::models::attachment::{Attachment, NewAttachment};
+use crate::result::Result;
+use crate::schema::attachments;
+use crate::{database::DbConn, types::Id};
+use diesel::prelude::*;
+
+#[derive(Clone)]
+pub struct AttachmentRepo(DbConn);
+
+impl AttachmentRepo {
+    pub fn new(conn: DbConn) -> Self {
+        Self(conn)
+    }
+
+    pub fn select_by_chat_log_id(&self, chat_log_id: Id) -> Result<Vec<Attachment>> {
+        attachments::table
+            .filter(attachments::chat_log_id.eq(chat_log_id))
+            .order(attachments::created_at.asc())
+            .load::<Attachment>(&mut *self.0.conn())
+            .map_err(|e| e.into())
+    }
+
+    pub fn select_by_chat_log_ids(&self, chat_log_ids: &[Id]) -> Result<Vec<Attachment>> {
+        attachments::table
+            .filter(attachments::chat_log_id.eq_any(chat_log_ids))
+            .order(attachments::created_at.asc())
+            .load::<Attachment>(&mut *self.0.conn())
+            .map_err(|e| e.into())
+    }
+
+    pub fn insert(&self, attachment: &NewAttachment) -> Result<usize> {
+        let size = diesel::insert_into(attachments::table)
+            .values(attachment)
+            .execute(&mut *self.0.conn())?;
+
+        Ok(size)
+    }
+}
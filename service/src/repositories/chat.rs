@@ -241,6 +241,15 @@ impl ChatRepo {
         Ok(())
     }
 
+    pub fn update_active_log(&self, id: Id, active_log_id: Option<Id>) -> Result<()> {
+        diesel::update(chats::table)
+            .filter(chats::id.eq(id))
+            .set(chats::active_log_id.eq(active_log_id))
+            .execute(&mut *self.0.conn())?;
+
+        Ok(())
+    }
+
     pub fn add_cost_and_update(&self, id: Id, cost: f32) -> Result<usize> {
         let size = diesel::update(chats::table)
             .filter(chats::id.eq(id))
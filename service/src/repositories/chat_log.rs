@@ -1,10 +1,14 @@
+use std::collections::HashMap;
+
 use crate::database::pagination::{Paginate, PaginatedRecords};
-use crate::models::chat_log::{ChatLog, NewChatLog, PatchChatLog};
+use crate::models::chat_log::{ChatLog, ChatLogHit, NewChatLog, PatchChatLog};
 use crate::result::Result;
-use crate::schema::chat_logs;
+use crate::schema::{chat_logs, chats};
 use crate::{database::DbConn, types::Id};
 use crate::{CursorDirection, CursorQueryParams, CursorQueryResult, PageQueryParams};
+use diesel::dsl::sql;
 use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Binary, Text};
 use serde::Deserialize;
 
 #[derive(Clone)]
@@ -41,10 +45,103 @@ impl ChatLogRepo {
         Ok(result)
     }
 
+    /// Ranked full-text search over the messages and chat titles of a user
+    pub fn search(
+        &self,
+        params: PageQueryParams<ChatLogSearchParams, ()>,
+    ) -> Result<PaginatedRecords<ChatLogHit>> {
+        let per_page = params.per_page;
+        let filter_chat = params.query.chat_id.is_some();
+
+        let rows = diesel::sql_query(format!(
+            // Hits are from every branch, a title hit has no log.
+            // Auxiliary functions of FTS5 can't share a query with a window function
+            "SELECT *, COUNT(*) OVER () AS total FROM ( \
+                SELECT chat_logs.id, chats.id AS chat_id, chat_logs.role, \
+                    COALESCE(chat_logs.created_at, chats.created_at) AS created_at, \
+                    CASE WHEN chat_logs_fts_ids.log_id IS NULL \
+                        THEN highlight(chat_logs_fts, 0, '{HIGHLIGHT_START}', '{HIGHLIGHT_END}') \
+                        ELSE chats.title END AS title, \
+                    CASE WHEN chat_logs_fts_ids.log_id IS NULL THEN '' \
+                        ELSE snippet(chat_logs_fts, 1, '{HIGHLIGHT_START}', '{HIGHLIGHT_END}', '…', {SNIPPET_TOKENS}) END AS snippet, \
+                    rank \
+                FROM chat_logs_fts \
+                JOIN chat_logs_fts_ids ON chat_logs_fts_ids.fts_rowid = chat_logs_fts.rowid \
+                JOIN chats ON chats.id = chat_logs_fts_ids.chat_id \
+                LEFT JOIN chat_logs ON chat_logs.id = chat_logs_fts_ids.log_id \
+                WHERE chat_logs_fts MATCH ? AND chats.user_id = ? {} \
+            ) \
+            ORDER BY rank \
+            LIMIT ? OFFSET ?",
+            if filter_chat { "AND chats.id = ?" } else { "" }
+        ))
+        .into_boxed()
+        .bind::<Text, _>(match_expression(&params.query.keyword))
+        .bind::<Binary, _>(params.user_id);
+
+        let rows = if let Some(chat_id) = params.query.chat_id {
+            rows.bind::<Binary, _>(chat_id)
+        } else {
+            rows
+        }
+        .bind::<BigInt, _>(per_page)
+        .bind::<BigInt, _>((params.page - 1) * per_page)
+        .load::<ChatLogHitRow>(&mut *self.0.conn())?;
+
+        let total = rows.first().map(|row| row.total).unwrap_or(0);
+
+        Ok(PaginatedRecords {
+            records: rows
+                .into_iter()
+                .map(|row| ChatLogHit {
+                    title: highlight_html(&row.hit.title),
+                    snippet: highlight_html(&row.hit.snippet),
+                    ..row.hit
+                })
+                .collect(),
+            total,
+            total_pages: (total as f64 / per_page as f64).ceil() as i64,
+            per_page,
+        })
+    }
+
     pub fn select_by_cursor(
         &self,
         params: CursorQueryParams<ChatLogQueryParams, ()>,
     ) -> Result<CursorQueryResult<ChatLog>> {
+        // Logs of a chat follow its active branch
+        if let Some(chat_id) = params.query.chat_id {
+            let mut path = self.select_active_path(chat_id)?;
+            if let CursorDirection::Forward = params.direction {
+                path.reverse();
+            }
+
+            let start = match params.cursor {
+                Some(cursor) => path
+                    .iter()
+                    .position(|id| *id == cursor)
+                    .unwrap_or(path.len()),
+                None => 0,
+            };
+            let ids = path
+                .into_iter()
+                .skip(start)
+                .take(params.size as usize + 1)
+                .collect::<Vec<Id>>();
+            let mut records = self.select_by_ids(&ids)?;
+
+            let next_cursor = if records.len() > params.size as usize {
+                records.pop().map(|log| log.id)
+            } else {
+                None
+            };
+
+            return Ok(CursorQueryResult {
+                records,
+                next_cursor,
+            });
+        }
+
         let cursor_created_at = params
             .cursor
             .map(|id| {
@@ -114,17 +211,6 @@ impl ChatLogRepo {
         Ok(result)
     }
 
-    pub fn delete_since_id(&self, id: Id) -> Result<ChatLog> {
-        let target_log = self.select_by_id(id)?;
-        let chat_id = target_log.chat_id;
-        diesel::delete(chat_logs::table)
-            .filter(chat_logs::chat_id.eq(chat_id))
-            .filter(chat_logs::created_at.ge(target_log.created_at))
-            .execute(&mut *self.0.conn())?;
-
-        Ok(target_log)
-    }
-
     pub fn delete_after_id(&self, id: Id) -> Result<usize> {
         let target_log = self.select_by_id(id)?;
         let chat_id = target_log.chat_id;
@@ -180,6 +266,83 @@ impl ChatLogRepo {
 
         Ok(records)
     }
+
+    /// Ids of the logs on the active branch of the chat, newest first.
+    pub fn select_active_path(&self, chat_id: Id) -> Result<Vec<Id>> {
+        let active_log_id = chats::table
+            .filter(chats::id.eq(chat_id))
+            .select(chats::active_log_id)
+            .first::<Option<Id>>(&mut *self.0.conn())?;
+
+        let parents = chat_logs::table
+            .filter(chat_logs::chat_id.eq(chat_id))
+            .select((chat_logs::id, chat_logs::parent_id))
+            .load::<(Id, Option<Id>)>(&mut *self.0.conn())?
+            .into_iter()
+            .collect::<HashMap<Id, Option<Id>>>();
+
+        let mut path = vec![];
+        let mut next = active_log_id;
+        while let Some(id) = next {
+            let Some(parent_id) = parents.get(&id) else {
+                break;
+            };
+            path.push(id);
+            next = *parent_id;
+        }
+
+        Ok(path)
+    }
+
+    /// Logs of `ids`, in the same order.
+    pub fn select_by_ids(&self, ids: &[Id]) -> Result<Vec<ChatLog>> {
+        let mut records = chat_logs::table
+            .filter(chat_logs::id.eq_any(ids))
+            .load::<ChatLog>(&mut *self.0.conn())?;
+        records.sort_by_key(|log| ids.iter().position(|id| *id == log.id));
+
+        Ok(records)
+    }
+
+    /// Logs following the same parent as the log `id`, itself included, oldest first.
+    pub fn select_branches(&self, id: Id) -> Result<Vec<ChatLog>> {
+        let target_log = self.select_by_id(id)?;
+
+        let mut query = chat_logs::table
+            .filter(chat_logs::chat_id.eq(target_log.chat_id))
+            .into_boxed();
+        query = match target_log.parent_id {
+            Some(parent_id) => query.filter(chat_logs::parent_id.eq(parent_id)),
+            None => query.filter(chat_logs::parent_id.is_null()),
+        };
+
+        // Logs created within the same second keep their order of insertion
+        let records = query
+            .order((chat_logs::created_at.asc(), sql::<BigInt>("rowid").asc()))
+            .load::<ChatLog>(&mut *self.0.conn())?;
+
+        Ok(records)
+    }
+
+    /// The newest log following the log `id`.
+    pub fn select_latest_child(&self, id: Id) -> Result<Option<ChatLog>> {
+        chat_logs::table
+            .filter(chat_logs::parent_id.eq(id))
+            .order((chat_logs::created_at.desc(), sql::<BigInt>("rowid").desc()))
+            .first::<ChatLog>(&mut *self.0.conn())
+            .optional()
+            .map_err(|e| e.into())
+    }
+
+    /// Move the logs following the log `id` to `parent_id`.
+    pub fn update_parent(&self, id: Id, parent_id: Option<Id>) -> Result<usize> {
+        let size = diesel::update(chat_logs::table)
+            .filter(chat_logs::parent_id.eq(id))
+            .set(chat_logs::parent_id.eq(parent_id))
+            .execute(&mut *self.0.conn())?;
+
+        Ok(size)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Default)]
@@ -187,3 +350,52 @@ impl ChatLogRepo {
 pub struct ChatLogQueryParams {
     pub chat_id: Option<Id>,
 }
+
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatLogSearchParams {
+    pub keyword: String,
+    pub chat_id: Option<Id>,
+}
+
+// Private use characters, replaced by `<mark>` once the text is escaped
+const HIGHLIGHT_START: char = '\u{E000}';
+const HIGHLIGHT_END: char = '\u{E001}';
+const SNIPPET_TOKENS: i32 = 16;
+
+#[derive(QueryableByName)]
+struct ChatLogHitRow {
+    #[diesel(embed)]
+    hit: ChatLogHit,
+    #[diesel(sql_type = BigInt)]
+    total: i64,
+}
+
+/// Escape the text as HTML, then mark the highlighted terms
+fn highlight_html(text: &str) -> String {
+    let mut html = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            HIGHLIGHT_START => html.push_str("<mark>"),
+            HIGHLIGHT_END => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+
+    html
+}
+
+/// Quotes each term of a keyword so that it never fails as FTS5 syntax,
+/// the terms are matched by prefix and all of them are required
+fn match_expression(keyword: &str) -> String {
+    keyword
+        .split_whitespace()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -1,6 +1,7 @@
 use crate::models::chat_model::{NewChatModel, PatchChatModel};
 use crate::result::Result;
 use crate::schema::chat_models;
+use crate::Error;
 use crate::{database::DbConn, models::chat_model::ChatModel, types::Id};
 use diesel::*;
 
@@ -25,10 +26,48 @@ impl ChatModelRepo {
             .map_err(|e| e.into())
     }
 
-    pub fn select_by_name(&self, name: &str) -> Result<ChatModel> {
-        chat_models::table
+    /// The model serving `name`.
+    ///
+    /// Models of different vendors or servers may share a name, `id` tells them
+    /// apart. Without it the name must be unique, or unique among the models of `vendor`.
+    pub fn resolve(&self, name: &str, id: Option<Id>, vendor: &str) -> Result<ChatModel> {
+        let mut chat_models = chat_models::table
             .filter(chat_models::name.eq(name))
-            .first::<ChatModel>(&mut *self.0.conn())
+            .load::<ChatModel>(&mut *self.0.conn())?;
+
+        let index = match id.and_then(|id| chat_models.iter().position(|m| m.id == id)) {
+            Some(index) => index,
+            None if chat_models.len() == 1 => 0,
+            None if chat_models.is_empty() => return Err(diesel::result::Error::NotFound.into()),
+            None => {
+                let mut indices =
+                    (0..chat_models.len()).filter(|index| chat_models[*index].vendor == vendor);
+                match (indices.next(), indices.next()) {
+                    (Some(index), None) => index,
+                    _ => {
+                        return Err(Error::Validation(format!(
+                            "several models are named {}, pick one of them",
+                            name
+                        )))
+                    }
+                }
+            }
+        };
+
+        Ok(chat_models.swap_remove(index))
+    }
+
+    pub fn select_by_vendor(&self, vendor: &str) -> Result<Vec<ChatModel>> {
+        chat_models::table
+            .filter(chat_models::vendor.eq(vendor))
+            .load::<ChatModel>(&mut *self.0.conn())
+            .map_err(|e| e.into())
+    }
+
+    pub fn select_by_server_id(&self, server_id: Id) -> Result<Vec<ChatModel>> {
+        chat_models::table
+            .filter(chat_models::server_id.eq(server_id))
+            .load::<ChatModel>(&mut *self.0.conn())
             .map_err(|e| e.into())
     }
 
@@ -67,3 +106,54 @@ impl ChatModelRepo {
         Ok(size)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        models::chat_model::{ChatModelCapabilities, NewChatModel},
+        test::establish_connection,
+        types::Id,
+        Error,
+    };
+
+    use super::ChatModelRepo;
+
+    fn new_chat_model(name: &str, vendor: &str) -> NewChatModel {
+        NewChatModel {
+            id: Id::random(),
+            name: name.to_string(),
+            description: "".to_string(),
+            price: 0.0,
+            unit: "USD".to_string(),
+            vendor: vendor.to_string(),
+            server_id: None,
+            capabilities: ChatModelCapabilities::default().into(),
+            served_model: None,
+        }
+    }
+
+    #[test]
+    fn test_resolve_shared_name() {
+        let repo = ChatModelRepo::new(establish_connection());
+
+        // An Azure deployment named after the OpenAI model it serves
+        let openai = new_chat_model("gpt-shared", "openai");
+        let azure = new_chat_model("gpt-shared", "azure");
+        repo.insert(&openai).unwrap();
+        repo.insert(&azure).unwrap();
+
+        let resolve = |id, vendor| repo.resolve("gpt-shared", id, vendor);
+        assert_eq!(resolve(Some(azure.id), "openai").unwrap().id, azure.id);
+        assert_eq!(resolve(Some(openai.id), "azure").unwrap().id, openai.id);
+        assert_eq!(resolve(None, "openai").unwrap().id, openai.id);
+        assert_eq!(resolve(None, "azure").unwrap().id, azure.id);
+        assert!(matches!(resolve(None, "gemini"), Err(Error::Validation(_))));
+        // A stale id falls back to the name
+        assert_eq!(resolve(Some(Id::random()), "azure").unwrap().id, azure.id);
+
+        repo.delete(openai.id).unwrap();
+        assert_eq!(resolve(None, "gemini").unwrap().id, azure.id);
+        repo.delete(azure.id).unwrap();
+        assert!(resolve(None, "azure").is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use crate::models::chat_summary::{ChatSummary, NewChatSummary, NewChatSummaryLog};
+use crate::result::Result;
+use crate::schema::{chat_summaries, chat_summary_logs};
+use crate::{database::DbConn, types::Id};
+use diesel::prelude::*;
+
+#[derive(Clone)]
+pub struct ChatSummaryRepo(DbConn);
+
+impl ChatSummaryRepo {
+    pub fn new(conn: DbConn) -> Self {
+        Self(conn)
+    }
+
+    /// The newest summary of the chat ending on `path`, the branch being followed.
+    ///
+    /// Stale summaries are deleted along with their logs.
+    pub fn select_latest(&self, chat_id: Id, path: &[Id]) -> Result<Option<ChatSummary>> {
+        chat_summaries::table
+            .filter(chat_summaries::chat_id.eq(chat_id))
+            .filter(chat_summaries::last_log_id.eq_any(path))
+            .order(chat_summaries::created_at.desc())
+            .first::<ChatSummary>(&mut *self.0.conn())
+            .optional()
+            .map_err(|e| e.into())
+    }
+
+    pub fn select_by_chat_id(&self, chat_id: Id) -> Result<Vec<ChatSummary>> {
+        chat_summaries::table
+            .filter(chat_summaries::chat_id.eq(chat_id))
+            .order(chat_summaries::created_at.asc())
+            .load::<ChatSummary>(&mut *self.0.conn())
+            .map_err(|e| e.into())
+    }
+
+    /// Save the summary of `log_ids`, the logs of the branch it stands for.
+    pub fn insert(&self, summary: &NewChatSummary, log_ids: &[Id]) -> Result<usize> {
+        let size = diesel::insert_into(chat_summaries::table)
+            .values(summary)
+            .execute(&mut *self.0.conn())?;
+        let summary_logs = log_ids
+            .iter()
+            .map(|log_id| NewChatSummaryLog {
+                summary_id: summary.id,
+                log_id: *log_id,
+            })
+            .collect::<Vec<_>>();
+        diesel::insert_into(chat_summary_logs::table)
+            .values(&summary_logs)
+            .execute(&mut *self.0.conn())?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        models::chat_log::{NewChatLog, PatchChatLog, Role},
+        models::chat_summary::NewChatSummary,
+        repositories::chat_log::ChatLogRepo,
+        test::establish_connection,
+        types::Id,
+    };
+
+    use super::ChatSummaryRepo;
+
+    fn new_chat_log(chat_id: Id, role: Role, message: &str) -> NewChatLog {
+        NewChatLog {
+            id: Id::random(),
+            chat_id,
+            role: role.into(),
+            message: message.to_string(),
+            model: "gpt-4o".to_string(),
+            tokens: 0,
+            cost: 0.0,
+            finished: true,
+            tool_calls: vec![].into(),
+            tool_call_id: None,
+            finish_reason: None,
+            prompt_tokens: None,
+            time_to_first_token: None,
+            context_log_ids: vec![].into(),
+            structured: None,
+            reasoning: None,
+            parent_id: None,
+            latency: None,
+        }
+    }
+
+    fn new_summary(chat_id: Id, first_log_id: Id, last_log_id: Id) -> NewChatSummary {
+        NewChatSummary {
+            id: Id::random(),
+            chat_id,
+            content: "The user said hello.".to_string(),
+            first_log_id,
+            last_log_id,
+            model: "gpt-4o".to_string(),
+            tokens: 5,
+            cost: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_stale_summaries() {
+        let conn = establish_connection();
+        let repo = ChatSummaryRepo::new(conn.clone());
+        let chat_log_repo = ChatLogRepo::new(conn);
+
+        let chat_id = Id::random();
+        let question = new_chat_log(chat_id, Role::User, "Hello");
+        let reply = NewChatLog {
+            parent_id: Some(question.id),
+            ..new_chat_log(chat_id, Role::Assistant, "Hi!")
+        };
+        let other_reply = NewChatLog {
+            parent_id: Some(question.id),
+            ..new_chat_log(chat_id, Role::Assistant, "Hey!")
+        };
+        chat_log_repo.insert(&question).unwrap();
+        chat_log_repo.insert(&reply).unwrap();
+        chat_log_repo.insert(&other_reply).unwrap();
+
+        let summary = new_summary(chat_id, question.id, reply.id);
+        let path = [reply.id, question.id];
+        repo.insert(&summary, &path).unwrap();
+        assert_eq!(
+            repo.select_latest(chat_id, &path).unwrap().unwrap().id,
+            summary.id
+        );
+        // Summaries of other branches are not followed
+        assert!(repo
+            .select_latest(chat_id, &[question.id])
+            .unwrap()
+            .is_none());
+
+        // Logs of other branches are not covered
+        chat_log_repo.delete_by_id(other_reply.id).unwrap();
+        assert_eq!(repo.select_by_chat_id(chat_id).unwrap().len(), 1);
+
+        // Editing a covered log
+        chat_log_repo
+            .update(&PatchChatLog {
+                id: question.id,
+                message: Some("Hello there".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(repo.select_latest(chat_id, &path).unwrap().is_none());
+
+        // Marking a log finished leaves the summary valid
+        repo.insert(&new_summary(chat_id, question.id, reply.id), &path)
+            .unwrap();
+        chat_log_repo
+            .update(&PatchChatLog {
+                id: reply.id,
+                finished: Some(true),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(repo.select_by_chat_id(chat_id).unwrap().len(), 1);
+
+        // Deleting a covered log
+        chat_log_repo.delete_by_id(reply.id).unwrap();
+        assert!(repo.select_by_chat_id(chat_id).unwrap().is_empty());
+
+        chat_log_repo.delete_by_chat_id(chat_id).unwrap();
+    }
+}
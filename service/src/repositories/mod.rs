@@ -1,6 +1,8 @@
+pub mod attachment;
 pub mod chat;
 pub mod chat_log;
 pub mod chat_model;
+pub mod chat_summary;
 pub mod plugin;
 pub mod prompt;
 pub mod prompt_source;
@@ -1,5 +1,18 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    attachments (id) {
+        id -> Binary,
+        chat_log_id -> Binary,
+        name -> Text,
+        mime_type -> Text,
+        data -> Binary,
+        width -> Integer,
+        height -> Integer,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     chat_logs (id) {
         id -> Binary,
@@ -12,6 +25,16 @@ diesel::table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         finished -> Bool,
+        tool_calls -> Text,
+        tool_call_id -> Nullable<Text>,
+        finish_reason -> Nullable<Text>,
+        prompt_tokens -> Nullable<Integer>,
+        time_to_first_token -> Nullable<Integer>,
+        context_log_ids -> Text,
+        structured -> Nullable<Text>,
+        reasoning -> Nullable<Text>,
+        parent_id -> Nullable<Binary>,
+        latency -> Nullable<Integer>,
     }
 }
 
@@ -25,6 +48,31 @@ diesel::table! {
         vendor -> Text,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        server_id -> Nullable<Binary>,
+        unlisted -> Bool,
+        capabilities -> Text,
+        served_model -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    chat_summaries (id) {
+        id -> Binary,
+        chat_id -> Binary,
+        content -> Text,
+        first_log_id -> Binary,
+        last_log_id -> Binary,
+        model -> Text,
+        tokens -> Integer,
+        cost -> Float,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    chat_summary_logs (summary_id, log_id) {
+        summary_id -> Binary,
+        log_id -> Binary,
     }
 }
 
@@ -43,6 +91,7 @@ diesel::table! {
         stick -> Bool,
         archive -> Bool,
         archived_at -> Nullable<Timestamp>,
+        active_log_id -> Nullable<Binary>,
     }
 }
 
@@ -77,10 +126,11 @@ diesel::table! {
     prompts (id) {
         id -> Binary,
         name -> Text,
-        content -> Text,
         user_id -> Binary,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        messages -> Text,
+        variables -> Text,
     }
 }
 
@@ -99,6 +149,14 @@ diesel::table! {
         hide_taskbar -> Bool,
         home_page -> Text,
         scale -> Integer,
+        anthropic_api_key -> Nullable<Text>,
+        anthropic_url -> Nullable<Text>,
+        local_servers -> Text,
+        azure -> Nullable<Text>,
+        gemini_api_key -> Nullable<Text>,
+        gemini_url -> Nullable<Text>,
+        retry_policy -> Text,
+        auto_title -> Bool,
     }
 }
 
@@ -114,8 +172,11 @@ diesel::table! {
 }
 
 diesel::allow_tables_to_appear_in_same_query!(
+    attachments,
     chat_logs,
     chat_models,
+    chat_summaries,
+    chat_summary_logs,
     chats,
     plugins,
     prompt_sources,
@@ -1,23 +1,78 @@
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
 use chrono::Utc;
 use futures::StreamExt;
 use tokio::sync::mpsc::Sender;
 use tokio::sync::oneshot::Receiver;
 use tokio::task::JoinHandle;
 
-use crate::api::openai::chat::params::{OpenAIChatMessage, OpenAIChatParams, OpenAIChatRole};
+use crate::api::backend::{BackendRegistry, ChatBackend, ChatImage, ChatMessage, ChatRequest};
+use crate::api::tokenizer::{context_size, Tokenizer};
 use crate::database::pagination::PaginatedRecords;
+use crate::error::ApiErrorKind;
+use crate::models::attachment::NewAttachment;
 use crate::models::chat::{Chat, NewChat, PatchChat};
-use crate::models::chat_log::{ChatLog, NewChatLog, PatchChatLog, Role};
-use crate::models::chat_model::{ChatModel, NewChatModel, PatchChatModel};
+use crate::models::chat_log::{ChatLog, ChatLogHit, NewChatLog, PatchChatLog, Role, ToolCall};
+use crate::models::chat_model::{ChatModel, ChatModelCapabilities, NewChatModel, PatchChatModel};
+use crate::models::chat_summary::{ChatSummary, NewChatSummary};
+use crate::repositories::attachment::AttachmentRepo;
 use crate::repositories::chat::ChatRepo;
-use crate::repositories::chat_log::{ChatLogQueryParams, ChatLogRepo};
+use crate::repositories::chat_log::{ChatLogQueryParams, ChatLogRepo, ChatLogSearchParams};
 use crate::repositories::chat_model::ChatModelRepo;
+use crate::repositories::chat_summary::ChatSummaryRepo;
 use crate::repositories::prompt::PromptRepo;
 use crate::repositories::setting::SettingRepo;
 use crate::result::Result;
-use crate::types::{PageQueryParams, StreamContent};
-use crate::{database::DbConn, models::chat::ChatConfig, types::Id};
-use crate::{CursorDirection, CursorQueryResult};
+use crate::services::context::{ContextBuilder, COMPLETION_TOKENS};
+use crate::types::{PageQueryParams, StreamContent, StreamMeta};
+use crate::{
+    database::DbConn,
+    models::chat::{ChatConfig, ChatParams, ChatResponseFormat},
+    types::Id,
+};
+use crate::{CursorDirection, CursorQueryResult, Error};
+
+/// Logs loaded at once while walking back the history.
+const PAGE_SIZE: usize = 20;
+
+/// Title of a chat created without one.
+pub const DEFAULT_CHAT_TITLE: &str = "New Chat";
+
+/// Characters of each message sent to title a chat.
+const TITLE_EXCERPT: usize = 1000;
+
+const TITLE_PROMPT: &str =
+    "Write a title of at most six words for the conversation below, in its language. \
+Reply with the title only, without quotes or punctuation at the end.";
+
+const SUMMARIZE_PROMPT: &str =
+    "Summarize the conversation below, starting with the summary of its earlier part if any. \
+Keep the facts, decisions, names, figures and open questions, drop the pleasantries. \
+Reply with the summary only.";
+
+/// A chat being summarized, until dropped.
+struct Summarizing(Id);
+
+impl Summarizing {
+    fn chats() -> &'static Mutex<HashSet<Id>> {
+        static CHATS: OnceLock<Mutex<HashSet<Id>>> = OnceLock::new();
+        CHATS.get_or_init(Default::default)
+    }
+
+    /// `None` when the chat is being summarized already.
+    fn start(chat_id: Id) -> Option<Self> {
+        let started = Self::chats().lock().unwrap().insert(chat_id);
+        started.then(|| Self(chat_id))
+    }
+}
+
+impl Drop for Summarizing {
+    fn drop(&mut self) {
+        Self::chats().lock().unwrap().remove(&self.0);
+    }
+}
 
 #[derive(Clone)]
 pub struct ChatService {
@@ -25,9 +80,12 @@ pub struct ChatService {
     conn: DbConn,
     chat_repo: ChatRepo,
     chat_log_repo: ChatLogRepo,
+    attachment_repo: AttachmentRepo,
     prompt_repo: PromptRepo,
     setting_repo: SettingRepo,
     chat_model_repo: ChatModelRepo,
+    chat_summary_repo: ChatSummaryRepo,
+    backend_registry: BackendRegistry,
 }
 
 impl From<DbConn> for ChatService {
@@ -41,9 +99,12 @@ impl ChatService {
         Self {
             chat_repo: ChatRepo::new(conn.clone()),
             chat_log_repo: ChatLogRepo::new(conn.clone()),
+            attachment_repo: AttachmentRepo::new(conn.clone()),
             chat_model_repo: ChatModelRepo::new(conn.clone()),
+            chat_summary_repo: ChatSummaryRepo::new(conn.clone()),
             prompt_repo: PromptRepo::new(conn.clone()),
             setting_repo: SettingRepo::new(conn.clone()),
+            backend_registry: BackendRegistry::default(),
             conn,
         }
     }
@@ -51,6 +112,12 @@ impl ChatService {
     pub fn create_chat(&self, payload: CreateChatPayload) -> Result<Id> {
         let chat_id = Id::random();
 
+        // A half-filled prompt is never sent
+        if let Some(prompt_id) = payload.prompt_id {
+            let prompt = self.prompt_repo.select_by_id(prompt_id)?;
+            prompt.render(&payload.config.variables)?;
+        }
+
         let non_stick_min_order = self.chat_repo.select_non_stick_min_order(payload.user_id)?;
 
         let new_chat = NewChat {
@@ -90,14 +157,32 @@ impl ChatService {
     pub fn search_chat_logs(
         &self,
         payload: SearchChatLogPayload,
-    ) -> Result<PaginatedRecords<ChatLog>> {
-        self.chat_log_repo.select(PageQueryParams {
+    ) -> Result<PaginatedRecords<ChatLogHit>> {
+        let mut params = PageQueryParams {
             user_id: payload.user_id,
-            query: ChatLogQueryParams {
+            query: ChatLogSearchParams {
+                keyword: payload.keyword,
                 chat_id: payload.chat_id,
             },
             ..Default::default()
-        })
+        };
+        if let Some(page) = payload.page {
+            params.page = page;
+        }
+        if let Some(per_page) = payload.per_page {
+            params.per_page = per_page;
+        }
+
+        if params.query.keyword.trim().is_empty() {
+            return Ok(PaginatedRecords {
+                records: vec![],
+                total: 0,
+                total_pages: 0,
+                per_page: params.per_page,
+            });
+        }
+
+        self.chat_log_repo.search(params)
     }
 
     pub fn get_chat_logs_by_cursor(
@@ -233,28 +318,109 @@ impl ChatService {
         Ok(())
     }
 
-    pub fn update_chat_log(&self, payload: UpdateChatLogPayload) -> Result<()> {
-        let patch_chat_log = PatchChatLog {
-            id: payload.id,
-            message: Some(payload.content),
-            ..Default::default()
+    /// Edit a log into a new branch beside it, the log and the ones following it are kept.
+    pub fn update_chat_log(&self, payload: UpdateChatLogPayload) -> Result<Id> {
+        let chat_log = self.chat_log_repo.select_by_id(payload.id)?;
+        let images = self
+            .attachment_repo
+            .select_by_chat_log_id(payload.id)?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        let role = chat_log.role.0;
+        let message = ChatMessage {
+            role: role.clone(),
+            content: payload.content,
+            tool_calls: chat_log.tool_calls.0,
+            tool_call_id: chat_log.tool_call_id,
+            images,
+        };
+        let tokens = message.tokens(&chat_log.model);
+
+        // The copy keeps the message, what was measured while replying
+        // belongs to the original only
+        let id = Id::random();
+        self.chat_log_repo.insert(&NewChatLog {
+            id,
+            chat_id: chat_log.chat_id,
+            // An edited question is not answered yet
+            finished: role != Role::User,
+            role: role.into(),
+            message: message.content,
+            model: chat_log.model,
+            tokens: tokens as i32,
+            cost: 0.0,
+            tool_calls: message.tool_calls.into(),
+            tool_call_id: message.tool_call_id,
+            finish_reason: None,
+            prompt_tokens: None,
+            time_to_first_token: None,
+            context_log_ids: vec![].into(),
+            structured: None,
+            reasoning: None,
+            parent_id: chat_log.parent_id,
+            latency: None,
+        })?;
+        for image in message.images {
+            self.attachment_repo.insert(&NewAttachment {
+                id: Id::random(),
+                chat_log_id: id,
+                name: image.name,
+                mime_type: image.mime_type,
+                data: image.data,
+                width: image.width as i32,
+                height: image.height as i32,
+            })?;
+        }
+        self.chat_repo
+            .update_active_log(chat_log.chat_id, Some(id))?;
+
+        Ok(id)
+    }
+
+    /// Delete a log, the logs following it follow its parent instead.
+    pub fn delete_chat_log(&self, id: Id) -> Result<()> {
+        let Some(chat_log) = self.chat_log_repo.select_by_ids(&[id])?.pop() else {
+            return Ok(());
         };
+        let chat = self.chat_repo.select_by_id(chat_log.chat_id)?;
 
-        self.chat_log_repo.update(&patch_chat_log)?;
+        self.chat_log_repo.update_parent(id, chat_log.parent_id)?;
+        if chat.active_log_id == Some(id) {
+            self.chat_repo
+                .update_active_log(chat.id, chat_log.parent_id)?;
+        }
+        self.chat_log_repo.delete_by_id(id)?;
 
         Ok(())
     }
 
-    pub fn delete_chat_log_since_id(&self, id: Id) -> Result<ChatLog> {
-        let chat_log = self.chat_log_repo.delete_since_id(id)?;
+    /// Logs branching at the same point as the log `id`, oldest first.
+    pub fn get_chat_log_branches(&self, id: Id) -> Result<Vec<ChatLog>> {
+        self.chat_log_repo.select_branches(id)
+    }
+
+    /// Follow the branch through the log `id`, down to its newest logs.
+    pub fn switch_chat_branch(&self, id: Id) -> Result<Id> {
+        let mut chat_log = self.chat_log_repo.select_by_id(id)?;
+        let chat_id = chat_log.chat_id;
+        while let Some(child) = self.chat_log_repo.select_latest_child(chat_log.id)? {
+            chat_log = child;
+        }
+        self.chat_repo
+            .update_active_log(chat_id, Some(chat_log.id))?;
 
-        Ok(chat_log)
+        Ok(chat_log.id)
     }
 
-    pub fn delete_chat_log(&self, id: Id) -> Result<()> {
-        self.chat_log_repo.delete_by_id(id)?;
+    /// Pick one of the replies to the same message, only the picked one is kept in the context.
+    pub fn pick_chat_reply(&self, id: Id) -> Result<Id> {
+        let chat_log = self.chat_log_repo.select_by_id(id)?;
+        if chat_log.role.0 != Role::Assistant {
+            return Err(Error::Validation(format!("log {} is not a reply", id)));
+        }
 
-        Ok(())
+        self.switch_chat_branch(id)
     }
 
     pub async fn resend_message(
@@ -264,13 +430,33 @@ impl ChatService {
         stop_receiver: Receiver<()>,
     ) -> Result<(Id, Id, JoinHandle<()>)> {
         let message_id = payload.id;
-        let chat_log = self.delete_chat_log_since_id(message_id)?;
 
-        self.send_message(
-            SendMessagePayload {
-                chat_id: chat_log.chat_id,
-                message: chat_log.message,
-            },
+        let images = self
+            .attachment_repo
+            .select_by_chat_log_id(message_id)?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        let chat_log = self.chat_log_repo.select_by_id(message_id)?;
+        let chat_id = chat_log.chat_id;
+
+        // An edited message that was never replied to is answered in place,
+        // any other message is sent again as a new branch beside the original one
+        let unanswered = chat_log.role.0 == Role::User
+            && !chat_log.finished
+            && self
+                .chat_log_repo
+                .select_latest_child(message_id)?
+                .is_none();
+        self.chat_repo
+            .update_active_log(chat_id, chat_log.parent_id)?;
+
+        let mut user_message = ChatMessage::new(Role::User, chat_log.message);
+        user_message.images = images;
+        self.send_user_message(
+            chat_id,
+            user_message,
+            unanswered.then_some(message_id),
             sender,
             stop_receiver,
         )
@@ -281,86 +467,297 @@ impl ChatService {
         &self,
         payload: SendMessagePayload,
         sender: Sender<StreamContent>,
-        mut stop_receiver: Receiver<()>,
+        stop_receiver: Receiver<()>,
     ) -> Result<(Id, Id, JoinHandle<()>)> {
-        let SendMessagePayload { chat_id, message } = payload;
+        let SendMessagePayload {
+            chat_id,
+            message,
+            images,
+        } = payload;
 
-        let Chat {
-            user_id,
-            prompt_id,
-            config,
-            ..
-        } = self.chat_repo.select_by_id(chat_id)?;
+        let mut user_message = ChatMessage::new(Role::User, message);
+        user_message.images = images;
+        self.send_user_message(chat_id, user_message, None, sender, stop_receiver)
+            .await
+    }
+
+    /// Reply to `user_message` after the active log, `user_log_id` is its log when it's saved already.
+    async fn send_user_message(
+        &self,
+        chat_id: Id,
+        user_message: ChatMessage,
+        user_log_id: Option<Id>,
+        sender: Sender<StreamContent>,
+        stop_receiver: Receiver<()>,
+    ) -> Result<(Id, Id, JoinHandle<()>)> {
+        let chat = self.chat_repo.select_by_id(chat_id)?;
+        let config = &chat.config.0;
+        let chat_model = self.chat_model_repo.resolve(
+            &config.params.model,
+            config.params.model_id,
+            &chat.vendor,
+        )?;
+
+        let path = self.chat_log_repo.select_active_path(chat_id)?;
+        let reply = self.prepare_reply(&chat, &path, &user_message, chat_model)?;
+
+        // Condense the logs left out for the next messages
+        if let (true, Some(log_id)) = (config.summarize, reply.omitted) {
+            let chat_service = self.clone();
+            tokio::spawn(async move {
+                if let Err(err) = chat_service.summarize_chat(chat_id, log_id).await {
+                    log::warn!("summarize chat {:?} failed: {}", chat_id, err);
+                }
+            });
+        }
+
+        let user_log_id = match user_log_id {
+            Some(id) => {
+                // Priced by the model replying now
+                let user_token = user_message.tokens(reply.chat_model.served_model());
+                self.chat_log_repo.update(&PatchChatLog {
+                    id,
+                    model: Some(reply.chat_model.name.clone()),
+                    tokens: Some(user_token as i32),
+                    cost: Some(reply.chat_model.calc_cost(user_token)),
+                    ..Default::default()
+                })?;
+                id
+            }
+            None => self.add_user_log(&chat, &[&reply.chat_model], &user_message)?,
+        };
+        self.chat_repo
+            .update_active_log(chat_id, Some(user_log_id))?;
+
+        let (reply_log_id, handle) =
+            self.spawn_reply(chat_id, user_log_id, reply, true, sender, stop_receiver);
+
+        Ok((user_log_id, reply_log_id, handle))
+    }
+
+    /// Send the message to several models at once, each reply streaming to its own channel.
+    ///
+    /// The message and the replies branch off the active log without following it,
+    /// until a reply is picked with [`ChatService::pick_chat_reply`].
+    pub async fn arena_message(
+        &self,
+        payload: ArenaMessagePayload,
+        channels: Vec<(Sender<StreamContent>, Receiver<()>)>,
+    ) -> Result<(Id, Vec<ArenaReply>)> {
+        let ArenaMessagePayload {
+            chat_id,
+            message,
+            images,
+            models,
+        } = payload;
+        if models.is_empty() {
+            return Err(Error::Validation(
+                "arena needs at least one model".to_string(),
+            ));
+        }
+
+        let chat = self.chat_repo.select_by_id(chat_id)?;
+        let path = self.chat_log_repo.select_active_path(chat_id)?;
+        let mut user_message = ChatMessage::new(Role::User, message);
+        user_message.images = images;
+
+        // Every model is checked before anything is saved
+        let replies = models
+            .into_iter()
+            .map(|id| {
+                let chat_model = self.chat_model_repo.select_by_id(id)?;
+                self.prepare_reply(&chat, &path, &user_message, chat_model)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let chat_models = replies
+            .iter()
+            .map(|reply| &reply.chat_model)
+            .collect::<Vec<_>>();
+        let user_log_id = self.add_user_log(&chat, &chat_models, &user_message)?;
+
+        let replies = replies
+            .into_iter()
+            .zip(channels)
+            .map(|(reply, (sender, stop_receiver))| {
+                let model = reply.chat_model.name.clone();
+                let (reply_id, handle) =
+                    self.spawn_reply(chat_id, user_log_id, reply, false, sender, stop_receiver);
+                ArenaReply {
+                    model,
+                    reply_id,
+                    handle,
+                }
+            })
+            .collect();
+
+        Ok((user_log_id, replies))
+    }
 
-        let config = config.0;
-        let params = config.params;
+    /// Build the request of `chat_model` replying to `user_message` after the logs of `path`.
+    fn prepare_reply(
+        &self,
+        chat: &Chat,
+        path: &[Id],
+        user_message: &ChatMessage,
+        chat_model: ChatModel,
+    ) -> Result<PreparedReply> {
+        let config = &chat.config.0;
+        let model = chat_model.name.as_str();
+        let params = ChatParams {
+            model: model.to_string(),
+            model_id: Some(chat_model.id),
+            ..config.params.clone()
+        };
+        params.validate(&chat_model.vendor)?;
         let backtrack = config.backtrack;
-        let model = params.model;
+        let summarize = config.summarize;
 
-        let chat_model = self.chat_model_repo.select_by_name(&model)?;
+        let params = chat_model.capabilities.0.adapt(params);
 
-        let mut messages: Vec<OpenAIChatMessage> = vec![];
+        let mut messages: Vec<ChatMessage> = vec![];
 
-        // Add prompt to messages
-        if let Some(prompt_id) = prompt_id {
+        // Add the messages of the prompt, in order and with their roles
+        if let Some(prompt_id) = chat.prompt_id {
             let prompt = self.prompt_repo.select_by_id(prompt_id)?;
-            messages.push(OpenAIChatMessage {
-                role: OpenAIChatRole::User,
-                content: prompt.content,
-            })
+            for message in prompt.render(&config.variables)? {
+                messages.push(ChatMessage::new(message.role.into(), message.content))
+            }
         }
 
-        // Add previous logs to messages
-        let logs = self
-            .chat_log_repo
-            .select_last_n(backtrack as i64, payload.chat_id)?;
-        for log in logs {
-            messages.push(OpenAIChatMessage {
-                role: log.role.0.into(),
-                content: log.message,
-            })
+        // The summary stands for the logs it covers
+        let summary = match summarize {
+            true => self.chat_summary_repo.select_latest(chat.id, path)?,
+            false => None,
+        };
+        if let Some(summary) = &summary {
+            messages.push(ChatMessage::new(
+                Role::System,
+                format!("Summary of the earlier conversation:\n{}", summary.content),
+            ))
+        }
+
+        // Fill the rest of the context window with previous logs
+        let completion_tokens = params
+            .max_tokens
+            .or(params.max_completion_tokens)
+            .map_or(COMPLETION_TOKENS, |max_tokens| max_tokens as usize);
+        let served_model = chat_model.served_model();
+        let budget = context_size(served_model).saturating_sub(completion_tokens);
+        let mut builder = ContextBuilder::new(served_model, budget, backtrack);
+        for message in &messages {
+            builder.keep(message);
         }
+        builder.keep(user_message);
+        let until = summary.map(|summary| summary.last_log_id);
+        self.fill_context(&mut builder, path, until)?;
+        let context = builder.build();
+        messages.extend(context.history);
 
         // Add user message to messages
-        let user_message = OpenAIChatMessage {
-            role: OpenAIChatRole::User,
-            content: message.clone(),
-        };
-        let user_token = user_message.tokens();
-        messages.push(user_message);
+        messages.push(user_message.clone());
+
+        // Create chat backend of the model's vendor
+        let setting = self.setting_repo.select_by_user_id(chat.user_id)?;
+        let backend = self
+            .backend_registry
+            .create(&chat_model, &chat.vendor, &setting)?;
+
+        Ok(PreparedReply {
+            response_format: params.response_format.clone(),
+            request: ChatRequest { messages, params },
+            backend,
+            chat_model,
+            prompt_tokens: context.tokens,
+            context_log_ids: context.log_ids,
+            omitted: context.omitted,
+        })
+    }
+
+    /// Add the log of the message, following the active log.
+    ///
+    /// The message is paid once to each of `chat_models` it is sent to.
+    fn add_user_log(
+        &self,
+        chat: &Chat,
+        chat_models: &[&ChatModel],
+        user_message: &ChatMessage,
+    ) -> Result<Id> {
+        let user_token = user_message.tokens(chat_models[0].served_model());
+        let cost = chat_models
+            .iter()
+            .map(|chat_model| chat_model.calc_cost(user_message.tokens(chat_model.served_model())))
+            .sum();
 
-        // Add user log to database
         let user_log_id = Id::random();
         let user_log = NewChatLog {
             id: user_log_id,
-            chat_id,
+            chat_id: chat.id,
             role: Role::User.into(),
-            message,
-            model: model.clone(),
+            message: user_message.content.clone(),
+            model: chat_models[0].name.clone(),
             tokens: user_token as i32,
-            cost: chat_model.calc_cost(user_token),
+            cost,
             finished: false,
+            tool_calls: vec![].into(),
+            tool_call_id: None,
+            finish_reason: None,
+            prompt_tokens: None,
+            time_to_first_token: None,
+            context_log_ids: vec![].into(),
+            structured: None,
+            reasoning: None,
+            parent_id: chat.active_log_id,
+            latency: None,
         };
         self.chat_log_repo.insert(&user_log)?;
+        for image in &user_message.images {
+            self.attachment_repo.insert(&NewAttachment {
+                id: Id::random(),
+                chat_log_id: user_log_id,
+                name: image.name.clone(),
+                mime_type: image.mime_type.clone(),
+                data: image.data.clone(),
+                width: image.width as i32,
+                height: image.height as i32,
+            })?;
+        }
 
-        // Create OpenAI API
-        let setting = self.setting_repo.select_by_user_id(user_id)?;
-        let api = setting.create_openai_chat();
-        let api_params = OpenAIChatParams {
-            stream: true,
-            model: model.clone(),
-            messages,
-            frequency_penalty: params.frequency_penalty,
-            presence_penalty: params.presence_penalty,
-            temperature: params.temperature,
-            ..Default::default()
-        };
-        let total_tokens = api_params.calc_tokens();
-        let question_cost = chat_model.calc_cost(total_tokens);
+        Ok(user_log_id)
+    }
+
+    /// Stream the reply to `sender` and save it once done or stopped,
+    /// `activate` makes the reply the active log of the chat.
+    fn spawn_reply(
+        &self,
+        chat_id: Id,
+        user_log_id: Id,
+        reply: PreparedReply,
+        activate: bool,
+        sender: Sender<StreamContent>,
+        mut stop_receiver: Receiver<()>,
+    ) -> (Id, JoinHandle<()>) {
+        let PreparedReply {
+            request,
+            backend,
+            chat_model,
+            response_format,
+            prompt_tokens,
+            context_log_ids,
+            ..
+        } = reply;
+        let model = request.params.model.clone();
+        let served_model = chat_model.served_model().to_string();
+        let question_cost = chat_model.calc_cost(prompt_tokens);
 
         let chat_repo = self.chat_repo.clone();
         let chat_log_repo = self.chat_log_repo.clone();
         let mut reply = Some(String::new());
+        let mut reasoning = String::new();
+        let mut tool_calls: Vec<ToolCall> = vec![];
+        // Additional replies, by choice index minus 1
+        let mut alternatives: Vec<String> = vec![];
+        let mut meta = StreamMeta::default();
         let reply_log_id = Id::random();
 
         let send = |sender: Sender<StreamContent>, content: StreamContent| async move {
@@ -368,24 +765,107 @@ impl ChatService {
         };
 
         let handle = tokio::spawn(async move {
-            let save_reply = |reply_message: &str, finished: bool| {
-                let reply_tokens =
-                    OpenAIChatMessage::calc_tokens(&OpenAIChatRole::Assistant, reply_message);
-                let reply_cost = chat_model.calc_cost(reply_tokens);
-                let total_cost = question_cost + reply_cost;
+            let start = Instant::now();
+            let save_reply = |reply_message: &str,
+                              reasoning: &str,
+                              tool_calls: &[ToolCall],
+                              alternatives: &[String],
+                              meta: &StreamMeta,
+                              structured: Option<serde_json::Value>,
+                              finished: bool| {
+                let mut reply = ChatMessage::new(Role::Assistant, reply_message);
+                reply.tool_calls = tool_calls.to_vec();
+                let estimated_tokens = reply.tokens(&served_model);
+                let estimated_alternative_tokens = alternatives
+                    .iter()
+                    .map(|alternative| {
+                        ChatMessage::calc_tokens(&served_model, &Role::Assistant, alternative)
+                    })
+                    .collect::<Vec<_>>();
+                let estimated_total =
+                    estimated_tokens + estimated_alternative_tokens.iter().sum::<usize>();
+
+                // Prefer the usage reported by the provider to the estimation,
+                // it covers all the replies at once and is shared by their estimated lengths
+                let completion_tokens = meta.completion_tokens.unwrap_or(estimated_total);
+                let alternative_tokens = estimated_alternative_tokens
+                    .iter()
+                    .map(|tokens| match estimated_total {
+                        0 => 0,
+                        total => completion_tokens * tokens / total,
+                    })
+                    .collect::<Vec<_>>();
+                let reply_tokens = completion_tokens - alternative_tokens.iter().sum::<usize>();
+                let question_cost = meta
+                    .prompt_tokens
+                    .map(|tokens| chat_model.calc_cost(tokens))
+                    .unwrap_or(question_cost);
+                // The question is paid along with the first reply
+                let reply_cost = question_cost + chat_model.calc_cost(reply_tokens);
+                let mut total_cost = reply_cost;
                 let reply_log = NewChatLog {
                     id: reply_log_id,
                     chat_id,
                     role: Role::Assistant.into(),
                     message: reply_message.to_string(),
-                    model: model.clone(),
+                    model: meta.model.clone().unwrap_or_else(|| model.clone()),
                     tokens: reply_tokens as i32,
-                    cost: total_cost,
+                    cost: reply_cost,
                     finished,
+                    tool_calls: tool_calls.to_vec().into(),
+                    tool_call_id: None,
+                    finish_reason: meta.finish_reason.clone().map(Into::into),
+                    prompt_tokens: Some(meta.prompt_tokens.unwrap_or(prompt_tokens) as i32),
+                    time_to_first_token: meta.time_to_first_token.map(|ms| ms as i32),
+                    context_log_ids: context_log_ids.clone().into(),
+                    structured: structured.map(Into::into),
+                    reasoning: (!reasoning.is_empty()).then(|| reasoning.to_string()),
+                    parent_id: Some(user_log_id),
+                    latency: Some(start.elapsed().as_millis() as i32),
                 };
 
                 // Add reply log to database
                 chat_log_repo.insert(&reply_log).unwrap();
+                if activate {
+                    chat_repo
+                        .update_active_log(chat_id, Some(reply_log_id))
+                        .unwrap();
+                }
+
+                // Additional replies branch beside the first one, until picked
+                for (index, (alternative, tokens)) in
+                    alternatives.iter().zip(alternative_tokens).enumerate()
+                {
+                    let cost = chat_model.calc_cost(tokens);
+                    total_cost += cost;
+                    chat_log_repo
+                        .insert(&NewChatLog {
+                            id: Id::random(),
+                            chat_id,
+                            role: Role::Assistant.into(),
+                            message: alternative.to_string(),
+                            model: reply_log.model.clone(),
+                            tokens: tokens as i32,
+                            cost,
+                            finished,
+                            tool_calls: vec![].into(),
+                            tool_call_id: None,
+                            finish_reason: meta
+                                .choice_finish_reasons
+                                .get(index)
+                                .cloned()
+                                .flatten()
+                                .map(Into::into),
+                            prompt_tokens: reply_log.prompt_tokens,
+                            time_to_first_token: reply_log.time_to_first_token,
+                            context_log_ids: context_log_ids.clone().into(),
+                            structured: None,
+                            reasoning: None,
+                            parent_id: Some(user_log_id),
+                            latency: reply_log.latency,
+                        })
+                        .unwrap();
+                }
 
                 // Update chat cost
                 chat_repo.add_cost_and_update(chat_id, total_cost).unwrap();
@@ -399,24 +879,83 @@ impl ChatService {
                     })
                     .unwrap();
             };
-            let stream = api.send_message(api_params).await;
+            let mut time_to_first_token = None;
+            let stream = backend.send_message(request).await;
             match stream {
                 Ok(mut stream) => {
-                    while let Some(content) = stream.next().await {
-                        match &content {
+                    while let Some(mut content) = stream.next().await {
+                        if time_to_first_token.is_none()
+                            && matches!(
+                                content,
+                                StreamContent::Data(_)
+                                    | StreamContent::Reasoning(_)
+                                    | StreamContent::ToolCall(_)
+                                    | StreamContent::Choice(_)
+                            )
+                        {
+                            time_to_first_token = Some(start.elapsed().as_millis() as u64);
+                        }
+
+                        match &mut content {
                             StreamContent::Data(data) => match &mut reply {
                                 Some(reply) => reply.push_str(data),
                                 None => unreachable!(),
                             },
+                            StreamContent::Reasoning(data) => reasoning.push_str(data),
+                            StreamContent::ToolCall(tool_call) => {
+                                tool_calls.push(tool_call.clone())
+                            }
+                            StreamContent::Choice(choice) => {
+                                if alternatives.len() < choice.index {
+                                    alternatives.resize(choice.index, String::new());
+                                }
+                                alternatives[choice.index - 1].push_str(&choice.data);
+                            }
+                            StreamContent::Meta(stream_meta) => {
+                                stream_meta.time_to_first_token = time_to_first_token;
+                                meta = stream_meta.clone();
+                            }
                             StreamContent::Done => {
-                                save_reply(reply.as_deref().unwrap_or_default(), true);
+                                let reply_message = reply.as_deref().unwrap_or_default();
+                                // A reply calling tools has no content to parse
+                                let structured = match &response_format {
+                                    Some(format) if tool_calls.is_empty() => {
+                                        format.parse(reply_message)
+                                    }
+                                    _ => Ok(None),
+                                };
+                                save_reply(
+                                    reply_message,
+                                    &reasoning,
+                                    &tool_calls,
+                                    &alternatives,
+                                    &meta,
+                                    structured.clone().unwrap_or_default(),
+                                    true,
+                                );
+                                // The reply is kept, but reported as a violation instead of done
+                                if let Err(err) = structured {
+                                    content = StreamContent::Error(err);
+                                }
                             }
                             _ => {}
                         }
                         send(sender.clone(), content).await;
 
                         if stop_receiver.try_recv().is_ok() {
-                            save_reply(reply.as_deref().unwrap_or_default(), false);
+                            let meta = StreamMeta {
+                                time_to_first_token,
+                                ..Default::default()
+                            };
+                            save_reply(
+                                reply.as_deref().unwrap_or_default(),
+                                &reasoning,
+                                &tool_calls,
+                                &alternatives,
+                                &meta,
+                                None,
+                                false,
+                            );
                             break;
                         }
                     }
@@ -426,7 +965,352 @@ impl ChatService {
             }
         });
 
-        Ok((user_log_id, reply_log_id, handle))
+        (reply_log_id, handle)
+    }
+
+    /// Push the logs of `path`, newest first, until the context is full
+    /// or the log `until` a summary covers is reached.
+    fn fill_context(
+        &self,
+        builder: &mut ContextBuilder,
+        path: &[Id],
+        until: Option<Id>,
+    ) -> Result<()> {
+        for ids in path.chunks(PAGE_SIZE) {
+            let logs = self.chat_log_repo.select_by_ids(ids)?;
+
+            let log_ids = logs.iter().map(|log| log.id).collect::<Vec<Id>>();
+            let mut attachments = self.attachment_repo.select_by_chat_log_ids(&log_ids)?;
+            for log in logs {
+                if Some(log.id) == until {
+                    return Ok(());
+                }
+
+                let (log_attachments, rest) = attachments
+                    .into_iter()
+                    .partition(|attachment| attachment.chat_log_id == log.id);
+                attachments = rest;
+
+                let message = ChatMessage {
+                    role: log.role.0,
+                    content: log.message,
+                    tool_calls: log.tool_calls.0,
+                    tool_call_id: log.tool_call_id,
+                    images: log_attachments
+                        .into_iter()
+                        .map(Into::into)
+                        .collect::<Vec<_>>(),
+                };
+                if !builder.push(log.id, message) {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Condense the logs not summarized yet, up to `until`, along with the latest summary
+    /// into a new one standing for all of them.
+    ///
+    /// Logs that do not fit in the context of the model are left to the next summary.
+    pub async fn summarize_chat(&self, chat_id: Id, until: Id) -> Result<()> {
+        // Messages sent meanwhile would summarize the same logs again
+        let Some(_summarizing) = Summarizing::start(chat_id) else {
+            return Ok(());
+        };
+
+        let Chat {
+            user_id,
+            config,
+            vendor,
+            ..
+        } = self.chat_repo.select_by_id(chat_id)?;
+        let chat_model = self.chat_model_repo.resolve(
+            &config.0.params.model,
+            config.0.params.model_id,
+            &vendor,
+        )?;
+        let model = chat_model.name.clone();
+        let path = self.chat_log_repo.select_active_path(chat_id)?;
+        let previous = self.chat_summary_repo.select_latest(chat_id, &path)?;
+        let after = previous.as_ref().map(|summary| summary.last_log_id);
+
+        // Newest first, from `until` to the last summarized log
+        let mut logs = vec![];
+        'load: for ids in path.chunks(PAGE_SIZE) {
+            for log in self.chat_log_repo.select_by_ids(ids)? {
+                if Some(log.id) == after {
+                    break 'load;
+                }
+                if log.id == until || !logs.is_empty() {
+                    logs.push(log);
+                }
+            }
+        }
+
+        let served_model = chat_model.served_model();
+        let tokenizer = Tokenizer::for_model(served_model);
+        let budget = context_size(served_model).saturating_sub(COMPLETION_TOKENS);
+        let mut tokens = ChatMessage::calc_tokens(served_model, &Role::System, SUMMARIZE_PROMPT);
+        let mut transcript = vec![];
+        if let Some(previous) = &previous {
+            let text = format!("summary: {}", previous.content);
+            tokens += tokenizer.count(&text);
+            transcript.push(text);
+        }
+        let mut range = None;
+        for log in logs.iter().rev() {
+            let text = format!("{}: {}", log.role.0.as_ref(), log.message);
+            tokens += tokenizer.count(&text);
+            if tokens > budget {
+                break;
+            }
+            transcript.push(text);
+            range = Some((range.map_or(log.id, |(first, _)| first), log.id));
+        }
+        let Some((first_log_id, last_log_id)) = range else {
+            return Ok(());
+        };
+        let first_log_id = previous.map_or(first_log_id, |summary| summary.first_log_id);
+
+        let setting = self.setting_repo.select_by_user_id(user_id)?;
+        let backend = self
+            .backend_registry
+            .create(&chat_model, &vendor, &setting)?;
+        let request = ChatRequest {
+            messages: vec![
+                ChatMessage::new(Role::System, SUMMARIZE_PROMPT),
+                ChatMessage::new(Role::User, transcript.join("\n\n")),
+            ],
+            params: ChatParams {
+                model: model.clone(),
+                model_id: Some(chat_model.id),
+                ..Default::default()
+            },
+        };
+
+        let mut content = String::new();
+        let mut meta = StreamMeta::default();
+        let mut stream = backend.send_message(request).await?;
+        while let Some(stream_content) = stream.next().await {
+            match stream_content {
+                StreamContent::Data(data) => content.push_str(&data),
+                StreamContent::Meta(stream_meta) => meta = stream_meta,
+                StreamContent::Error(err) => return Err(err.into()),
+                StreamContent::Done => break,
+                StreamContent::Reasoning(_)
+                | StreamContent::ToolCall(_)
+                | StreamContent::Retry(_)
+                | StreamContent::Choice(_) => {}
+            }
+        }
+        drop(stream);
+
+        let prompt_tokens = meta.prompt_tokens.unwrap_or(tokens);
+        let summary_tokens = meta
+            .completion_tokens
+            .unwrap_or_else(|| tokenizer.count(&content));
+        let cost = chat_model.calc_cost(prompt_tokens + summary_tokens);
+        // The logs of the path from the last one back to the first
+        let mut log_ids = vec![];
+        for id in path.into_iter().skip_while(|id| *id != last_log_id) {
+            log_ids.push(id);
+            if id == first_log_id {
+                break;
+            }
+        }
+        self.chat_summary_repo.insert(
+            &NewChatSummary {
+                id: Id::random(),
+                chat_id,
+                content,
+                first_log_id,
+                last_log_id,
+                model: meta.model.unwrap_or(model),
+                tokens: summary_tokens as i32,
+                cost,
+            },
+            &log_ids,
+        )?;
+        self.chat_repo.add_cost_and_update(chat_id, cost)?;
+
+        Ok(())
+    }
+
+    /// Title the chat after its first exchange with the cheapest model of the vendor.
+    ///
+    /// Chats the user renamed or opted out for keep their title, `None` is returned then.
+    pub async fn generate_title(&self, chat_id: Id) -> Result<Option<String>> {
+        let Chat {
+            user_id,
+            title,
+            prompt_id,
+            config,
+            vendor,
+            ..
+        } = self.chat_repo.select_by_id(chat_id)?;
+        let setting = self.setting_repo.select_by_user_id(user_id)?;
+        if !setting.auto_title {
+            return Ok(None);
+        }
+
+        // Only the titles given at creation are replaced
+        let prompt_name = match prompt_id {
+            Some(prompt_id) => Some(self.prompt_repo.select_by_id(prompt_id)?.name),
+            None => None,
+        };
+        if !title.is_empty() && title != DEFAULT_CHAT_TITLE && Some(&title) != prompt_name.as_ref()
+        {
+            return Ok(None);
+        }
+
+        let path = self.chat_log_repo.select_active_path(chat_id)?;
+        let logs = match path.len() {
+            2 => self.chat_log_repo.select_by_ids(&path)?,
+            _ => return Ok(None),
+        };
+        let [reply, question] = &logs[..] else {
+            return Ok(None);
+        };
+        if question.role.0 != Role::User || reply.role.0 != Role::Assistant || !reply.finished {
+            return Ok(None);
+        }
+
+        // The cheapest priced model of the vendor, the model of the chat otherwise
+        let chat_model = self.chat_model_repo.resolve(
+            &config.0.params.model,
+            config.0.params.model_id,
+            &vendor,
+        )?;
+        let chat_model = self
+            .chat_model_repo
+            .select_by_vendor(&chat_model.vendor)?
+            .into_iter()
+            .filter(|model| {
+                model.price > 0.0 && !model.unlisted && !model.capabilities.0.fixed_sampling
+            })
+            .min_by(|a, b| a.price.total_cmp(&b.price))
+            .unwrap_or(chat_model);
+        let model = chat_model.name.clone();
+
+        let excerpt = |message: &str| message.chars().take(TITLE_EXCERPT).collect::<String>();
+        let transcript = format!(
+            "user: {}\n\nassistant: {}",
+            excerpt(&question.message),
+            excerpt(&reply.message)
+        );
+        let messages = vec![
+            ChatMessage::new(Role::System, TITLE_PROMPT),
+            ChatMessage::new(Role::User, transcript),
+        ];
+        let tokens = messages
+            .iter()
+            .map(|message| message.tokens(chat_model.served_model()))
+            .sum();
+
+        let backend = self
+            .backend_registry
+            .create(&chat_model, &vendor, &setting)?;
+        let request = ChatRequest {
+            messages,
+            params: chat_model.capabilities.0.adapt(ChatParams {
+                model: model.clone(),
+                model_id: Some(chat_model.id),
+                max_tokens: Some(32),
+                ..Default::default()
+            }),
+        };
+
+        let mut content = String::new();
+        let mut meta = StreamMeta::default();
+        let mut stream = backend.send_message(request).await?;
+        while let Some(stream_content) = stream.next().await {
+            match stream_content {
+                StreamContent::Data(data) => content.push_str(&data),
+                StreamContent::Meta(stream_meta) => meta = stream_meta,
+                StreamContent::Error(err) => return Err(err.into()),
+                StreamContent::Done => break,
+                StreamContent::Reasoning(_)
+                | StreamContent::ToolCall(_)
+                | StreamContent::Retry(_)
+                | StreamContent::Choice(_) => {}
+            }
+        }
+        drop(stream);
+
+        let prompt_tokens = meta.prompt_tokens.unwrap_or(tokens);
+        let title_tokens = meta
+            .completion_tokens
+            .unwrap_or_else(|| Tokenizer::for_model(chat_model.served_model()).count(&content));
+        let cost = chat_model.calc_cost(prompt_tokens + title_tokens);
+        self.chat_repo.add_cost_and_update(chat_id, cost)?;
+
+        let title = content
+            .trim()
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .trim_matches(|c: char| c == '"' || c == '\'' || c == '.')
+            .to_string();
+        if title.is_empty() {
+            return Ok(None);
+        }
+        self.chat_repo.update(&PatchChat {
+            id: chat_id,
+            title: Some(title.clone()),
+            ..Default::default()
+        })?;
+
+        Ok(Some(title))
+    }
+
+    pub fn get_chat_summaries(&self, chat_id: Id) -> Result<Vec<ChatSummary>> {
+        self.chat_summary_repo.select_by_chat_id(chat_id)
+    }
+
+    /// Save the result of a tool called by the chat's last reply.
+    pub fn add_tool_message(&self, payload: AddToolMessagePayload) -> Result<Id> {
+        let AddToolMessagePayload {
+            chat_id,
+            tool_call_id,
+            content,
+        } = payload;
+
+        let chat = self.chat_repo.select_by_id(chat_id)?;
+        let active_log_id = chat.active_log_id;
+        let params = chat.config.0.params;
+        let chat_model =
+            self.chat_model_repo
+                .resolve(&params.model, params.model_id, &chat.vendor)?;
+        let model = chat_model.name.clone();
+
+        let tokens = ChatMessage::calc_tokens(chat_model.served_model(), &Role::Tool, &content);
+
+        let id = Id::random();
+        self.chat_log_repo.insert(&NewChatLog {
+            id,
+            chat_id,
+            role: Role::Tool.into(),
+            message: content,
+            model,
+            tokens: tokens as i32,
+            cost: chat_model.calc_cost(tokens),
+            finished: true,
+            tool_calls: vec![].into(),
+            tool_call_id: Some(tool_call_id),
+            finish_reason: None,
+            prompt_tokens: None,
+            time_to_first_token: None,
+            context_log_ids: vec![].into(),
+            structured: None,
+            reasoning: None,
+            parent_id: active_log_id,
+            latency: None,
+        })?;
+        self.chat_repo.update_active_log(chat_id, Some(id))?;
+
+        Ok(id)
     }
 
     pub fn get_chat_models(&self) -> Result<Vec<ChatModel>> {
@@ -442,11 +1326,181 @@ impl ChatService {
             price: payload.price,
             unit: payload.unit,
             vendor: payload.vendor,
+            server_id: None,
+            capabilities: payload.capabilities.into(),
+            served_model: None,
         })?;
 
         Ok(id)
     }
 
+    /// Insert the models served by the user's local servers into `chat_models`.
+    ///
+    /// Servers which can not be reached are skipped.
+    pub async fn discover_local_models(&self, user_id: Id) -> Result<Vec<ChatModel>> {
+        let setting = self.setting_repo.select_by_user_id(user_id)?;
+
+        for server in setting.local_servers.as_ref() {
+            let api = setting.create_local_api(server);
+            let names = match api.list_models().await {
+                Ok(names) => names,
+                Err(err) => {
+                    log::warn!("discover models of {} failed: {}", server.name, err);
+                    continue;
+                }
+            };
+
+            let chat_models = self.chat_model_repo.select_by_server_id(server.id)?;
+            for name in names {
+                if chat_models.iter().any(|chat_model| chat_model.name == name) {
+                    continue;
+                }
+
+                self.chat_model_repo.insert_or_update(&NewChatModel {
+                    id: Id::random(),
+                    name,
+                    description: server.name.clone(),
+                    price: 0.0,
+                    unit: "USD".to_string(),
+                    vendor: "local".to_string(),
+                    server_id: Some(server.id),
+                    capabilities: ChatModelCapabilities::default().into(),
+                    served_model: None,
+                })?;
+            }
+        }
+
+        let chat_models = self
+            .chat_model_repo
+            .select()?
+            .into_iter()
+            .filter(|chat_model| chat_model.vendor == "local")
+            .collect();
+
+        Ok(chat_models)
+    }
+
+    /// Insert the user's Azure deployments into `chat_models`.
+    ///
+    /// New deployments take the price of the model they serve when it is known.
+    pub fn sync_azure_models(&self, user_id: Id) -> Result<Vec<ChatModel>> {
+        let setting = self.setting_repo.select_by_user_id(user_id)?;
+        let chat_models = self.chat_model_repo.select()?;
+
+        if let Some(azure) = setting.azure() {
+            for deployment in &azure.deployments {
+                let exists = chat_models.iter().any(|chat_model| {
+                    chat_model.vendor == "azure" && chat_model.name == deployment.name
+                });
+                if exists {
+                    continue;
+                }
+
+                let price = chat_models
+                    .iter()
+                    .find(|chat_model| {
+                        chat_model.vendor == "openai" && chat_model.name == deployment.model
+                    })
+                    .map(|chat_model| chat_model.price)
+                    .unwrap_or_default();
+
+                self.chat_model_repo.insert_or_update(&NewChatModel {
+                    id: Id::random(),
+                    name: deployment.name.clone(),
+                    description: deployment.model.clone(),
+                    price,
+                    unit: "USD".to_string(),
+                    vendor: "azure".to_string(),
+                    server_id: None,
+                    // Deployments are described by the model they serve
+                    capabilities: ChatModelCapabilities::detect(&deployment.model).into(),
+                    served_model: Some(deployment.model.clone()),
+                })?;
+            }
+        }
+
+        let chat_models = self
+            .chat_model_repo
+            .select()?
+            .into_iter()
+            .filter(|chat_model| chat_model.vendor == "azure")
+            .collect();
+
+        Ok(chat_models)
+    }
+
+    /// Sync `chat_models` of `vendor` with the models its provider lists.
+    ///
+    /// Known models keep their user edited price, models no longer listed
+    /// are flagged as `unlisted` rather than deleted since chats may use them.
+    pub async fn sync_chat_models(&self, user_id: Id, vendor: &str) -> Result<Vec<ChatModel>> {
+        let setting = self.setting_repo.select_by_user_id(user_id)?;
+
+        let names = match vendor {
+            "openai" => setting.create_openai_model_api().list_models().await?,
+            "anthropic" => setting.create_anthropic_model_api().list_models().await?,
+            _ => return Err(ApiErrorKind::UnknownVendor(vendor.to_string()).into()),
+        };
+
+        // Models of other vendors may share names, only the vendor's own are synced
+        let chat_models = self.chat_model_repo.select_by_vendor(vendor)?;
+        for name in &names {
+            let chat_model = chat_models
+                .iter()
+                .find(|chat_model| &chat_model.name == name);
+            let new_chat_model = match chat_model {
+                Some(chat_model) => NewChatModel {
+                    id: chat_model.id,
+                    name: chat_model.name.clone(),
+                    description: chat_model.description.clone(),
+                    price: chat_model.price,
+                    unit: chat_model.unit.clone(),
+                    vendor: chat_model.vendor.clone(),
+                    server_id: chat_model.server_id,
+                    capabilities: chat_model.capabilities.0.clone().into(),
+                    served_model: chat_model.served_model.clone(),
+                },
+                None => NewChatModel {
+                    id: Id::random(),
+                    name: name.clone(),
+                    description: "".to_string(),
+                    price: 0.0,
+                    unit: "USD".to_string(),
+                    vendor: vendor.to_string(),
+                    server_id: None,
+                    capabilities: ChatModelCapabilities::detect(name).into(),
+                    served_model: None,
+                },
+            };
+            self.chat_model_repo.insert_or_update(&new_chat_model)?;
+        }
+
+        for chat_model in &chat_models {
+            let unlisted = !names.contains(&chat_model.name);
+            if unlisted != chat_model.unlisted {
+                self.chat_model_repo.update(&PatchChatModel {
+                    id: chat_model.id,
+                    name: None,
+                    description: None,
+                    price: None,
+                    unit: None,
+                    vendor: None,
+                    unlisted: Some(unlisted),
+                    capabilities: None,
+                })?;
+            }
+        }
+
+        let chat_models = self
+            .chat_model_repo
+            .select()?
+            .into_iter()
+            .filter(|chat_model| chat_model.vendor == vendor)
+            .collect();
+
+        Ok(chat_models)
+    }
+
     pub fn update_chat_model(&self, payload: UpdateChatModelPayload) -> Result<()> {
         self.chat_model_repo.update(&PatchChatModel {
             id: payload.id,
@@ -455,6 +1509,8 @@ impl ChatService {
             price: payload.price,
             unit: payload.unit,
             vendor: payload.vendor,
+            unlisted: None,
+            capabilities: payload.capabilities.map(Into::into),
         })?;
 
         Ok(())
@@ -505,6 +1561,7 @@ impl<T: Default, U: Default> From<SearchChatPayload> for PageQueryParams<T, U> {
 #[derive(serde::Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct SearchChatLogPayload {
+    pub keyword: String,
     pub page: Option<i64>,
     pub per_page: Option<i64>,
     pub chat_id: Option<Id>,
@@ -550,6 +1607,37 @@ pub struct DeleteChatPayload {
 pub struct SendMessagePayload {
     pub chat_id: Id,
     pub message: String,
+    #[serde(skip)]
+    pub images: Vec<ChatImage>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArenaMessagePayload {
+    pub chat_id: Id,
+    pub message: String,
+    #[serde(skip)]
+    pub images: Vec<ChatImage>,
+    /// Ids of the chat models replying side by side
+    pub models: Vec<Id>,
+}
+
+pub struct ArenaReply {
+    pub model: String,
+    pub reply_id: Id,
+    pub handle: JoinHandle<()>,
+}
+
+/// A request ready to be sent, with what is needed to save its reply.
+struct PreparedReply {
+    request: ChatRequest,
+    backend: Box<dyn ChatBackend>,
+    chat_model: ChatModel,
+    response_format: Option<ChatResponseFormat>,
+    prompt_tokens: usize,
+    context_log_ids: Vec<Id>,
+    /// The newest log left out of the context
+    omitted: Option<Id>,
 }
 
 #[derive(serde::Deserialize, Default)]
@@ -571,6 +1659,15 @@ pub struct CreateChatModelPayload {
     pub price: f32,
     pub unit: String,
     pub vendor: String,
+    pub capabilities: ChatModelCapabilities,
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AddToolMessagePayload {
+    pub chat_id: Id,
+    pub tool_call_id: String,
+    pub content: String,
 }
 
 #[derive(serde::Deserialize, Default)]
@@ -582,6 +1679,7 @@ pub struct UpdateChatModelPayload {
     pub price: Option<f32>,
     pub unit: Option<String>,
     pub vendor: Option<String>,
+    pub capabilities: Option<ChatModelCapabilities>,
 }
 
 #[derive(serde::Deserialize, Default)]
@@ -592,15 +1690,27 @@ pub struct DeleteChatModelPayload {
 
 #[cfg(test)]
 mod tests {
+    use diesel::prelude::*;
     use tokio::sync::mpsc::channel;
     use tokio::sync::oneshot;
+    use tokio::task::JoinHandle;
 
     use crate::{
+        api::backend::ChatMessage,
         models::chat::ChatConfig,
+        models::chat_log::{FinishReason, NewChatLog, Role},
+        models::chat_model::{ChatModelCapabilities, NewChatModel},
         result::Result,
-        services::chat::{ChatService, CreateChatPayload, SendMessagePayload},
-        test::establish_connection,
+        schema::{chat_models, settings},
+        services::chat::{
+            ArenaMessagePayload, ChatService, CreateChatPayload, DeleteChatPayload,
+            GetChatLogByCursorPayload, ResendMessagePayload, SearchChatLogPayload,
+            SendMessagePayload, UpdateChatLogPayload, UpdateChatPayload, DEFAULT_CHAT_TITLE,
+        },
+        services::setting::{SettingService, UpdateSettingPayload},
+        test::{establish_connection, lock_setting, mock_server, MockResponse},
         types::{Id, StreamContent},
+        AzureDeployment, AzureSetting, ChatParams, Error, LocalServer,
     };
 
     #[tokio::test]
@@ -625,6 +1735,7 @@ mod tests {
                 SendMessagePayload {
                     chat_id,
                     message: "reply Hi! to me, no more other words".to_string(),
+                    images: vec![],
                 },
                 sender,
                 stop_receiver,
@@ -650,4 +1761,1011 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_generate_title_keeps_titles() -> Result<()> {
+        let conn = establish_connection();
+        let chat_service = ChatService::new(conn);
+
+        for title in ["Release notes", DEFAULT_CHAT_TITLE] {
+            let chat_id = chat_service.create_chat(CreateChatPayload {
+                title: title.to_string(),
+                prompt_id: None,
+                vendor: "openai".to_string(),
+                user_id: Id::local(),
+                config: ChatConfig::default(),
+            })?;
+
+            // Renamed chats, and chats without an exchange yet, are left alone
+            assert_eq!(chat_service.generate_title(chat_id).await?, None);
+            assert_eq!(chat_service.get_chat(chat_id)?.title, title);
+
+            chat_service.delete_chat(DeleteChatPayload { id: chat_id })?;
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_title() -> Result<()> {
+        let conn = establish_connection();
+        let chat_service = ChatService::new(conn.clone());
+        let setting_service = SettingService::new(conn);
+        let _lock = lock_setting().await;
+        let local_servers = setting_service.get_setting(Id::local())?.local_servers.0;
+
+        // The chat replies with its own model, the title comes from a cheaper one
+        let (chat_model, _) = create_local_model(
+            &chat_service,
+            &setting_service,
+            vec![mock_reply("Run diesel migration run")],
+        )
+        .await?;
+        let title_chunk = serde_json::json!({
+            "choices": [{"index": 0, "delta": {"content": " \"Diesel migrations.\"\nA chat about diesel"}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 60, "completion_tokens": 12},
+        });
+        let (url, requests) = mock_server(vec![MockResponse::new(
+            200,
+            vec![&format!("data: {title_chunk}\n\ndata: [DONE]\n\n")],
+        )])
+        .await;
+        let mut servers = setting_service.get_setting(Id::local())?.local_servers.0;
+        let server = LocalServer {
+            id: Id::random(),
+            name: "cheap".to_string(),
+            url,
+        };
+        servers.push(server.clone());
+        setting_service.update_setting(UpdateSettingPayload {
+            local_servers: Some(servers),
+            ..Default::default()
+        })?;
+        let cheap_model = NewChatModel {
+            id: Id::random(),
+            name: format!("cheap-{}:latest", server.id),
+            description: "".to_string(),
+            price: 0.5,
+            unit: "USD".to_string(),
+            vendor: "local".to_string(),
+            server_id: Some(server.id),
+            capabilities: ChatModelCapabilities::default().into(),
+            served_model: None,
+        };
+        chat_service.chat_model_repo.insert(&cheap_model)?;
+
+        let chat_id = create_local_chat(&chat_service, &chat_model, ChatConfig::default())?;
+        send_and_wait(&chat_service, chat_id, "How do I apply migrations?").await?;
+
+        // Users who opted out keep the default title
+        setting_service.update_setting(UpdateSettingPayload {
+            auto_title: Some(false),
+            ..Default::default()
+        })?;
+        assert_eq!(chat_service.generate_title(chat_id).await?, None);
+        setting_service.update_setting(UpdateSettingPayload {
+            auto_title: Some(true),
+            ..Default::default()
+        })?;
+
+        let cost = chat_service.chat_repo.select_by_id(chat_id)?.cost;
+        let title = chat_service.generate_title(chat_id).await?;
+        assert_eq!(title.as_deref(), Some("Diesel migrations"));
+        let chat = chat_service.chat_repo.select_by_id(chat_id)?;
+        assert_eq!(chat.title, "Diesel migrations");
+        // Paid at the price of the cheaper model, by the usage it reported
+        assert!((chat.cost - cost - 0.5 * 72.0 / 1000.0).abs() < 1e-6);
+        let requests = requests.await.unwrap();
+        assert!(requests[0].contains("How do I apply migrations?"));
+
+        chat_service.delete_chat(DeleteChatPayload { id: chat_id })?;
+        chat_service.chat_model_repo.delete(chat_model.id)?;
+        chat_service.chat_model_repo.delete(cheap_model.id)?;
+        setting_service.update_setting(UpdateSettingPayload {
+            local_servers: Some(local_servers),
+            ..Default::default()
+        })?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_chat_logs() -> Result<()> {
+        let conn = establish_connection();
+        let chat_service = ChatService::new(conn.clone());
+        let setting_service = SettingService::new(conn);
+        let _lock = lock_setting().await;
+        let local_servers = setting_service.get_setting(Id::local())?.local_servers.0;
+
+        let (chat_model, _) = create_local_model(
+            &chat_service,
+            &setting_service,
+            vec![mock_reply("The diesel schema is up to date")],
+        )
+        .await?;
+        let chat_id = chat_service.create_chat(CreateChatPayload {
+            title: "Database".to_string(),
+            prompt_id: None,
+            vendor: "local".to_string(),
+            user_id: Id::local(),
+            config: ChatConfig {
+                params: ChatParams {
+                    model: chat_model.name.clone(),
+                    model_id: Some(chat_model.id),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        })?;
+        let (sender, mut receiver) = channel::<StreamContent>(20);
+        let (_stop_sender, stop_receiver) = oneshot::channel::<()>();
+        let (user_log_id, _, handle) = chat_service
+            .send_message(
+                SendMessagePayload {
+                    chat_id,
+                    message: "Run the pending diesel migrations before <script>starting</script> the server"
+                        .to_string(),
+                    images: vec![],
+                },
+                sender,
+                stop_receiver,
+            )
+            .await?;
+        while receiver.recv().await.is_some() {}
+        handle.await.unwrap();
+
+        let search = |keyword: &str| {
+            chat_service.search_chat_logs(SearchChatLogPayload {
+                keyword: keyword.to_string(),
+                chat_id: Some(chat_id),
+                ..Default::default()
+            })
+        };
+
+        let result = search("diesel migration")?;
+        assert_eq!(result.total, 1);
+        assert_eq!(result.records[0].id, Some(user_log_id));
+        assert!(result.records[0]
+            .snippet
+            .contains("<mark>diesel</mark> <mark>migrations</mark>"));
+        // The message is escaped, only the marks are HTML
+        assert!(result.records[0]
+            .snippet
+            .contains("&lt;script&gt;starting&lt;/script&gt;"));
+        assert_eq!(search("diesel")?.total, 2);
+
+        // Syntax of FTS5 in a keyword never breaks the query
+        assert_eq!(search("\"diesel -server")?.total, 1);
+        assert_eq!(search(" ")?.total, 0);
+
+        // Logs of every branch are hits
+        let edit_id = chat_service.update_chat_log(UpdateChatLogPayload {
+            id: user_log_id,
+            content: "Nothing to see here".to_string(),
+        })?;
+        assert_eq!(search("diesel")?.total, 2);
+        assert_eq!(search("nothing")?.records[0].id, Some(edit_id));
+
+        // A title is matched once for its chat, without a log
+        chat_service.update_chat(UpdateChatPayload {
+            id: chat_id,
+            title: Some("Diesel notes".to_string()),
+            prompt_id: None,
+            config: None,
+            sort: None,
+        })?;
+        let result = search("notes")?;
+        assert_eq!(result.total, 1);
+        assert_eq!(result.records[0].id, None);
+        assert_eq!(result.records[0].title, "Diesel <mark>notes</mark>");
+        assert_eq!(search("diesel")?.total, 3);
+
+        // A chat without logs is found by its title
+        let empty_chat_id = chat_service.create_chat(CreateChatPayload {
+            title: "Empty <notes>".to_string(),
+            prompt_id: None,
+            vendor: "local".to_string(),
+            user_id: Id::local(),
+            config: ChatConfig::default(),
+        })?;
+        let result = chat_service.search_chat_logs(SearchChatLogPayload {
+            keyword: "notes".to_string(),
+            chat_id: Some(empty_chat_id),
+            ..Default::default()
+        })?;
+        assert_eq!(result.total, 1);
+        assert_eq!(result.records[0].title, "Empty &lt;<mark>notes</mark>&gt;");
+        chat_service.delete_chat(DeleteChatPayload { id: empty_chat_id })?;
+
+        chat_service.delete_chat(DeleteChatPayload { id: chat_id })?;
+        assert_eq!(search("diesel")?.total, 0);
+
+        chat_service.chat_model_repo.delete(chat_model.id)?;
+        setting_service.update_setting(UpdateSettingPayload {
+            local_servers: Some(local_servers),
+            ..Default::default()
+        })?;
+
+        Ok(())
+    }
+
+    /// A local model answering with `responses` in order, served by the only local server.
+    ///
+    /// Take [`lock_setting`] first and restore the local servers afterwards.
+    async fn create_local_model(
+        chat_service: &ChatService,
+        setting_service: &SettingService,
+        responses: Vec<MockResponse>,
+    ) -> Result<(NewChatModel, JoinHandle<Vec<String>>)> {
+        let (url, handle) = mock_server(responses).await;
+        let server = LocalServer {
+            id: Id::random(),
+            name: "mock".to_string(),
+            url,
+        };
+        setting_service.update_setting(UpdateSettingPayload {
+            local_servers: Some(vec![server.clone()]),
+            ..Default::default()
+        })?;
+        let chat_model = NewChatModel {
+            id: Id::random(),
+            name: format!("mock-{}:latest", server.id),
+            description: "".to_string(),
+            price: 1.0,
+            unit: "USD".to_string(),
+            vendor: "local".to_string(),
+            server_id: Some(server.id),
+            capabilities: ChatModelCapabilities::default().into(),
+            served_model: None,
+        };
+        chat_service.chat_model_repo.insert(&chat_model)?;
+
+        Ok((chat_model, handle))
+    }
+
+    /// A streamed reply of a local model
+    fn mock_reply(content: &str) -> MockResponse {
+        let chunk = serde_json::json!({
+            "choices": [{"index": 0, "delta": {"content": content}, "finish_reason": "stop"}]
+        });
+        MockResponse::new(200, vec![&format!("data: {chunk}\n\ndata: [DONE]\n\n")])
+    }
+
+    fn create_local_chat(
+        chat_service: &ChatService,
+        chat_model: &NewChatModel,
+        config: ChatConfig,
+    ) -> Result<Id> {
+        chat_service.create_chat(CreateChatPayload {
+            title: DEFAULT_CHAT_TITLE.to_string(),
+            prompt_id: None,
+            vendor: "local".to_string(),
+            user_id: Id::local(),
+            config: ChatConfig {
+                params: ChatParams {
+                    model: chat_model.name.clone(),
+                    model_id: Some(chat_model.id),
+                    ..config.params
+                },
+                ..config
+            },
+        })
+    }
+
+    /// Send `message` and wait until its reply is saved
+    async fn send_and_wait(
+        chat_service: &ChatService,
+        chat_id: Id,
+        message: &str,
+    ) -> Result<(Id, Id)> {
+        let (sender, mut receiver) = channel::<StreamContent>(20);
+        let (_stop_sender, stop_receiver) = oneshot::channel::<()>();
+        let (user_log_id, reply_log_id, handle) = chat_service
+            .send_message(
+                SendMessagePayload {
+                    chat_id,
+                    message: message.to_string(),
+                    images: vec![],
+                },
+                sender,
+                stop_receiver,
+            )
+            .await?;
+        while receiver.recv().await.is_some() {}
+        handle.await.unwrap();
+
+        Ok((user_log_id, reply_log_id))
+    }
+
+    async fn resend_and_wait(chat_service: &ChatService, id: Id) -> Result<(Id, Id)> {
+        let (sender, mut receiver) = channel::<StreamContent>(20);
+        let (_stop_sender, stop_receiver) = oneshot::channel::<()>();
+        let (user_log_id, reply_log_id, handle) = chat_service
+            .resend_message(ResendMessagePayload { id }, sender, stop_receiver)
+            .await?;
+        while receiver.recv().await.is_some() {}
+        handle.await.unwrap();
+
+        Ok((user_log_id, reply_log_id))
+    }
+
+    #[tokio::test]
+    async fn test_chat_log_branches() -> Result<()> {
+        let conn = establish_connection();
+        let chat_service = ChatService::new(conn.clone());
+        let setting_service = SettingService::new(conn);
+        let _lock = lock_setting().await;
+        let local_servers = setting_service.get_setting(Id::local())?.local_servers.0;
+
+        let (chat_model, requests) = create_local_model(
+            &chat_service,
+            &setting_service,
+            vec![
+                mock_reply("Answer one"),
+                mock_reply("Answer two"),
+                mock_reply("Answer two again"),
+                mock_reply("Answer to the edit"),
+            ],
+        )
+        .await?;
+        let chat_id = create_local_chat(&chat_service, &chat_model, ChatConfig::default())?;
+        let path = || chat_service.chat_log_repo.select_active_path(chat_id);
+
+        let (question_one, answer_one) =
+            send_and_wait(&chat_service, chat_id, "Question one").await?;
+        let (question_two, answer_two) =
+            send_and_wait(&chat_service, chat_id, "Question two").await?;
+        assert_eq!(
+            path()?,
+            vec![answer_two, question_two, answer_one, question_one]
+        );
+
+        // Resending branches beside the message, the old branch is kept
+        let (resent_two, answer_two_again) = resend_and_wait(&chat_service, question_two).await?;
+        assert_ne!(resent_two, question_two);
+        assert_eq!(
+            path()?,
+            vec![answer_two_again, resent_two, answer_one, question_one]
+        );
+        let branches = chat_service.get_chat_log_branches(resent_two)?;
+        assert_eq!(
+            branches.iter().map(|log| log.id).collect::<Vec<_>>(),
+            vec![question_two, resent_two]
+        );
+        assert_eq!(
+            chat_service
+                .chat_log_repo
+                .select_by_id(answer_two)?
+                .parent_id,
+            Some(question_two)
+        );
+
+        // Pages follow the active path
+        let page = chat_service.get_chat_logs_by_cursor(GetChatLogByCursorPayload {
+            chat_id: Some(chat_id),
+            size: 2,
+            ..Default::default()
+        })?;
+        assert_eq!(
+            page.records.iter().map(|log| log.id).collect::<Vec<_>>(),
+            vec![answer_two_again, resent_two]
+        );
+        assert_eq!(page.next_cursor, Some(answer_one));
+        let page = chat_service.get_chat_logs_by_cursor(GetChatLogByCursorPayload {
+            chat_id: Some(chat_id),
+            cursor: page.next_cursor,
+            size: 2,
+            ..Default::default()
+        })?;
+        assert_eq!(
+            page.records.iter().map(|log| log.id).collect::<Vec<_>>(),
+            vec![answer_one, question_one]
+        );
+        assert_eq!(page.next_cursor, None);
+
+        // Switching follows the branch down to its newest log
+        assert_eq!(chat_service.switch_chat_branch(question_two)?, answer_two);
+        assert_eq!(
+            path()?,
+            vec![answer_two, question_two, answer_one, question_one]
+        );
+
+        // An edit is answered in place when resent, within the context of its own branch
+        let edit_id = chat_service.update_chat_log(UpdateChatLogPayload {
+            id: question_one,
+            content: "Question one, edited".to_string(),
+        })?;
+        assert_eq!(path()?, vec![edit_id]);
+        let (resent_edit, answer_edit) = resend_and_wait(&chat_service, edit_id).await?;
+        assert_eq!(resent_edit, edit_id);
+        assert_eq!(path()?, vec![answer_edit, edit_id]);
+        let edit = chat_service.chat_log_repo.select_by_id(edit_id)?;
+        assert!(edit.finished);
+        assert!(edit.cost > 0.0);
+        assert_eq!(chat_service.get_chat_log_branches(edit_id)?.len(), 2);
+
+        // Deleting a log moves the logs following it to its parent
+        chat_service.delete_chat_log(answer_one)?;
+        for id in [question_two, resent_two] {
+            assert_eq!(
+                chat_service.chat_log_repo.select_by_id(id)?.parent_id,
+                Some(question_one)
+            );
+        }
+        assert_eq!(
+            chat_service.switch_chat_branch(question_one)?,
+            answer_two_again
+        );
+        assert_eq!(path()?, vec![answer_two_again, resent_two, question_one]);
+
+        // The context of each request is the active path only
+        let requests = requests.await.unwrap();
+        assert!(requests[2].contains("Answer one"));
+        assert!(!requests[2].contains("Answer two"));
+        assert!(requests[3].contains("Question one, edited"));
+        assert!(!requests[3].contains("Answer one"));
+
+        chat_service.delete_chat(DeleteChatPayload { id: chat_id })?;
+        chat_service.chat_model_repo.delete(chat_model.id)?;
+        setting_service.update_setting(UpdateSettingPayload {
+            local_servers: Some(local_servers),
+            ..Default::default()
+        })?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_summarize_chat() -> Result<()> {
+        let conn = establish_connection();
+        let chat_service = ChatService::new(conn.clone());
+        let setting_service = SettingService::new(conn);
+        let _lock = lock_setting().await;
+        let local_servers = setting_service.get_setting(Id::local())?.local_servers.0;
+
+        let (chat_model, requests) = create_local_model(
+            &chat_service,
+            &setting_service,
+            vec![
+                mock_reply("Answer one"),
+                mock_reply("Answer two"),
+                mock_reply("The user asked twice"),
+                mock_reply("Answer three"),
+            ],
+        )
+        .await?;
+        let chat_id = create_local_chat(
+            &chat_service,
+            &chat_model,
+            ChatConfig {
+                summarize: true,
+                ..Default::default()
+            },
+        )?;
+
+        let (question_one, answer_one) =
+            send_and_wait(&chat_service, chat_id, "Question one").await?;
+        send_and_wait(&chat_service, chat_id, "Question two").await?;
+
+        // A chat is summarized once at a time
+        let cost = chat_service.chat_repo.select_by_id(chat_id)?.cost;
+        let (first, second) = tokio::join!(
+            chat_service.summarize_chat(chat_id, answer_one),
+            chat_service.summarize_chat(chat_id, answer_one),
+        );
+        first?;
+        second?;
+        let summaries = chat_service.get_chat_summaries(chat_id)?;
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].content, "The user asked twice");
+        assert_eq!(summaries[0].first_log_id, question_one);
+        assert_eq!(summaries[0].last_log_id, answer_one);
+        assert!(summaries[0].cost > 0.0);
+        assert_eq!(
+            chat_service.chat_repo.select_by_id(chat_id)?.cost,
+            cost + summaries[0].cost
+        );
+
+        // The logs up to the summary are sent as the summary
+        send_and_wait(&chat_service, chat_id, "Question three").await?;
+        let requests = requests.await.unwrap();
+        assert!(requests[2].contains("Question one"));
+        assert!(!requests[2].contains("Question two"));
+        assert!(requests[3].contains("The user asked twice"));
+        assert!(!requests[3].contains("Question one"));
+        assert!(!requests[3].contains("Answer one"));
+        assert!(requests[3].contains("Question two"));
+
+        // Deleting a covered log drops the summary
+        chat_service.delete_chat_log(question_one)?;
+        assert!(chat_service.get_chat_summaries(chat_id)?.is_empty());
+
+        chat_service.delete_chat(DeleteChatPayload { id: chat_id })?;
+        chat_service.chat_model_repo.delete(chat_model.id)?;
+        setting_service.update_setting(UpdateSettingPayload {
+            local_servers: Some(local_servers),
+            ..Default::default()
+        })?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_arena_message() -> Result<()> {
+        let conn = establish_connection();
+        let chat_service = ChatService::new(conn.clone());
+        let setting_service = SettingService::new(conn);
+        let _lock = lock_setting().await;
+        let local_servers = setting_service.get_setting(Id::local())?.local_servers.0;
+
+        // Two local models priced apart, each served by its own server
+        let mut servers = vec![];
+        let mut chat_models = vec![];
+        for (name, price) in [("arena-small", 1.0), ("arena-large", 2.0)] {
+            let (url, _) = mock_server(vec![mock_reply("Same answer")]).await;
+            let server = LocalServer {
+                id: Id::random(),
+                name: name.to_string(),
+                url,
+            };
+            let chat_model = NewChatModel {
+                id: Id::random(),
+                name: format!("{name}-{}:latest", server.id),
+                description: "".to_string(),
+                price,
+                unit: "USD".to_string(),
+                vendor: "local".to_string(),
+                server_id: Some(server.id),
+                capabilities: ChatModelCapabilities::default().into(),
+                served_model: None,
+            };
+            chat_service.chat_model_repo.insert(&chat_model)?;
+            servers.push(server);
+            chat_models.push(chat_model);
+        }
+        setting_service.update_setting(UpdateSettingPayload {
+            local_servers: Some(servers),
+            ..Default::default()
+        })?;
+
+        let chat_id = create_local_chat(&chat_service, &chat_models[0], ChatConfig::default())?;
+        let active_log_id = chat_service.chat_repo.select_by_id(chat_id)?.active_log_id;
+
+        let mut receivers = vec![];
+        let mut channels = vec![];
+        for _ in &chat_models {
+            let (sender, receiver) = channel::<StreamContent>(20);
+            let (stop_sender, stop_receiver) = oneshot::channel::<()>();
+            receivers.push((receiver, stop_sender));
+            channels.push((sender, stop_receiver));
+        }
+        let (user_log_id, replies) = chat_service
+            .arena_message(
+                ArenaMessagePayload {
+                    chat_id,
+                    message: "Which model is better?".to_string(),
+                    images: vec![],
+                    models: chat_models.iter().map(|chat_model| chat_model.id).collect(),
+                },
+                channels,
+            )
+            .await?;
+        for (mut receiver, _stop_sender) in receivers {
+            while receiver.recv().await.is_some() {}
+        }
+        let mut reply_ids = vec![];
+        for reply in replies {
+            reply.handle.await.unwrap();
+            reply_ids.push((reply.model, reply.reply_id));
+        }
+
+        // The question is paid to both models
+        let user_log = chat_service.chat_log_repo.select_by_id(user_log_id)?;
+        let user_message = ChatMessage::new(Role::User, "Which model is better?");
+        let mut question_cost = 0.0;
+        for chat_model in &chat_models {
+            let chat_model = chat_service.chat_model_repo.select_by_id(chat_model.id)?;
+            question_cost += chat_model.calc_cost(user_message.tokens(&chat_model.name));
+        }
+        assert_eq!(user_log.cost, question_cost);
+
+        let mut costs = vec![];
+        for (chat_model, (model, reply_id)) in chat_models.iter().zip(reply_ids) {
+            assert_eq!(model, chat_model.name);
+            let reply = chat_service.chat_log_repo.select_by_id(reply_id)?;
+            assert_eq!(reply.model, chat_model.name);
+            assert_eq!(reply.parent_id, Some(user_log_id));
+            assert!(reply.latency.is_some());
+            assert!(reply.cost > 0.0);
+            costs.push(reply.cost);
+        }
+        // Same tokens at twice the price
+        assert_eq!(costs[1], costs[0] * 2.0);
+
+        // Nothing is followed until a reply is picked
+        assert_eq!(
+            chat_service.chat_repo.select_by_id(chat_id)?.active_log_id,
+            active_log_id
+        );
+
+        chat_service.delete_chat(DeleteChatPayload { id: chat_id })?;
+        for chat_model in chat_models {
+            chat_service.chat_model_repo.delete(chat_model.id)?;
+        }
+        setting_service.update_setting(UpdateSettingPayload {
+            local_servers: Some(local_servers),
+            ..Default::default()
+        })?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_azure_served_model() -> Result<()> {
+        let conn = establish_connection();
+        let chat_service = ChatService::new(conn.clone());
+        let setting_service = SettingService::new(conn.clone());
+        let _lock = lock_setting().await;
+
+        let (url, requests) = mock_server(vec![mock_reply("Hi")]).await;
+        let deployment = format!("team-gpt-{}", Id::random());
+        setting_service.update_setting(UpdateSettingPayload {
+            azure: Some(AzureSetting {
+                endpoint: format!("{url}/"),
+                api_key: Some("azure-key".to_string()),
+                api_version: "2024-10-21".to_string(),
+                deployments: vec![AzureDeployment {
+                    name: deployment.clone(),
+                    model: "gpt-4o".to_string(),
+                }],
+            }),
+            ..Default::default()
+        })?;
+        let chat_model = chat_service
+            .sync_azure_models(Id::local())?
+            .into_iter()
+            .find(|chat_model| chat_model.name == deployment)
+            .unwrap();
+        assert_eq!(chat_model.served_model(), "gpt-4o");
+
+        let chat_id = chat_service.create_chat(CreateChatPayload {
+            title: DEFAULT_CHAT_TITLE.to_string(),
+            prompt_id: None,
+            vendor: "azure".to_string(),
+            user_id: Id::local(),
+            config: ChatConfig {
+                params: ChatParams {
+                    model: deployment.clone(),
+                    model_id: Some(chat_model.id),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        })?;
+        let (user_log_id, _) = send_and_wait(&chat_service, chat_id, "Hello Azure").await?;
+
+        // Sent to the deployment, counted as the model it serves
+        let requests = requests.await.unwrap();
+        assert!(requests[0].starts_with(&format!(
+            "POST /openai/deployments/{deployment}/chat/completions?api-version=2024-10-21 "
+        )));
+        assert!(requests[0].contains("api-key: azure-key"));
+        assert!(requests[0].contains("\"include_usage\":true"));
+        let user_log = chat_service.chat_log_repo.select_by_id(user_log_id)?;
+        assert_eq!(
+            user_log.tokens as usize,
+            ChatMessage::new(Role::User, "Hello Azure").tokens("gpt-4o")
+        );
+
+        chat_service.delete_chat(DeleteChatPayload { id: chat_id })?;
+        chat_service.chat_model_repo.delete(chat_model.id)?;
+        diesel::update(settings::table)
+            .set(settings::azure.eq(None::<String>))
+            .execute(&mut *conn.conn())?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_chat_models() -> Result<()> {
+        let conn = establish_connection();
+        let chat_service = ChatService::new(conn.clone());
+        let setting_service = SettingService::new(conn.clone());
+        let _lock = lock_setting().await;
+
+        let suffix = Id::random();
+        let kept = format!("gpt-kept-{suffix}");
+        let gone = format!("gpt-gone-{suffix}");
+        let added = format!("gpt-added-{suffix}");
+        for name in [&kept, &gone] {
+            chat_service.chat_model_repo.insert(&NewChatModel {
+                id: Id::random(),
+                name: name.clone(),
+                description: "edited".to_string(),
+                price: 3.0,
+                unit: "USD".to_string(),
+                vendor: "openai".to_string(),
+                server_id: None,
+                capabilities: ChatModelCapabilities::default().into(),
+                served_model: None,
+            })?;
+        }
+        let listed = chat_service
+            .chat_model_repo
+            .select_by_vendor("openai")?
+            .into_iter()
+            .filter(|chat_model| !chat_model.unlisted)
+            .map(|chat_model| chat_model.id)
+            .collect::<Vec<_>>();
+
+        let list = serde_json::json!({
+            "object": "list",
+            "data": [
+                {"id": kept, "object": "model"},
+                {"id": added, "object": "model"},
+                {"id": "text-embedding-3-small", "object": "model"},
+            ]
+        });
+        let (url, requests) =
+            mock_server(vec![MockResponse::new(200, vec![&list.to_string()])]).await;
+        let forward_url = setting_service.get_setting(Id::local())?.forward_url;
+        setting_service.update_setting(UpdateSettingPayload {
+            forward_url: Some(url),
+            ..Default::default()
+        })?;
+        let result = chat_service.sync_chat_models(Id::local(), "openai").await;
+        setting_service.update_setting(UpdateSettingPayload {
+            forward_url: Some(forward_url.unwrap_or_default()),
+            ..Default::default()
+        })?;
+        let chat_models = result?;
+        assert!(requests.await.unwrap()[0].starts_with("GET /v1/models "));
+
+        // The price and description edited by the user are kept
+        let find = |name: &str| {
+            chat_models
+                .iter()
+                .find(|chat_model| chat_model.name == name)
+        };
+        let kept = find(&kept).unwrap();
+        assert_eq!((kept.price, kept.description.as_str()), (3.0, "edited"));
+        assert!(!kept.unlisted);
+        let added = find(&added).unwrap();
+        assert_eq!(added.price, 0.0);
+        assert!(!added.unlisted);
+        assert!(find(&gone).unwrap().unlisted);
+        assert!(find("text-embedding-3-small").is_none());
+
+        for chat_model in [kept, added, find(&gone).unwrap()] {
+            chat_service.chat_model_repo.delete(chat_model.id)?;
+        }
+        diesel::update(chat_models::table.filter(chat_models::id.eq_any(listed)))
+            .set(chat_models::unlisted.eq(false))
+            .execute(&mut *conn.conn())?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_message_with_choices() -> Result<()> {
+        let conn = establish_connection();
+        let chat_service = ChatService::new(conn.clone());
+        let setting_service = SettingService::new(conn);
+        let _lock = lock_setting().await;
+        let local_servers = setting_service.get_setting(Id::local())?.local_servers.0;
+
+        let (url, _) = mock_server(vec![MockResponse::new(
+            200,
+            vec![
+                "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hi there, nice to meet you\"}}]}\n\n",
+                "data: {\"choices\":[{\"index\":1,\"delta\":{\"content\":\"Hello\"},\"finish_reason\":\"length\"}]}\n\n",
+                "data: {\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+                "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":10,\"completion_tokens\":40}}\n\ndata: [DONE]\n\n",
+            ],
+        )])
+        .await;
+        let server = LocalServer {
+            id: Id::random(),
+            name: "choices".to_string(),
+            url,
+        };
+        setting_service.update_setting(UpdateSettingPayload {
+            local_servers: Some(vec![server.clone()]),
+            ..Default::default()
+        })?;
+        let chat_model = NewChatModel {
+            id: Id::random(),
+            name: "choices-llama:latest".to_string(),
+            description: "".to_string(),
+            price: 1.0,
+            unit: "USD".to_string(),
+            vendor: "local".to_string(),
+            server_id: Some(server.id),
+            capabilities: ChatModelCapabilities::default().into(),
+            served_model: None,
+        };
+        chat_service.chat_model_repo.insert(&chat_model)?;
+
+        let chat_id = chat_service.create_chat(CreateChatPayload {
+            title: "Choices".to_string(),
+            prompt_id: None,
+            vendor: "local".to_string(),
+            user_id: Id::local(),
+            config: ChatConfig {
+                params: ChatParams {
+                    model: chat_model.name.clone(),
+                    model_id: Some(chat_model.id),
+                    n: Some(2),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        })?;
+        let (sender, mut receiver) = channel::<StreamContent>(20);
+        let (_stop_sender, stop_receiver) = oneshot::channel::<()>();
+        let (_, reply_log_id, handle) = chat_service
+            .send_message(
+                SendMessagePayload {
+                    chat_id,
+                    message: "Hi".to_string(),
+                    images: vec![],
+                },
+                sender,
+                stop_receiver,
+            )
+            .await?;
+        while receiver.recv().await.is_some() {}
+        handle.await.unwrap();
+
+        let replies = chat_service.get_chat_log_branches(reply_log_id)?;
+        assert_eq!(replies.len(), 2);
+        let (reply, alternative) = (&replies[0], &replies[1]);
+        assert_eq!(reply.id, reply_log_id);
+        assert_eq!(alternative.message, "Hello");
+        // Each choice keeps its own finish reason and its share of the usage
+        assert_eq!(reply.finish_reason.as_ref().unwrap().0, FinishReason::Stop);
+        assert_eq!(
+            alternative.finish_reason.as_ref().unwrap().0,
+            FinishReason::Length
+        );
+        assert_eq!(reply.tokens + alternative.tokens, 40);
+        assert!(alternative.tokens > 0 && alternative.tokens < reply.tokens);
+        assert!(alternative.cost > 0.0);
+        let chat = chat_service.chat_repo.select_by_id(chat_id)?;
+        assert!((chat.cost - (reply.cost + alternative.cost)).abs() < 1e-6);
+
+        chat_service.delete_chat(DeleteChatPayload { id: chat_id })?;
+        chat_service.chat_model_repo.delete(chat_model.id)?;
+        setting_service.update_setting(UpdateSettingPayload {
+            local_servers: Some(local_servers),
+            ..Default::default()
+        })?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_chat_log() -> Result<()> {
+        let conn = establish_connection();
+        let chat_service = ChatService::new(conn);
+
+        let chat_id = chat_service.create_chat(CreateChatPayload {
+            title: "Edit".to_string(),
+            prompt_id: None,
+            vendor: "openai".to_string(),
+            user_id: Id::local(),
+            config: ChatConfig::default(),
+        })?;
+        let reply_id = Id::random();
+        chat_service.chat_log_repo.insert(&NewChatLog {
+            id: reply_id,
+            chat_id,
+            role: Role::Assistant.into(),
+            message: "Hello".to_string(),
+            model: "gpt-4o".to_string(),
+            tokens: 1,
+            cost: 0.1,
+            finished: true,
+            tool_calls: vec![].into(),
+            tool_call_id: None,
+            finish_reason: Some(FinishReason::Stop.into()),
+            prompt_tokens: Some(42),
+            time_to_first_token: Some(300),
+            context_log_ids: vec![Id::random()].into(),
+            structured: None,
+            reasoning: Some("The user greets.".to_string()),
+            parent_id: None,
+            latency: Some(900),
+        })?;
+
+        let content = "Hello there, how can I help you today?";
+        let id = chat_service.update_chat_log(UpdateChatLogPayload {
+            id: reply_id,
+            content: content.to_string(),
+        })?;
+        let copy = chat_service.chat_log_repo.select_by_id(id)?;
+        assert_eq!(copy.message, content);
+        assert_eq!(
+            copy.tokens as usize,
+            ChatMessage::calc_tokens("gpt-4o", &Role::Assistant, content)
+        );
+        // Nothing measured while replying carries over to the edit
+        assert!(copy.finish_reason.is_none());
+        assert!(copy.prompt_tokens.is_none());
+        assert!(copy.time_to_first_token.is_none());
+        assert!(copy.context_log_ids.0.is_empty());
+        assert!(copy.reasoning.is_none());
+        assert!(copy.latency.is_none());
+
+        chat_service.delete_chat(DeleteChatPayload { id: chat_id })?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_discover_shared_local_models() -> Result<()> {
+        let conn = establish_connection();
+        let chat_service = ChatService::new(conn.clone());
+        let setting_service = SettingService::new(conn);
+        let _lock = lock_setting().await;
+        let local_servers = setting_service.get_setting(Id::local())?.local_servers.0;
+
+        // Both servers serve the same tag, discovered twice
+        let tags = r#"{"models":[{"name":"shared-llama:latest"}]}"#;
+        let mut servers = vec![];
+        for name in ["first", "second"] {
+            let (url, _) = mock_server(vec![
+                MockResponse::new(200, vec![tags]),
+                MockResponse::new(200, vec![tags]),
+            ])
+            .await;
+            servers.push(LocalServer {
+                id: Id::random(),
+                name: name.to_string(),
+                url,
+            });
+        }
+        setting_service.update_setting(UpdateSettingPayload {
+            local_servers: Some(servers.clone()),
+            ..Default::default()
+        })?;
+
+        chat_service.discover_local_models(Id::local()).await?;
+        let chat_models = chat_service.discover_local_models(Id::local()).await?;
+        let shared = chat_models
+            .iter()
+            .filter(|chat_model| chat_model.name == "shared-llama:latest")
+            .collect::<Vec<_>>();
+        assert_eq!(shared.len(), 2);
+
+        // Each model routes to its own server
+        for server in &servers {
+            let chat_model = shared
+                .iter()
+                .find(|chat_model| chat_model.server_id == Some(server.id))
+                .unwrap();
+            let resolved = chat_service.chat_model_repo.resolve(
+                "shared-llama:latest",
+                Some(chat_model.id),
+                "local",
+            )?;
+            assert_eq!(resolved.server_id, Some(server.id));
+        }
+        assert!(matches!(
+            chat_service
+                .chat_model_repo
+                .resolve("shared-llama:latest", None, "local"),
+            Err(Error::Validation(_))
+        ));
+
+        for chat_model in shared {
+            chat_service.chat_model_repo.delete(chat_model.id)?;
+        }
+        setting_service.update_setting(UpdateSettingPayload {
+            local_servers: Some(local_servers),
+            ..Default::default()
+        })?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,184 @@
+use crate::api::backend::ChatMessage;
+use crate::models::chat_log::Role;
+use crate::types::Id;
+
+/// Tokens kept free for the reply.
+pub const COMPLETION_TOKENS: usize = 1024;
+
+/// Fills the history of a chat, newest log first, into a token budget.
+///
+/// Kept messages (the prompt and the new message) count whatever the budget,
+/// older logs are added while they fit, up to `max_logs` of them unless it is 0.
+pub struct ContextBuilder {
+    model: String,
+    budget: usize,
+    max_logs: usize,
+    tokens: usize,
+    /// Newest first, with their tokens
+    history: Vec<(Id, ChatMessage, usize)>,
+    full: bool,
+    omitted: Option<Id>,
+}
+
+#[derive(Debug)]
+pub struct Context {
+    /// Oldest first
+    pub history: Vec<ChatMessage>,
+    /// The logs of the history
+    pub log_ids: Vec<Id>,
+    /// Tokens of the kept messages and the history
+    pub tokens: usize,
+    /// The newest log left out of the history
+    pub omitted: Option<Id>,
+}
+
+impl ContextBuilder {
+    pub fn new(model: &str, budget: usize, max_logs: usize) -> Self {
+        Self {
+            model: model.to_string(),
+            budget,
+            max_logs,
+            tokens: 0,
+            history: vec![],
+            full: false,
+            omitted: None,
+        }
+    }
+
+    pub fn keep(&mut self, message: &ChatMessage) {
+        self.tokens += message.tokens(&self.model);
+    }
+
+    /// Add the log before the ones already pushed, `false` once the history is full.
+    pub fn push(&mut self, log_id: Id, message: ChatMessage) -> bool {
+        if self.full || (self.max_logs > 0 && self.history.len() >= self.max_logs) {
+            return self.reject(log_id);
+        }
+
+        let tokens = message.tokens(&self.model);
+        if self.tokens + tokens > self.budget {
+            // Older logs are not picked over this one, the history stays contiguous
+            return self.reject(log_id);
+        }
+
+        self.tokens += tokens;
+        self.history.push((log_id, message, tokens));
+
+        true
+    }
+
+    fn reject(&mut self, log_id: Id) -> bool {
+        self.full = true;
+        self.omitted.get_or_insert(log_id);
+
+        false
+    }
+
+    pub fn build(mut self) -> Context {
+        // Tool messages whose calling reply did not fit are invalid
+        while matches!(self.history.last(), Some((_, message, _)) if message.role == Role::Tool) {
+            if let Some((log_id, _, tokens)) = self.history.pop() {
+                self.tokens -= tokens;
+                self.omitted = Some(log_id);
+            }
+        }
+
+        let (log_ids, history) = self
+            .history
+            .into_iter()
+            .rev()
+            .map(|(log_id, message, _)| (log_id, message))
+            .unzip();
+
+        Context {
+            history,
+            log_ids,
+            tokens: self.tokens,
+            omitted: self.omitted,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{api::backend::ChatMessage, models::chat_log::Role, types::Id};
+
+    use super::ContextBuilder;
+
+    const MODEL: &str = "gpt-4o";
+
+    fn tokens(message: &ChatMessage) -> usize {
+        message.tokens(MODEL)
+    }
+
+    #[test]
+    fn test_fill_budget() {
+        let prompt = ChatMessage::new(Role::User, "You are a helpful assistant.");
+        let message = ChatMessage::new(Role::User, "And the third one?");
+        let logs = (0..3)
+            .map(|index| {
+                let role = if index % 2 == 0 {
+                    Role::User
+                } else {
+                    Role::Assistant
+                };
+                (
+                    Id::random(),
+                    ChatMessage::new(role, "word ".repeat(10 * (index + 1))),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // Room for the two newest logs only
+        let budget = tokens(&prompt) + tokens(&message) + tokens(&logs[2].1) + tokens(&logs[1].1);
+        let mut builder = ContextBuilder::new(MODEL, budget, 0);
+        builder.keep(&prompt);
+        builder.keep(&message);
+        for (id, log) in logs.iter().rev() {
+            if !builder.push(*id, log.clone()) {
+                break;
+            }
+        }
+        let context = builder.build();
+
+        assert_eq!(context.log_ids, vec![logs[1].0, logs[2].0]);
+        assert_eq!(context.history[0].content, logs[1].1.content);
+        assert_eq!(context.tokens, budget);
+        assert_eq!(context.omitted, Some(logs[0].0));
+    }
+
+    #[test]
+    fn test_keep_over_budget() {
+        let message = ChatMessage::new(Role::User, "word ".repeat(100));
+        let mut builder = ContextBuilder::new(MODEL, 10, 0);
+        builder.keep(&message);
+
+        assert!(!builder.push(Id::random(), ChatMessage::new(Role::Assistant, "Hi")));
+        let context = builder.build();
+
+        assert!(context.history.is_empty());
+        assert_eq!(context.tokens, tokens(&message));
+    }
+
+    #[test]
+    fn test_max_logs_and_tool_messages() {
+        let mut builder = ContextBuilder::new(MODEL, usize::MAX, 2);
+        let reply = Id::random();
+        let tool = Id::random();
+        assert!(builder.push(reply, ChatMessage::new(Role::Assistant, "It is sunny")));
+        assert!(builder.push(
+            tool,
+            ChatMessage::new(Role::Tool, "{\"weather\":\"sunny\"}")
+        ));
+        assert!(!builder.push(Id::random(), ChatMessage::new(Role::Assistant, "")));
+        let context = builder.build();
+
+        // The tool message lost the reply calling it
+        assert_eq!(context.log_ids, vec![reply]);
+        assert_eq!(context.omitted, Some(tool));
+        assert_eq!(
+            context.tokens,
+            tokens(&ChatMessage::new(Role::Assistant, "It is sunny"))
+        );
+    }
+}
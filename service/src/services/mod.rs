@@ -1,4 +1,5 @@
 pub mod chat;
+pub mod context;
 pub mod plugin;
 pub mod plugin_market;
 pub mod prompt;
@@ -1,13 +1,13 @@
 use std::{collections::HashMap, sync::Arc};
 
 use crate::{
-    api::openai::chat::params::{OpenAIChatMessage, OpenAIChatParams, OpenAIChatRole},
-    error::StreamError,
+    api::backend::{BackendRegistry, ChatBackend, ChatMessage, ChatRequest},
+    models::chat_log::Role,
     models::plugin::{InstalledPlugin, NewPlugin, PatchPlugin, Plugin, PluginConfig},
     plugin::{RunningPlugin, RunningPluginState},
-    repositories::{plugin::PluginRepo, setting::SettingRepo},
+    repositories::{chat_model::ChatModelRepo, plugin::PluginRepo, setting::SettingRepo},
     result::Result,
-    DbConn, Error, Id, StreamContent,
+    ChatParams, DbConn, Error, Id, StreamContent,
 };
 use futures::StreamExt;
 use tokio::sync::{mpsc::Receiver, Mutex};
@@ -18,6 +18,8 @@ pub struct PluginService {
     conn: DbConn,
     plugin_repo: PluginRepo,
     setting_repo: SettingRepo,
+    chat_model_repo: ChatModelRepo,
+    backend_registry: BackendRegistry,
     chat_stream_map: Arc<Mutex<HashMap<Id, Receiver<StreamContent>>>>,
 }
 
@@ -32,6 +34,8 @@ impl PluginService {
         Self {
             plugin_repo: PluginRepo::new(conn.clone()),
             setting_repo: SettingRepo::new(conn.clone()),
+            chat_model_repo: ChatModelRepo::new(conn.clone()),
+            backend_registry: BackendRegistry::default(),
             chat_stream_map: Arc::new(Mutex::new(HashMap::new())),
             conn,
         }
@@ -89,23 +93,35 @@ impl PluginService {
         Ok(())
     }
 
-    pub async fn send_message(&self, prompt: &str) -> Result<String> {
-        let user_message = OpenAIChatMessage {
-            role: OpenAIChatRole::User,
-            content: prompt.to_string(),
-        };
+    fn create_chat(
+        &self,
+        prompt: &str,
+        params: ChatParams,
+    ) -> Result<(Box<dyn ChatBackend>, ChatRequest)> {
+        // Plugins name models as chats do, chats default to the openai vendor
+        let chat_model = self
+            .chat_model_repo
+            .resolve(&params.model, params.model_id, "openai")?;
+        params.validate(&chat_model.vendor)?;
+        let params = chat_model.capabilities.0.adapt(params);
         let setting = self.setting_repo.select_by_user_id(Id::local())?;
-        let api = setting.create_openai_chat();
-        let api_params = OpenAIChatParams {
-            stream: true,
-            model: "gpt-3.5-turbo".to_string(),
-            messages: vec![user_message],
-            ..Default::default()
+        let backend = self
+            .backend_registry
+            .create(&chat_model, &chat_model.vendor, &setting)?;
+        let request = ChatRequest {
+            messages: vec![ChatMessage::new(Role::User, prompt)],
+            params,
         };
 
+        Ok((backend, request))
+    }
+
+    pub async fn send_message(&self, prompt: &str, params: ChatParams) -> Result<String> {
+        let (backend, request) = self.create_chat(prompt, params)?;
+
         let mut reply = Some(String::new());
         let mut error = Option::<String>::None;
-        let stream = api.send_message(api_params).await;
+        let stream = backend.send_message(request).await;
         match stream {
             Ok(mut stream) => {
                 while let Some(content) = stream.next().await {
@@ -114,6 +130,12 @@ impl PluginService {
                             Some(reply) => reply.push_str(data),
                             None => unreachable!(),
                         },
+                        // Plugins do not define tools
+                        StreamContent::ToolCall(_) => {}
+                        StreamContent::Reasoning(_)
+                        | StreamContent::Meta(_)
+                        | StreamContent::Retry(_)
+                        | StreamContent::Choice(_) => {}
                         StreamContent::Done => {
                             break;
                         }
@@ -134,26 +156,15 @@ impl PluginService {
         }
     }
 
-    pub async fn send_message_stream(&self, prompt: &str) -> Result<Id> {
-        let user_message = OpenAIChatMessage {
-            role: OpenAIChatRole::User,
-            content: prompt.to_string(),
-        };
-        let setting = self.setting_repo.select_by_user_id(Id::local())?;
-        let api = setting.create_openai_chat();
-        let api_params = OpenAIChatParams {
-            stream: true,
-            model: "gpt-3.5-turbo".to_string(),
-            messages: vec![user_message],
-            ..Default::default()
-        };
+    pub async fn send_message_stream(&self, prompt: &str, params: ChatParams) -> Result<Id> {
+        let (backend, request) = self.create_chat(prompt, params)?;
 
         let id = Id::random();
         let (sender, receiver) = tokio::sync::mpsc::channel::<StreamContent>(10);
         self.chat_stream_map.lock().await.insert(id, receiver);
 
         tokio::spawn(async move {
-            let stream = api.send_message(api_params).await;
+            let stream = backend.send_message(request).await;
             match stream {
                 Ok(mut stream) => {
                     while let Some(content) = stream.next().await {
@@ -161,10 +172,7 @@ impl PluginService {
                     }
                     drop(stream);
                 }
-                Err(err) => sender
-                    .send(StreamContent::Error(StreamError::Unknown(err.to_string())))
-                    .await
-                    .unwrap(),
+                Err(err) => sender.send(StreamContent::Error(err.into())).await.unwrap(),
             }
         });
 
@@ -184,7 +192,7 @@ impl PluginService {
     }
 
     pub async fn execute(&self, plugin: Plugin) -> Result<()> {
-        let state = RunningPluginState::new(self.clone());
+        let state = RunningPluginState::new(self.clone(), plugin.config.0.chat_params);
         let mut running_plugin = RunningPlugin::init(&plugin.code, state).await?;
         running_plugin.run().await?;
 
@@ -1,5 +1,7 @@
 use crate::database::pagination::PaginatedRecords;
-use crate::models::prompt::{NewPrompt, PatchPrompt, Prompt, PromptIndex};
+use crate::models::prompt::{
+    NewPrompt, PatchPrompt, Prompt, PromptIndex, PromptMessage, PromptVariable,
+};
 use crate::result::Result;
 use crate::types::{Id, PageQueryParams};
 use crate::{
@@ -49,8 +51,9 @@ impl PromptService {
         let prompt = NewPrompt {
             id,
             name: payload.name,
-            content: payload.content,
             user_id: payload.user_id,
+            messages: payload.messages.into(),
+            variables: payload.variables.into(),
         };
         self.prompt_repo.insert(&prompt)?;
 
@@ -61,7 +64,8 @@ impl PromptService {
         let prompt = PatchPrompt {
             id: payload.id,
             name: payload.name,
-            content: payload.content,
+            messages: payload.messages.map(Into::into),
+            variables: payload.variables.map(Into::into),
         };
 
         self.prompt_repo.update(&prompt)?;
@@ -69,6 +73,12 @@ impl PromptService {
         Ok(())
     }
 
+    pub fn get_prompt_variables(&self, prompt_id: Id) -> Result<Vec<PromptVariable>> {
+        let prompt = self.prompt_repo.select_by_id(prompt_id)?;
+
+        Ok(prompt.variables.0)
+    }
+
     pub fn delete_prompt(&self, prompt_id: Id) -> Result<()> {
         self.prompt_repo.delete_by_id(prompt_id)?;
         self.chat_repo.update_deleted_prompt(prompt_id)?;
@@ -106,7 +116,9 @@ impl<T: Default, U: Default> From<SearchPromptPayload> for PageQueryParams<T, U>
 #[serde(rename_all = "camelCase")]
 pub struct CreatePromptPayload {
     pub name: String,
-    pub content: String,
+    pub messages: Vec<PromptMessage>,
+    #[serde(default)]
+    pub variables: Vec<PromptVariable>,
     pub user_id: Id,
 }
 
@@ -115,5 +127,6 @@ pub struct CreatePromptPayload {
 pub struct UpdatePromptPayload {
     pub id: Id,
     pub name: Option<String>,
-    pub content: Option<String>,
+    pub messages: Option<Vec<PromptMessage>>,
+    pub variables: Option<Vec<PromptVariable>>,
 }
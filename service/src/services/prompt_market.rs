@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::time::Duration;
 
 use serde::Serialize;
@@ -8,7 +9,7 @@ use crate::{
         chat::ChatRepo, prompt::PromptRepo, prompt_source::PromptSourceRepo, setting::SettingRepo,
     },
     result::Result,
-    ChatConfig, DbConn, Id, NewChat, NewPrompt,
+    ChatConfig, DbConn, Id, NewChat, NewPrompt, PromptMessage, PromptVariable,
 };
 
 #[derive(Clone)]
@@ -60,9 +61,11 @@ impl PromptMarketService {
                 let prompts: Vec<serde_json::Value> = res.json().await?;
                 prompts
                     .into_iter()
-                    .map(|p| MarketPrompt {
-                        name: p["act"].as_str().unwrap_or("").to_string(),
-                        content: p["prompt"].as_str().unwrap_or("").to_string(),
+                    .map(|p| {
+                        MarketPrompt::new(
+                            p["act"].as_str().unwrap_or("").to_string(),
+                            p["prompt"].as_str().unwrap_or("").to_string(),
+                        )
                     })
                     .collect::<Vec<MarketPrompt>>()
             }
@@ -76,7 +79,7 @@ impl PromptMarketService {
                 for record in rdr.records().flatten() {
                     let name = record[0].to_string();
                     let content = record[1].to_string();
-                    let market_prompt = MarketPrompt { name, content };
+                    let market_prompt = MarketPrompt::new(name, content);
                     prompts.push(market_prompt);
                 }
                 prompts
@@ -92,8 +95,10 @@ impl PromptMarketService {
         let prompt = NewPrompt {
             id,
             name: payload.prompt.name,
-            content: payload.prompt.content,
             user_id: payload.user_id,
+            // Market prompts are plain instructions
+            messages: vec![PromptMessage::system(payload.prompt.content)].into(),
+            variables: payload.prompt.variables.into(),
         };
         self.prompt_repo.insert(&prompt)?;
 
@@ -104,8 +109,15 @@ impl PromptMarketService {
         &self,
         payload: InstallMarketPromptPayload,
     ) -> Result<(Id, Id)> {
+        // Neither the prompt nor the chat is created with missing variables
+        PromptVariable::fill(&payload.prompt.variables, &payload.variables)?;
+
         let name = payload.prompt.name.clone();
         let user_id = payload.user_id;
+        let config = ChatConfig {
+            variables: payload.variables.clone(),
+            ..Default::default()
+        };
         let prompt_id = self.install_market_prompt(payload)?;
 
         let min_sort = self.chat_repo.select_non_stick_min_order(user_id)?;
@@ -115,7 +127,7 @@ impl PromptMarketService {
             prompt_id: Some(prompt_id),
             user_id,
             title: name,
-            config: ChatConfig::default().into(),
+            config: config.into(),
             sort: min_sort - 1,
             ..Default::default()
         };
@@ -128,9 +140,25 @@ impl PromptMarketService {
 pub struct MarketPrompt {
     pub name: String,
     pub content: String,
+    /// The placeholders of the content
+    pub variables: Vec<PromptVariable>,
+}
+
+impl MarketPrompt {
+    pub fn new(name: String, content: String) -> Self {
+        let variables = PromptVariable::parse(&content);
+
+        Self {
+            name,
+            content,
+            variables,
+        }
+    }
 }
 
 pub struct InstallMarketPromptPayload {
     pub prompt: MarketPrompt,
+    /// Values of the variables of the prompt, for the created chat
+    pub variables: HashMap<String, String>,
     pub user_id: Id,
 }
@@ -1,8 +1,9 @@
 use serde::Deserialize;
 
+use crate::api::client::RetryPolicy;
 use crate::result::Result;
 use crate::{models::setting::Setting, repositories::setting::SettingRepo, DbConn, Id};
-use crate::{HomePage, PatchSetting, Theme};
+use crate::{AzureSetting, HomePage, LocalServer, PatchSetting, Theme};
 
 #[derive(Clone)]
 pub struct SettingService {
@@ -45,6 +46,14 @@ impl SettingService {
             hide_taskbar: payload.hide_taskbar,
             enable_web_server: payload.enable_web_server,
             home_page: payload.home_page.map(|h| h.into()),
+            anthropic_api_key: payload.anthropic_api_key,
+            anthropic_url: payload.anthropic_url,
+            local_servers: payload.local_servers.map(|l| l.into()),
+            azure: payload.azure.map(|a| a.into()),
+            gemini_api_key: payload.gemini_api_key,
+            gemini_url: payload.gemini_url,
+            retry_policy: payload.retry_policy.map(|r| r.into()),
+            auto_title: payload.auto_title,
         })?;
 
         Ok(())
@@ -66,4 +75,12 @@ pub struct UpdateSettingPayload {
     pub hide_taskbar: Option<bool>,
     pub enable_web_server: Option<bool>,
     pub home_page: Option<HomePage>,
+    pub anthropic_api_key: Option<String>,
+    pub anthropic_url: Option<String>,
+    pub local_servers: Option<Vec<LocalServer>>,
+    pub azure: Option<AzureSetting>,
+    pub gemini_api_key: Option<String>,
+    pub gemini_url: Option<String>,
+    pub retry_policy: Option<RetryPolicy>,
+    pub auto_title: Option<bool>,
 }
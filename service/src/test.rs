@@ -1,8 +1,15 @@
-use once_cell::sync::OnceCell;
+use std::time::Duration;
+
+use once_cell::sync::{Lazy, OnceCell};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::{Mutex, MutexGuard};
+use tokio::task::JoinHandle;
 
 use crate::{database::DbConn, init};
 
 static DB_CONN: OnceCell<DbConn> = OnceCell::new();
+static SETTING_LOCK: Lazy<Mutex<()>> = Lazy::new(Default::default);
 
 pub fn establish_connection() -> DbConn {
     DB_CONN
@@ -14,3 +21,92 @@ pub fn establish_connection() -> DbConn {
         })
         .clone()
 }
+
+/// Hold while a test changes the shared setting, e.g. its local servers.
+pub async fn lock_setting() -> MutexGuard<'static, ()> {
+    SETTING_LOCK.lock().await
+}
+
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub chunks: Vec<String>,
+}
+
+impl MockResponse {
+    /// A response whose body is written chunk by chunk, with a short pause
+    /// between chunks so that the client observes them separately.
+    pub fn new(status: u16, chunks: Vec<&str>) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            chunks: chunks.into_iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// Start a local HTTP server answering one connection per response, in order.
+///
+/// Returns the server's base url and a handle resolving to the raw requests
+/// the server received.
+pub async fn mock_server(responses: Vec<MockResponse>) -> (String, JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    let handle = tokio::spawn(async move {
+        let mut requests = vec![];
+        for response in responses {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            requests.push(read_request(&mut socket).await);
+
+            let mut head = format!("HTTP/1.1 {} MOCK\r\nconnection: close\r\n", response.status);
+            for (name, value) in &response.headers {
+                head.push_str(&format!("{name}: {value}\r\n"));
+            }
+            head.push_str("\r\n");
+            socket.write_all(head.as_bytes()).await.unwrap();
+
+            for chunk in response.chunks {
+                socket.write_all(chunk.as_bytes()).await.unwrap();
+                socket.flush().await.unwrap();
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            socket.shutdown().await.unwrap();
+        }
+        requests
+    });
+
+    (url, handle)
+}
+
+async fn read_request(socket: &mut tokio::net::TcpStream) -> String {
+    let mut data = vec![];
+    let mut buf = [0; 4096];
+    loop {
+        let size = socket.read(&mut buf).await.unwrap();
+        data.extend_from_slice(&buf[..size]);
+
+        let text = String::from_utf8_lossy(&data).to_string();
+        if let Some(index) = text.find("\r\n\r\n") {
+            let content_length = text[..index]
+                .lines()
+                .find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.eq_ignore_ascii_case("content-length")
+                        .then(|| value.trim().parse::<usize>().ok())?
+                })
+                .unwrap_or(0);
+            if data.len() >= index + 4 + content_length {
+                return text;
+            }
+        }
+        if size == 0 {
+            return text;
+        }
+    }
+}
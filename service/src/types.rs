@@ -10,7 +10,9 @@ use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 use uuid::{self, Uuid};
 
+use crate::api::client::RetryAttempt;
 use crate::error::StreamError;
+use crate::models::chat_log::{FinishReason, ToolCall};
 
 #[derive(
     Debug,
@@ -222,5 +224,35 @@ pub struct CursorQueryResult<T> {
 pub enum StreamContent {
     Error(StreamError),
     Data(String),
+    /// Reasoning of the model, streamed apart from the reply
+    Reasoning(String),
+    ToolCall(ToolCall),
+    Meta(StreamMeta),
+    /// The request failed and will be sent again
+    Retry(RetryAttempt),
+    /// Content of an additional reply when several are requested, the first one streams as `Data`
+    Choice(StreamChoice),
     Done,
 }
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct StreamChoice {
+    /// Starts from 1
+    pub index: usize,
+    pub data: String,
+}
+
+/// Sent once before `Done`, with whatever the provider reported.
+#[derive(serde::Serialize, Default, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamMeta {
+    pub finish_reason: Option<FinishReason>,
+    /// Finish reasons of the additional replies, by choice index minus 1
+    pub choice_finish_reasons: Vec<Option<FinishReason>>,
+    pub prompt_tokens: Option<usize>,
+    pub completion_tokens: Option<usize>,
+    /// The model which actually served the request
+    pub model: Option<String>,
+    /// Milliseconds between sending the request and the first content
+    pub time_to_first_token: Option<u64>,
+}
@@ -26,6 +26,10 @@ export interface ChatLog {
   finished: boolean;
   createdAt: string;
   updatedAt: string;
+  structured?: unknown;
+  reasoning?: string | null;
+  parentId?: string | null;
+  latency?: number | null;
 }
 
 export interface ChatUpdatePayload {
@@ -37,12 +41,24 @@ export interface ChatUpdatePayload {
 
 export interface ChatConfig {
   backtrack: number;
+  summarize: boolean;
+  variables: Record<string, string>;
   params: {
     model?: string;
+    modelId?: string;
     temperature?: number;
+    topP?: number;
+    maxTokens?: number;
     stop?: Array<string>;
     presencePenalty?: number;
     frequencyPenalty?: number;
+    seed?: number;
+    n?: number;
+    logitBias?: Record<string, number>;
+    responseFormat?:
+      | { type: "text" }
+      | { type: "jsonObject" }
+      | { type: "jsonSchema"; name: string; schema: object; strict?: boolean };
   };
 }
 
@@ -53,6 +69,12 @@ export interface ChatModel {
   price: number;
   unit: string;
   vendor: string;
+  capabilities: {
+    fixedSampling: boolean;
+    maxCompletionTokens: boolean;
+  };
+  // the model answering under the name, e.g. of an Azure deployment
+  servedModel: string | null;
 }
 
 export interface PromptIndex {
@@ -62,10 +84,24 @@ export interface PromptIndex {
   updatedAt: string;
 }
 
+export type PromptRole = "system" | "user" | "assistant";
+
+export interface PromptMessage {
+  role: PromptRole;
+  content: string;
+}
+
+export interface PromptVariable {
+  name: string;
+  default?: string;
+  description?: string;
+}
+
 export interface PromptData {
   id: string;
   name: string;
-  content: string;
+  messages: Array<PromptMessage>;
+  variables: Array<PromptVariable>;
   createdAt: string;
   updatedAt: string;
 }
@@ -81,12 +117,13 @@ export interface PromptMarketSource {
 export interface MarketPrompt {
   name: string;
   content: string;
+  variables: Array<PromptVariable>;
 }
 
 export type PromptUpdatePayload = {
   id: string;
   name?: string;
-  content?: string;
+  messages?: Array<PromptMessage>;
 };
 
 export interface Settings {
@@ -101,6 +138,7 @@ export interface Settings {
   hideMainWindow?: boolean;
   hideTaskbar?: boolean;
   homePage?: HomePage;
+  autoTitle?: boolean;
 }
 
 export enum HomePage {
@@ -169,6 +207,40 @@ export async function loadChatLogByCursor(params: {
   return res;
 }
 
+// title and snippet are escaped HTML, highlighted terms are wrapped in <mark></mark>
+// id and role are null when only the chat title matched
+export interface ChatLogHit {
+  id: string | null;
+  chatId: string;
+  role: "system" | "user" | "assistant" | "tool" | null;
+  title: string;
+  snippet: string;
+  createdAt: string;
+}
+
+export async function searchChatLogs(params: {
+  keyword: string;
+  chatId?: string;
+  page?: number;
+  perPage?: number;
+}) {
+  return execCommand<{
+    records: Array<ChatLogHit>;
+    total: number;
+    total_pages: number;
+    per_page: number;
+  }>("search_chat_logs", params);
+}
+
+// hits can be on inactive branches, so switch to the branch of the log before loading from it
+export async function openChatLogHit(hit: ChatLogHit, size: number) {
+  if (hit.id === null) {
+    return loadChatLogByCursor({ chatId: hit.chatId, size });
+  }
+  await switchChatBranch(hit.id);
+  return loadChatLogByCursor({ chatId: hit.chatId, cursor: hit.id, size });
+}
+
 export async function updateChat(payload: ChatUpdatePayload) {
   return execCommand<void>("update_chat", { payload });
 }
@@ -177,7 +249,11 @@ export async function removeChatPrompt(chatId: string) {
   return execCommand<void>("remove_chat_prompt", { id: chatId });
 }
 
-export async function newChat(params?: { promptId?: string; title?: string }) {
+export async function newChat(params?: {
+  promptId?: string;
+  title?: string;
+  variables?: Record<string, string>;
+}) {
   return execCommand<string>("new_chat", params);
 }
 
@@ -210,13 +286,25 @@ export async function moveNonStickChat(from: string, to: string) {
 }
 
 export async function updateChatLog(id: string, content: string) {
-  return execCommand<void>("update_chat_log", { id, content });
+  return execCommand<string>("update_chat_log", { id, content });
 }
 
 export async function deleteChatLog(logId: string) {
   return execCommand<void>("delete_chat_log", { logId });
 }
 
+export async function getChatLogBranches(id: string) {
+  return execCommand<Array<ChatLog>>("get_chat_log_branches", { id });
+}
+
+export async function switchChatBranch(id: string) {
+  return execCommand<string>("switch_chat_branch", { id });
+}
+
+export async function pickChatReply(id: string) {
+  return execCommand<string>("pick_chat_reply", { id });
+}
+
 export function sendMessage(chatId: string, message: string) {
   return execCommand<[string, string]>("send_message", { chatId, message });
 }
@@ -225,6 +313,22 @@ export function resendMessage(messageId: string) {
   return execCommand<[string, string]>("resend_message", { messageId });
 }
 
+export interface ArenaReply {
+  model: string;
+  replyId: string;
+}
+
+export function arenaMessage(
+  chatId: string,
+  message: string,
+  modelIds: Array<string>
+) {
+  return execCommand<{ messageId: string; replies: Array<ArenaReply> }>(
+    "arena_message",
+    { chatId, message, models: modelIds }
+  );
+}
+
 export function stopReply(messageId: string) {
   return execCommand<void>("stop_reply", { messageId });
 }
@@ -253,7 +357,10 @@ export function allPrompts() {
   return execCommand<Array<PromptIndex>>("all_prompts");
 }
 
-export function createPrompt(prompt: { name: string; content: string }) {
+export function createPrompt(prompt: {
+  name: string;
+  messages: Array<PromptMessage>;
+}) {
   return execCommand<string>("create_prompt", prompt);
 }
 
@@ -269,6 +376,10 @@ export function loadPrompt(id: string) {
   return execCommand<PromptData>("load_prompt", { id });
 }
 
+export function getPromptVariables(id: string) {
+  return execCommand<Array<PromptVariable>>("get_prompt_variables", { id });
+}
+
 export function getPromptSources() {
   return execCommand<Array<PromptMarketSource>>("get_prompt_sources");
 }
@@ -280,12 +391,20 @@ export function getPromptSourcePrompts(sourceId: string) {
 }
 
 export function installMarketPrompt(prompt: MarketPrompt) {
-  return execCommand<string>("install_market_prompt", { ...prompt });
+  return execCommand<string>("install_market_prompt", {
+    name: prompt.name,
+    content: prompt.content,
+  });
 }
 
-export function installMarketPromptAndCreateChat(prompt: MarketPrompt) {
+export function installMarketPromptAndCreateChat(
+  prompt: MarketPrompt,
+  variables?: Record<string, string>
+) {
   return execCommand<string>("install_market_prompt_and_create_chat", {
-    ...prompt,
+    name: prompt.name,
+    content: prompt.content,
+    variables,
   });
 }
 
@@ -0,0 +1,81 @@
+import { defineComponent, PropType, ref } from "vue";
+import { DataBarVertical20Regular as ArenaIcon } from "@vicons/fluent";
+import { NButton, NIcon, NSelect } from "naive-ui";
+import { Chat } from "../../models/chat";
+import { getChatModels } from "../../api";
+import { useI18n } from "../../hooks/i18n";
+import { dialog, message } from "../../utils/prompt";
+
+export default defineComponent({
+  props: {
+    chat: {
+      type: Object as PropType<Chat>,
+      required: true,
+    },
+    message: {
+      type: String,
+      default: "",
+    },
+    onSend: {
+      type: Function as PropType<(models: Array<string>) => void>,
+      required: true,
+    },
+  },
+  setup(props) {
+    const { t } = useI18n();
+
+    // ids of the chat models, kept between the rounds of a chat
+    const models = ref<Array<string>>([]);
+
+    async function arenaHandler() {
+      if (!props.message) {
+        return;
+      }
+      if (props.chat.busy) {
+        message.warning(t("chat.busy"));
+        return;
+      }
+
+      const options = (await getChatModels()).map((model) => ({
+        label: `${model.name} (${model.vendor})`,
+        value: model.id,
+      }));
+      if (!models.value.length) {
+        models.value = [props.chat.index.config.params.modelId].filter(
+          (id): id is string => !!id
+        );
+      }
+
+      dialog.create({
+        title: t("chat.arena.models"),
+        showIcon: false,
+        content() {
+          return (
+            <NSelect
+              multiple
+              filterable
+              options={options}
+              v-model:value={models.value}
+            ></NSelect>
+          );
+        },
+        positiveText: t("common.ok"),
+        negativeText: t("common.cancel"),
+        onPositiveClick() {
+          if (models.value.length) {
+            props.onSend(models.value);
+          }
+        },
+      });
+    }
+
+    return () => (
+      <NButton tertiary size="tiny" onClick={arenaHandler}>
+        <NIcon size={14}>
+          <ArenaIcon></ArenaIcon>
+        </NIcon>
+        <span class="ml-[.1rem]">{t("chat.arena")}</span>
+      </NButton>
+    );
+  },
+});
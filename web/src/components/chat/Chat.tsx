@@ -52,6 +52,13 @@ export default defineComponent({
       historyRef.value?.startAutoScroll();
     }
 
+    function arenaMessage(message: string, models: Array<string>) {
+      props.chat.arenaMessage(message, models, {
+        onFinish: () => historyRef.value?.stopAutoScroll(),
+      });
+      historyRef.value?.startAutoScroll();
+    }
+
     function resendMessage(id: string) {
       props.chat.resendMessage(id, {
         onFinish: () => historyRef.value?.stopAutoScroll(),
@@ -59,8 +66,19 @@ export default defineComponent({
       historyRef.value?.startAutoScroll();
     }
 
-    function updateMessage(id: string, content: string) {
-      props.chat.updateLog(id, content);
+    async function updateMessage(id: string, content: string) {
+      await props.chat.updateLog(id, content);
+      reload();
+    }
+
+    async function switchBranch(id: string) {
+      await props.chat.switchBranch(id);
+      reload();
+    }
+
+    async function pickReply(id: string) {
+      await props.chat.pickReply(id);
+      reload();
     }
 
     function focusInput() {
@@ -87,6 +105,8 @@ export default defineComponent({
           chat={props.chat}
           resendMessage={resendMessage}
           updateMessage={updateMessage}
+          switchBranch={switchBranch}
+          pickReply={pickReply}
           deleteMessage={props.chat.deleteLog.bind(props.chat)}
           stopReply={props.chat.stopReply.bind(props.chat)}
         ></History>
@@ -95,6 +115,7 @@ export default defineComponent({
           ref={userInputRef}
           chat={props.chat}
           sendMessage={sendMessage}
+          arenaMessage={arenaMessage}
           onMessage={props.onMessage}
         ></UserInput>
       </div>
@@ -19,11 +19,12 @@ import {
   NInputNumber,
   NScrollbar,
   NSelect,
+  NSwitch,
   NTooltip,
 } from "naive-ui";
 import { Chat } from "../../models/chat";
 import { useI18n } from "../../hooks/i18n";
-import { getChatModels, updateChat } from "../../api";
+import { ChatModel, getChatModels, updateChat } from "../../api";
 import { useAsyncData } from "../../hooks/asyncData";
 
 export default defineComponent({
@@ -61,6 +62,26 @@ export default defineComponent({
 
     const drawerShown = ref(false);
 
+    // models of several vendors or servers may share a name, the id tells them apart
+    const chatModels = useAsyncData<Array<ChatModel>>(getChatModels, []);
+
+    watch(
+      [chatModels, () => props.chat],
+      () => {
+        const params = props.chat.index.config.params;
+        if (params.modelId) {
+          return;
+        }
+        const named = chatModels.value.filter((m) => m.name === params.model);
+        const chatModel =
+          named.find((m) => m.vendor === props.chat.index.vendor) ?? named[0];
+        if (chatModel) {
+          params.modelId = chatModel.id;
+        }
+      },
+      { immediate: true }
+    );
+
     const configs = computed<
       Array<{
         type: "number" | "select" | "input" | "dynamicTags";
@@ -68,6 +89,7 @@ export default defineComponent({
         path: keyof typeof props.chat.index.config.params;
         tooltip: string;
         options?: Ref<{ label?: string; value: string }[]>;
+        onUpdate?: (value: string) => void;
         precision?: number;
         max?: number;
         min?: number;
@@ -77,14 +99,20 @@ export default defineComponent({
       {
         type: "select",
         label: t("chat.config.model"),
-        path: "model",
+        path: "modelId",
         tooltip: t("chat.config.model.hint"),
-        options: useAsyncData(async () => {
-          const models = await getChatModels();
-          return models.map((model) => ({
-            value: model.name,
-          }));
-        }, []),
+        options: computed(() =>
+          chatModels.value.map((model) => ({
+            label: `${model.name} (${model.vendor})`,
+            value: model.id,
+          }))
+        ),
+        onUpdate(value) {
+          const chatModel = chatModels.value.find((m) => m.id === value);
+          if (chatModel) {
+            props.chat.index.config.params.model = chatModel.name;
+          }
+        },
       },
       {
         type: "number",
@@ -96,6 +124,41 @@ export default defineComponent({
         precision: 1,
         step: 0.1,
       },
+      {
+        type: "number",
+        label: t("chat.config.topP"),
+        path: "topP",
+        tooltip: t("chat.config.topP.hint"),
+        min: 0,
+        max: 1,
+        precision: 2,
+        step: 0.05,
+      },
+      {
+        type: "number",
+        label: t("chat.config.maxTokens"),
+        path: "maxTokens",
+        tooltip: t("chat.config.maxTokens.hint"),
+        min: 1,
+        precision: 0,
+        step: 1,
+      },
+      {
+        type: "number",
+        label: t("chat.config.n"),
+        path: "n",
+        tooltip: t("chat.config.n.hint"),
+        min: 1,
+        max: 8,
+        precision: 0,
+        step: 1,
+      },
+      {
+        type: "dynamicTags",
+        label: t("chat.config.stop"),
+        path: "stop",
+        tooltip: t("chat.config.stop.hint"),
+      },
       {
         type: "number",
         label: t("chat.config.presencePenalty"),
@@ -180,6 +243,7 @@ export default defineComponent({
                               v-model:value={
                                 props.chat.index.config.params[config.path]
                               }
+                              onUpdateValue={config.onUpdate}
                               options={config.options!.value.map((item) => {
                                 return {
                                   key: item.value,
@@ -204,6 +268,30 @@ export default defineComponent({
                   }}
                 </NFormItem>
               ))}
+              <NFormItem>
+                {{
+                  label: () => (
+                    <div class="flex items-center">
+                      {t("chat.config.summarize")}
+                      <NTooltip>
+                        {{
+                          trigger: () => (
+                            <NIcon class="ml-1" size={18}>
+                              <InfoIcon />
+                            </NIcon>
+                          ),
+                          default: () => t("chat.config.summarize.hint"),
+                        }}
+                      </NTooltip>
+                    </div>
+                  ),
+                  default: () => (
+                    <NSwitch
+                      v-model:value={props.chat.index.config.summarize}
+                    ></NSwitch>
+                  ),
+                }}
+              </NFormItem>
             </NForm>
           </NScrollbar>
         </NDrawer>
@@ -51,7 +51,10 @@ export default defineComponent({
                         {props.chat.prompt?.name}
                       </NTag>
                     ),
-                    default: () => props.chat.prompt?.content,
+                    default: () =>
+                      props.chat.prompt?.messages
+                        .map((message) => message.content)
+                        .join("\n\n"),
                   }}
                 </NTooltip>
               ) : null}
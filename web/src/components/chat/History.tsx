@@ -1,6 +1,9 @@
 import {
   NButton,
   NButtonGroup,
+  NCollapse,
+  NCollapseItem,
+  NDropdown,
   NIcon,
   NInput,
   NPopconfirm,
@@ -25,6 +28,7 @@ import { useLazyLoad } from "../../hooks/lazyLoad";
 import { useAutoScroll, useScroll } from "../../hooks/scroll";
 import { Chat } from "../../models/chat";
 import {
+  ArenaMessage,
   AssistantMessage,
   ErrorMessage,
   Message,
@@ -40,6 +44,7 @@ import {
   Checkmark20Regular as ConfirmIcon,
   Send20Regular as ResendIcon,
   DocumentEdit20Regular as EditIcon,
+  BranchFork20Regular as BranchIcon,
 } from "@vicons/fluent";
 import { dialog } from "../../utils/prompt";
 import ListTransition from "../listTransition/listTransition";
@@ -59,6 +64,12 @@ export default defineComponent({
     updateMessage: {
       type: Function as PropType<(messageId: string, content: string) => void>,
     },
+    switchBranch: {
+      type: Function as PropType<(messageId: string) => void>,
+    },
+    pickReply: {
+      type: Function as PropType<(messageId: string) => void>,
+    },
     stopReply: {
       type: Function as PropType<() => void>,
     },
@@ -203,9 +214,52 @@ export default defineComponent({
         return renderUserMessage(message);
       } else if (message instanceof ErrorMessage) {
         return renderErrorMessage(message);
+      } else if (message instanceof ArenaMessage) {
+        return renderArenaMessage(message);
       }
     }
 
+    // one column per model, the promoted reply replaces them in the history
+    function renderArenaMessage(msg: ArenaMessage) {
+      return (
+        <div
+          class="grid gap-2 px-4 pb-4"
+          style={{
+            gridTemplateColumns: `repeat(${msg.replies.length}, minmax(0, 1fr))`,
+          }}
+        >
+          {msg.replies.map((reply) => (
+            <div class="flex flex-col items-start overflow-hidden">
+              <div class="flex items-center gap-2 ml-2 mb-1 text-xs opacity-70">
+                <span>{reply.model}</span>
+                {msg.done && !reply.error ? (
+                  <NButton
+                    text
+                    size="tiny"
+                    type="primary"
+                    onClick={() => props.pickReply?.(reply.message.id)}
+                  >
+                    {t("chat.arena.promote")}
+                  </NButton>
+                ) : null}
+              </div>
+              {reply.error ? (
+                renderErrorMessage(reply.error)
+              ) : (
+                <>
+                  {renderReasoning(reply.message)}
+                  <div
+                    class="markdown-root assistant-msg inline-block px-3 ml-2 rounded-t-xl rounded-r-xl z-1"
+                    v-html={renderMarkdown(reply.message.content)}
+                  ></div>
+                </>
+              )}
+            </div>
+          ))}
+        </div>
+      );
+    }
+
     function renderAssistantMessage(msg: AssistantMessage) {
       let content = msg.content;
       const codeBlockAssignNum = msg.content.split("```").length - 1;
@@ -219,10 +273,14 @@ export default defineComponent({
           class="relative flex justify-start items-start px-4 pb-4 group"
           id={`assistant-${msg.id}`}
         >
-          <div
-            class="markdown-root assistant-msg inline-block px-3 ml-2 rounded-t-xl rounded-r-xl z-1"
-            v-html={html}
-          ></div>
+          <div class="flex flex-col items-start">
+            {renderReasoning(msg)}
+            <div
+              class="markdown-root assistant-msg inline-block px-3 ml-2 rounded-t-xl rounded-r-xl z-1"
+              v-html={html}
+            ></div>
+            {renderAlternatives(msg)}
+          </div>
           {msg.done ? (
             <div
               class="group-hover:grid w-full gap-1 hidden absolute bottom-[-.6rem] left-5 text-xs"
@@ -232,6 +290,7 @@ export default defineComponent({
                 {renderDeleteMessageButton(msg.id)}
                 {renderEditMessageButton(msg)}
                 {renderCopyMessageButton(msg.content)}
+                {renderBranchesButton(msg.id, props.pickReply)}
               </NButtonGroup>
             </div>
           ) : null}
@@ -239,6 +298,41 @@ export default defineComponent({
       );
     }
 
+    // collapsed by default, reasoning is never sent back as context
+    function renderReasoning(msg: AssistantMessage) {
+      if (!msg.reasoning) {
+        return null;
+      }
+      return (
+        <NCollapse class="ml-2 mb-1 text-xs">
+          <NCollapseItem title={t("chat.message.reasoning")} name="reasoning">
+            <div class="whitespace-pre-wrap opacity-70">{msg.reasoning}</div>
+          </NCollapseItem>
+        </NCollapse>
+      );
+    }
+
+    // replies streamed besides the first one, pickable from the branches once done
+    function renderAlternatives(msg: AssistantMessage) {
+      if (msg.done || !msg.alternatives.length) {
+        return null;
+      }
+      return (
+        <NCollapse class="ml-2 mt-1 text-xs">
+          <NCollapseItem
+            title={t("chat.message.alternatives")}
+            name="alternatives"
+          >
+            {msg.alternatives.map((alternative) => (
+              <div class="whitespace-pre-wrap opacity-70 mb-2">
+                {alternative}
+              </div>
+            ))}
+          </NCollapseItem>
+        </NCollapse>
+      );
+    }
+
     function renderUserMessage(msg: UserMessage) {
       const html = renderMarkdown(msg.content);
       return (
@@ -259,6 +353,7 @@ export default defineComponent({
             {renderEditMessageButton(msg)}
             {renderCopyMessageButton(msg.content)}
             {renderResendMessageButton(msg.id)}
+            {renderBranchesButton(msg.id, props.switchBranch)}
           </div>
         </div>
       );
@@ -285,6 +380,9 @@ export default defineComponent({
                   const error = message.error.error;
                   return error.message ?? error.type;
                 }
+                case "schemaViolation": {
+                  return message.error.error;
+                }
               }
             })()}
           </div>
@@ -462,6 +560,52 @@ export default defineComponent({
       );
     }
 
+    // Only one branch list is shown at a time
+    const branches = ref<Array<ChatLog>>([]);
+
+    function renderBranchesButton(
+      id: string,
+      select?: (messageId: string) => void
+    ) {
+      const options = branches.value.map((log, index) => ({
+        key: log.id,
+        label: `${index + 1}. ${log.message.slice(0, 40)}`,
+        disabled: log.id === id,
+      }));
+
+      return (
+        <NDropdown
+          trigger="click"
+          placement="bottom-start"
+          options={options}
+          onUpdateShow={async (show: boolean) => {
+            if (show) {
+              branches.value = await props.chat.getBranches(id);
+            }
+          }}
+          onSelect={(key: string) => select?.(key)}
+        >
+          <NTooltip placement="bottom" delay={500}>
+            {{
+              trigger: () => (
+                <NButton
+                  type="default"
+                  text
+                  size="tiny"
+                  class="text-gray-500"
+                >
+                  <NIcon size="1.2rem">
+                    <BranchIcon />
+                  </NIcon>
+                </NButton>
+              ),
+              default: () => t("chat.message.branches"),
+            }}
+          </NTooltip>
+        </NDropdown>
+      );
+    }
+
     return (() => (
       <div
         class="flex-1 flex flex-col overflow-hidden relative"
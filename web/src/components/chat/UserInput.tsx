@@ -11,6 +11,7 @@ import { usePromptService } from "../../services/prompt";
 import { PromptIndex } from "../../api";
 import CommandPanel from "./CommandPanel";
 import Export from "./Export";
+import Arena from "./Arena";
 import { useInput } from "../../hooks/input";
 
 function useHistoryNavigation(chat: Chat) {
@@ -68,6 +69,11 @@ export default defineComponent({
       type: Function as PropType<(message: string) => void>,
       required: true,
     },
+    arenaMessage: {
+      type: Function as PropType<
+        (message: string, models: Array<string>) => void
+      >,
+    },
     onMessage: {
       type: Function as PropType<(message: Message) => void>,
     },
@@ -258,7 +264,15 @@ export default defineComponent({
         <div class="flex items-center h-8">
           <Cost class="pl-2 text-xs" value={props.chat.index.cost}></Cost>
           <Backtrack class="ml-2" chat={props.chat}></Backtrack>
-          <div class="flex-1 flex justify-end py-1 px-2">
+          <div class="flex-1 flex justify-end gap-2 py-1 px-2">
+            <Arena
+              chat={props.chat}
+              message={userMessage.value}
+              onSend={(models) => {
+                props.arenaMessage?.(userMessage.value, models);
+                setUserMessage("");
+              }}
+            ></Arena>
             <Export chat={props.chat}></Export>
           </div>
         </div>
@@ -36,6 +36,9 @@ const messages: Messages = {
   "chat.message.delete": "удалить",
   "chat.message.delete.hint": "Вы уверены, что хотите удалить это сообщение?",
   "chat.message.stopReply": "Перестать отвечать",
+  "chat.message.reasoning": "Рассуждение",
+  "chat.message.branches": "Ветки",
+  "chat.message.alternatives": "Варианты",
 
   "chat.prompt.changed": "Подсказка изменена на: {name}",
 
@@ -44,6 +47,9 @@ const messages: Messages = {
   "chat.config.maxBacktrack": "Максимальный возврат",
   "chat.config.maxBacktrack.hint":
     "Максимальное количество возвратов, 0 означает отсутствие ограничений",
+  "chat.config.summarize": "Резюмировать",
+  "chat.config.summarize.hint":
+    "Сжимать сообщения, не вошедшие в контекст, в резюме, отправляемое вместо них",
   "chat.config.temperature": "Температура",
   "chat.config.temperature.hint":
     "Какую температуру выборки использовать, от 0 до 2. Более высокие значения, такие как 0,8, сделают вывод более случайным, а более низкие значения, такие как 0,2, сделают его более сфокусированным и детерминированным.",
@@ -67,6 +73,9 @@ const messages: Messages = {
     "Число от -2.0 до 2.0. Положительные значения штрафуют новые токены в зависимости от их текущей частоты в тексте, уменьшая вероятность того, что модель дословно повторит одну и ту же строку.",
 
   "chat.export": "Экспорт",
+  "chat.arena": "Арена",
+  "chat.arena.models": "Модели",
+  "chat.arena.promote": "Выбрать",
 
   "chatModel.new": "Новая модель",
   "chatModel.models": "Модели",
@@ -80,8 +89,13 @@ const messages: Messages = {
   "prompt.new": "Новая подсказка",
   "prompt.prompts": "Подсказки",
   "prompt.inputNameHint": "Введите название подсказки",
+  "prompt.inputVariableHint": "Пожалуйста, введите {name}",
   "prompt.newChat": "Новый чат",
   "prompt.rename": "Переименовать",
+  "prompt.addMessage": "Добавить сообщение",
+  "prompt.role.system": "Система",
+  "prompt.role.user": "Пользователь",
+  "prompt.role.assistant": "Ассистент",
   "prompt.update.success": "Приглашение успешно обновлено",
 
   "prompt.market.prompts": "Рынок подсказок",
@@ -121,6 +135,7 @@ const messages: Messages = {
   "setting.enableWebServer": "Включить веб-сервер",
   "setting.hideTaskbar": "Скрыть панель задач",
   "setting.hideMainWindow": "Скрыть главное окно",
+  "setting.autoTitle": "Названия новых чатов",
   "setting.needRestart.hint":
     "Следующие настройки вступят в силу после перезапуска приложения",
   "setting.homePage": "Домашняя страница",
@@ -37,6 +37,9 @@ const messages: Messages = {
   "chat.message.delete": "delete",
   "chat.message.delete.hint": "Are you sure to delete this message?",
   "chat.message.stopReply": "Stop replying",
+  "chat.message.reasoning": "Reasoning",
+  "chat.message.branches": "Branches",
+  "chat.message.alternatives": "Alternatives",
 
   "chat.prompt.changed": "Prompt changed to: {name}",
 
@@ -44,6 +47,9 @@ const messages: Messages = {
   "chat.config.model.hint": "ID of the model to use.",
   "chat.config.maxBacktrack": "Max Backtrack",
   "chat.config.maxBacktrack.hint": "Max backtrack count, 0 means no limit",
+  "chat.config.summarize": "Summarize",
+  "chat.config.summarize.hint":
+    "Condense the messages left out of the context into a summary sent in their place",
   "chat.config.temperature": "Temperature",
   "chat.config.temperature.hint":
     "What sampling temperature to use, between 0 and 2. Higher values like 0.8 will make the output more random, while lower values like 0.2 will make it more focused and deterministic.",
@@ -67,6 +73,9 @@ const messages: Messages = {
     "Number between -2.0 and 2.0. Positive values penalize new tokens based on their existing frequency in the text so far, decreasing the model's likelihood to repeat the same line verbatim.",
 
   "chat.export": "Export",
+  "chat.arena": "Arena",
+  "chat.arena.models": "Models",
+  "chat.arena.promote": "Promote",
 
   "chatModel.new": "New Model",
   "chatModel.models": "Models",
@@ -80,8 +89,13 @@ const messages: Messages = {
   "prompt.new": "New Prompt",
   "prompt.prompts": "Prompts",
   "prompt.inputNameHint": "Please input prompt name",
+  "prompt.inputVariableHint": "Please input {name}",
   "prompt.newChat": "New Chat",
   "prompt.rename": "Rename",
+  "prompt.addMessage": "Add Message",
+  "prompt.role.system": "System",
+  "prompt.role.user": "User",
+  "prompt.role.assistant": "Assistant",
   "prompt.update.success": "Prompt updated successfully",
 
   "prompt.market.prompts": "Prompts Market",
@@ -120,6 +134,7 @@ const messages: Messages = {
   "setting.enableWebServer": "Enable Web Server",
   "setting.hideTaskbar": "Hide Taskbar",
   "setting.hideMainWindow": "Hide Main Window",
+  "setting.autoTitle": "Title New Chats",
   "setting.needRestart.hint":
     "The following settings will take effect after restarting the app",
   "setting.homePage": "Home Page",
@@ -36,6 +36,9 @@ const messages: Messages = {
   "chat.message.delete": "删除",
   "chat.message.delete.hint": "确定要删除此消息吗？",
   "chat.message.stopReply": "停止回复",
+  "chat.message.reasoning": "思考过程",
+  "chat.message.branches": "分支",
+  "chat.message.alternatives": "候选回复",
 
   "chat.prompt.changed": "提示词更换为: {name}",
 
@@ -43,6 +46,8 @@ const messages: Messages = {
   "chat.config.model.hint": "要使用的模型的ID。",
   "chat.config.maxBacktrack": "最大回溯",
   "chat.config.maxBacktrack.hint": "最大回溯次数，0 表示无限制。",
+  "chat.config.summarize": "总结",
+  "chat.config.summarize.hint": "将超出上下文的消息压缩为摘要代替它们发送",
   "chat.config.temperature": "温度",
   "chat.config.temperature.hint":
     "使用什么取样温度，0到2之间。较高的值(如0.8)将使输出更加随机，而较低的值(如0.2)将使输出更加集中和确定。",
@@ -63,6 +68,9 @@ const messages: Messages = {
     "介于-2.0和2.0之间的数字。正值会根据新符号在文本中的现有频率来惩罚它们，从而降低模型逐字重复同一行的可能性。",
 
   "chat.export": "导出",
+  "chat.arena": "竞技场",
+  "chat.arena.models": "模型",
+  "chat.arena.promote": "采用",
 
   "chatModel.new": "新建模型",
   "chatModel.models": "模型列表",
@@ -76,8 +84,13 @@ const messages: Messages = {
   "prompt.new": "新建提示词",
   "prompt.prompts": "提示词列表",
   "prompt.inputNameHint": "请输入提示词标题",
+  "prompt.inputVariableHint": "请输入 {name}",
   "prompt.newChat": "新建对话",
   "prompt.rename": "重命名",
+  "prompt.addMessage": "添加消息",
+  "prompt.role.system": "系统",
+  "prompt.role.user": "用户",
+  "prompt.role.assistant": "助手",
   "prompt.update.success": "更新成功",
 
   "prompt.market.prompts": "提示词市场",
@@ -116,6 +129,7 @@ const messages: Messages = {
   "setting.enableWebServer": "启用 Web 服务",
   "setting.hideTaskbar": "隐藏任务栏图标",
   "setting.hideMainWindow": "隐藏主窗口",
+  "setting.autoTitle": "自动生成标题",
   "setting.needRestart.hint": "下面的设置需要重启应用才能生效",
   "setting.homePage": "主页",
 };
@@ -1,5 +1,6 @@
 import { computed, reactive, shallowReactive, watch } from "vue";
 import {
+  arenaMessage,
   resendMessage,
   sendMessage,
   ChatIndex,
@@ -8,12 +9,16 @@ import {
   updateChat,
   updateChatLog,
   deleteChatLog,
+  getChatLogBranches,
+  switchChatBranch,
+  pickChatReply,
   loadChatLogByCursor,
   stopReply,
   removeChatPrompt,
   PromptData,
 } from "../api";
 import {
+  ArenaMessage,
   AssistantMessage,
   ErrorMessage,
   Message,
@@ -55,6 +60,13 @@ export class Chat {
       }
     }).then((unlisten) => this.destroyCallbacks.push(unlisten));
 
+    listen("chat-updated", (event) => {
+      const { id, title } = event.payload as { id: string; title: string };
+      if (this.index.id === id) {
+        this.index.title = title;
+      }
+    }).then((unlisten) => this.destroyCallbacks.push(unlisten));
+
     return chat;
   }
 
@@ -91,7 +103,11 @@ export class Chat {
         return msg;
       }
       case "assistant": {
-        const msg = new AssistantMessage(log.id, log.message);
+        const msg = new AssistantMessage(
+          log.id,
+          log.message,
+          log.reasoning ?? ""
+        );
         msg.markHistory();
         return msg;
       }
@@ -101,12 +117,89 @@ export class Chat {
     }
   }
 
+  /**
+   * The edit becomes a new branch, so the logs following it need reloading
+   */
   async updateLog(logId: string, content: string) {
-    await updateChatLog(logId, content);
-    const msg = this.messages.find((item) => item.id === logId);
-    if (msg) {
-      msg.content = content;
+    return updateChatLog(logId, content);
+  }
+
+  async getBranches(logId: string) {
+    return getChatLogBranches(logId);
+  }
+
+  async switchBranch(logId: string) {
+    return switchChatBranch(logId);
+  }
+
+  async pickReply(logId: string) {
+    return pickChatReply(logId);
+  }
+
+  /**
+   * Send the message to several models side by side, their replies join the
+   * history once one of them is picked
+   */
+  async arenaMessage(
+    message: string,
+    modelIds: Array<string>,
+    params?: { onFinish?: () => void }
+  ) {
+    const userMessage = reactive(new UserMessage(message));
+    this.messages.push(userMessage);
+
+    const { messageId, replies } = await arenaMessage(
+      this.index.id,
+      message,
+      modelIds
+    );
+    userMessage.setId(messageId);
+
+    const arena = reactive(new ArenaMessage(replies));
+    this.messages.push(arena);
+
+    this.busy = true;
+    const finish = () => {
+      if (arena.done) {
+        this.busy = false;
+        userMessage.finished = true;
+        params?.onFinish?.();
+        getChat(this.index.id).then((newChat) => {
+          Object.assign(this.index, newChat);
+        });
+      }
+    };
+    for (const reply of arena.replies) {
+      const unListen = await listen(reply.message.id, (event) => {
+        const chunk = event.payload as MessageChunk;
+        userMessage.delivered = true;
+
+        switch (chunk.type) {
+          case "error": {
+            reply.error = new ErrorMessage(chunk.data);
+            unListen();
+            finish();
+            break;
+          }
+          case "data": {
+            reply.message.appendContent(chunk.data);
+            break;
+          }
+          case "reasoning": {
+            reply.message.appendReasoning(chunk.data);
+            break;
+          }
+          case "done": {
+            reply.message.markHistory();
+            unListen();
+            finish();
+            break;
+          }
+        }
+      });
     }
+
+    return messageId;
   }
 
   async clear() {
@@ -116,6 +209,19 @@ export class Chat {
 
   async stopReply() {
     this.busy = false;
+
+    // Arena replies are stopped one by one
+    const last = this.messages[this.messages.length - 1];
+    if (last instanceof ArenaMessage) {
+      for (const reply of last.replies) {
+        if (!reply.message.done && !reply.error) {
+          await stopReply(reply.message.id);
+          reply.message.markHistory();
+        }
+      }
+      return;
+    }
+
     let user_message_id = this.messages.findLast(
       (item) => item instanceof UserMessage
     )?.id;
@@ -216,27 +322,18 @@ export class Chat {
 
     const userMessage = this.messages[index] as UserMessage;
 
-    if (
-      index === this.messages.length - 1 ||
-      (index === this.messages.length - 2 &&
-        this.messages[index + 1] instanceof ErrorMessage)
-    ) {
-      // We can reuse the message if it is the last message
-      // or is the second last message and the last message is an error message
-
-      this.messages.length = index + 1;
+    // The message is resent as a new branch, the logs following it
+    // stay on the previous branch. An edited message never replied to
+    // is answered in place and keeps its id
+    this.messages.length = index + 1;
 
-      userMessage.delivered = false;
-      userMessage.finished = null;
+    userMessage.delivered = false;
+    userMessage.finished = null;
 
-      const [newMessageId, replyId] = await resendMessage(userMessage.id);
-      userMessage.id = newMessageId;
+    const [newMessageId, replyId] = await resendMessage(userMessage.id);
+    userMessage.id = newMessageId;
 
-      this.__receiveAssistantMessage(this, userMessage, replyId, params);
-    } else {
-      // Otherwise, we need to create a new message
-      this.sendMessage(userMessage.content, params);
-    }
+    this.__receiveAssistantMessage(this, userMessage, replyId, params);
   }
 
   async updateBacktrack(backtrack: number) {
@@ -284,6 +381,16 @@ export class Chat {
           userMessage.delivered = true;
           break;
         }
+        case "reasoning": {
+          assistantMessage.appendReasoning(chunk.data);
+          userMessage.delivered = true;
+          break;
+        }
+        case "choice": {
+          assistantMessage.appendAlternative(chunk.data.index, chunk.data.data);
+          userMessage.delivered = true;
+          break;
+        }
         case "done": {
           assistantMessage.markHistory();
           this.busy = false;
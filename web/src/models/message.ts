@@ -39,10 +39,30 @@ export class AssistantMessage extends Message {
   cachedContent = "";
   leading = true;
 
-  constructor(id: string, content: string) {
+  // reasoning streamed before the reply
+  reasoning = "";
+
+  // additional replies when several are requested, kept as branches once done
+  alternatives: Array<string> = [];
+
+  constructor(id: string, content: string, reasoning = "") {
     super();
     this.id = id;
     this.content = content;
+    this.reasoning = reasoning;
+  }
+
+  appendReasoning(reasoning: string) {
+    this.reasoning += reasoning;
+    return this;
+  }
+
+  appendAlternative(index: number, content: string) {
+    while (this.alternatives.length < index) {
+      this.alternatives.push("");
+    }
+    this.alternatives[index - 1] += content;
+    return this;
   }
 
   appendContent(content: string) {
@@ -77,6 +97,27 @@ export class AssistantMessage extends Message {
   }
 }
 
+// replies of several models to the same message, kept out of the history until one is promoted
+export class ArenaMessage extends Message {
+  replies: Array<{
+    model: string;
+    message: AssistantMessage;
+    error?: ErrorMessage;
+  }>;
+
+  constructor(replies: Array<{ model: string; replyId: string }>) {
+    super();
+    this.replies = replies.map(({ model, replyId }) => ({
+      model,
+      message: new AssistantMessage(replyId, ""),
+    }));
+  }
+
+  get done() {
+    return this.replies.every((reply) => reply.message.done || reply.error);
+  }
+}
+
 export class ErrorMessage extends Message {
   error: ErrorData;
   constructor(error: ErrorData) {
@@ -94,6 +135,14 @@ export type MessageChunk =
       type: "data";
       data: string;
     }
+  | {
+      type: "reasoning";
+      data: string;
+    }
+  | {
+      type: "choice";
+      data: { index: number; data: string };
+    }
   | {
       type: "done";
     };
@@ -111,5 +160,11 @@ export type ErrorData =
       error: {
         type: string;
         message?: string;
+        status?: number;
+        requestId?: string;
       };
+    }
+  | {
+      type: "schemaViolation";
+      error: string;
     };
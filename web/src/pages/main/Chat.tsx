@@ -1,4 +1,4 @@
-import { computed, defineComponent, ref, shallowReactive } from "vue";
+import { computed, defineComponent, onUnmounted, ref, shallowReactive } from "vue";
 import ChatComp from "../../components/chat/Chat";
 import * as api from "../../api";
 import { Chat } from "../../models/chat";
@@ -10,6 +10,7 @@ import { useI18n } from "../../hooks/i18n";
 import { prompt } from "../../utils/prompt";
 import Explorer, { ExplorerItem } from "../../components/Explorer";
 import { useChatService } from "../../services/chat";
+import { listen } from "../../utils/api";
 
 export default defineComponent({
   name: "ChatPage",
@@ -62,6 +63,10 @@ export default defineComponent({
       () => allChats.value.find((m) => m.id === currentChat.value?.index.id)!
     );
 
+    // titles generated after the first exchange
+    const unlisten = listen("chat-updated", () => reload());
+    onUnmounted(() => unlisten.then((f) => f()));
+
     load().then(() => {
       if (route.query.id) {
         selectHandler(route.query.id as string);
@@ -1,8 +1,8 @@
 import { computed, defineComponent, ref, watch } from "vue";
 import * as api from "../../api";
-import { message, prompt } from "../../utils/prompt";
-import { Plus as PlusIcon } from "@vicons/fa";
-import { NIcon, NScrollbar } from "naive-ui";
+import { message, prompt, promptVariables } from "../../utils/prompt";
+import { Plus as PlusIcon, Times as TimesIcon } from "@vicons/fa";
+import { NButton, NIcon, NScrollbar, NSelect } from "naive-ui";
 import { useRouter } from "vue-router";
 import { useI18n } from "../../hooks/i18n";
 import Explorer, { ExplorerItem } from "../../components/Explorer";
@@ -53,14 +53,21 @@ export default defineComponent({
         immediate: true,
       }
     );
-    const currentPromptInitialContent = ref<string>();
+    const currentPromptInitialMessages = ref<string>();
+
+    const roleOptions = computed(() =>
+      (["system", "user", "assistant"] as const).map((role) => ({
+        label: t(`prompt.role.${role}`),
+        value: role,
+      }))
+    );
 
     async function createPrompt() {
       prompt(t("prompt.inputNameHint"), {
         async okHandler(title) {
           const id = await api.createPrompt({
             name: title,
-            content: "",
+            messages: [{ role: "system", content: "" }],
           });
           await reload();
           selectHandler(id)
@@ -107,9 +114,13 @@ export default defineComponent({
     }
 
     async function newChatHandler(id: string, act: string) {
+      const variables = await promptVariables(
+        await api.getPromptVariables(id)
+      );
       const chatId = await api.newChat({
         promptId: id,
         title: act,
+        variables,
       });
       router.push({
         name: "chat",
@@ -124,18 +135,16 @@ export default defineComponent({
         return;
       }
 
-      if (
-        currentPromptInitialContent.value === currentPromptData.value?.content
-      ) {
+      const messages = currentPromptData.value?.messages ?? [];
+      if (currentPromptInitialMessages.value === JSON.stringify(messages)) {
         return;
       }
 
       await api.updatePrompt({
         id: currentPromptIndex.value!.id,
-        content: currentPromptData.value?.content ?? "",
+        messages,
       });
-      currentPromptInitialContent.value =
-        currentPromptData.value?.content ?? "";
+      currentPromptInitialMessages.value = JSON.stringify(messages);
 
       message.success(t("prompt.update.success"));
     }
@@ -143,18 +152,31 @@ export default defineComponent({
     async function deleteHandler(id: string) {
       if (currentPromptIndex.value?.id === id) {
         currentId.value = undefined;
-        currentPromptInitialContent.value = undefined;
+        currentPromptInitialMessages.value = undefined;
       }
       await api.deletePrompt(id);
       promptsMap.delete(id);
       reload();
     }
 
+    function addMessage() {
+      const messages = currentPromptData.value!.messages;
+      // Examples alternate between the user and the assistant
+      const role =
+        messages[messages.length - 1]?.role === "user" ? "assistant" : "user";
+      messages.push({ role, content: "" });
+    }
+
+    function removeMessage(index: number) {
+      currentPromptData.value!.messages.splice(index, 1);
+      updateHandler();
+    }
+
     async function selectHandler(id: string) {
       const promptData = await api.loadPrompt(id);
       promptsMap.set(id, promptData);
       currentId.value = id;
-      currentPromptInitialContent.value = promptData.content;
+      currentPromptInitialMessages.value = JSON.stringify(promptData.messages);
 
       const promptMetaData = prompts.value?.find((m) => m.id === id)!;
       currentId.value = promptMetaData.id;
@@ -215,19 +237,48 @@ export default defineComponent({
           >
             {currentPromptData.value ? (
               <NScrollbar class="h-full">
-                <textarea
-                  ref={promptRef}
-                  v-model={currentPromptData.value.content}
-                  class="p-4 h-full resize-none w-full rounded-lg outline-none placeholder-slate-500"
-                  style="color: var(--input-msg-color); background-color: var(--input-bg-color)"
-                  onFocusout={updateHandler}
-                  onInput={(e) =>
-                    autoGrowTextarea(e.target as HTMLTextAreaElement)
-                  }
-                  onFocus={(e) =>
-                    autoGrowTextarea(e.target as HTMLTextAreaElement)
-                  }
-                ></textarea>
+                {currentPromptData.value.messages.map((message, index) => (
+                  <div class="mb-4">
+                    <div class="flex items-center mb-1">
+                      <NSelect
+                        class="w-32"
+                        size="small"
+                        v-model:value={message.role}
+                        options={roleOptions.value}
+                        onUpdateValue={() => setTimeout(updateHandler)}
+                      ></NSelect>
+                      <span class="flex-1"></span>
+                      <NButton
+                        quaternary
+                        size="small"
+                        onClick={() => removeMessage(index)}
+                      >
+                        <NIcon>
+                          <TimesIcon />
+                        </NIcon>
+                      </NButton>
+                    </div>
+                    <textarea
+                      ref={index === 0 ? promptRef : undefined}
+                      v-model={message.content}
+                      class="p-4 resize-none w-full rounded-lg outline-none placeholder-slate-500"
+                      style="color: var(--input-msg-color); background-color: var(--input-bg-color)"
+                      onFocusout={updateHandler}
+                      onInput={(e) =>
+                        autoGrowTextarea(e.target as HTMLTextAreaElement)
+                      }
+                      onFocus={(e) =>
+                        autoGrowTextarea(e.target as HTMLTextAreaElement)
+                      }
+                    ></textarea>
+                  </div>
+                ))}
+                <NButton dashed block onClick={addMessage}>
+                  <NIcon class="mr-1">
+                    <PlusIcon />
+                  </NIcon>
+                  {t("prompt.addMessage")}
+                </NButton>
               </NScrollbar>
             ) : (
               <div class="h-full" data-tauri-drag-region></div>
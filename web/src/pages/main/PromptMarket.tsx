@@ -8,7 +8,7 @@ import {
   BagAdd as InstallIcon,
   InformationCircleOutline as InfoIcon,
 } from "@vicons/ionicons5";
-import { message } from "../../utils/prompt";
+import { message, promptVariables } from "../../utils/prompt";
 import { useTask } from "../../hooks/task";
 import { useRouter } from "vue-router";
 
@@ -101,7 +101,11 @@ export default defineComponent({
     }
 
     async function newChatHandler(prompt: api.MarketPrompt) {
-      const chatId = await api.installMarketPromptAndCreateChat(prompt);
+      const variables = await promptVariables(prompt.variables);
+      const chatId = await api.installMarketPromptAndCreateChat(
+        prompt,
+        variables
+      );
       router.push({
         name: "chat",
         query: {
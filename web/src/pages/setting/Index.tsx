@@ -117,6 +117,12 @@ export default defineComponent({
                     onUpdateValue={() => updateSettingHandler("forwardApiKey")}
                   ></NSwitch>
                 </NFormItem>
+                <NFormItem label={t("setting.autoTitle") + " :"}>
+                  <NSwitch
+                    v-model:value={model.value.autoTitle}
+                    onUpdateValue={() => updateSettingHandler("autoTitle")}
+                  ></NSwitch>
+                </NFormItem>
                 {isTauri ? (
                   <NFormItem label={t("setting.homePage") + " :"}>
                     <NSelect
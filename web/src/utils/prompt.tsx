@@ -6,6 +6,7 @@ import { NotificationApiInjection } from "naive-ui/es/notification/src/Notificat
 import { DialogApiInjection } from "naive-ui/es/dialog/src/DialogProvider";
 import { LoadingBarApiInjection } from "naive-ui/es/loading-bar/src/LoadingBarProvider";
 import { i18n } from "../hooks/i18n";
+import { PromptVariable } from "../api";
 
 let message!: MessageApiInjection;
 let notification!: NotificationApiInjection;
@@ -108,4 +109,22 @@ async function prompt(
   });
 }
 
-export { message, notification, dialog, loadingBar, prompt };
+/**
+ * Ask the value of each variable of a prompt, rejected if one is cancelled.
+ */
+async function promptVariables(variables: Array<PromptVariable>) {
+  const values: Record<string, string> = {};
+  for (const variable of variables) {
+    values[variable.name] = await prompt(
+      variable.description ||
+        t("prompt.inputVariableHint", { name: variable.name }),
+      {
+        defaultValue: variable.default,
+      }
+    );
+  }
+
+  return values;
+}
+
+export { message, notification, dialog, loadingBar, prompt, promptVariables };